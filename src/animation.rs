@@ -0,0 +1,213 @@
+use vec3::quaternion::Quaternion;
+use vec3::Vec3;
+
+/// How a track moves between its keyframes.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Interpolation {
+    /// In a straight line from each keyframe to the next, changing direction at keyframes.
+    Linear,
+    /// Along a Catmull-Rom spline through the keyframes, so motion does not jerk at them.
+    Smooth,
+}
+
+/// A value that can be blended with another of its kind.
+pub trait Interpolate: Copy {
+    /// `self` at `t = 0` and `other` at `t = 1`. `t` may lie outside 0 to 1, to extrapolate.
+    fn mix(self, other: Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn mix(self, other: f64, t: f64) -> f64 {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vec3<f64> {
+    fn mix(self, other: Vec3<f64>, t: f64) -> Vec3<f64> {
+        self + (other - self) * t
+    }
+}
+
+/// Turns the shorter way round, so keyframes should be less than half a turn apart.
+impl Interpolate for Quaternion<f64> {
+    fn mix(self, other: Quaternion<f64>, t: f64) -> Quaternion<f64> {
+        self.slerp(other, t)
+    }
+}
+
+/// One animated parameter: its values at some frames, and the way between them. Before the
+/// first keyframe and after the last the value holds still.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Track<V> {
+    keys: Vec<(f64, V)>,
+    interpolation: Interpolation,
+}
+
+impl<V: Interpolate> Track<V> {
+    /// Keyframes may be given in any order. Fails if there are none, or two share a frame.
+    pub fn new(mut keys: Vec<(f64, V)>, interpolation: Interpolation) -> Option<Track<V>> {
+        keys.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
+        if keys.is_empty() || keys.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return None;
+        }
+        Some(Track {
+            keys,
+            interpolation,
+        })
+    }
+
+    pub fn at(&self, frame: f64) -> V {
+        let keys = &self.keys;
+        let last = keys.len() - 1;
+        if frame <= keys[0].0 {
+            return keys[0].1;
+        }
+        if frame >= keys[last].0 {
+            return keys[last].1;
+        }
+        let i = keys.iter().rposition(|(key, _)| *key <= frame).unwrap();
+        let (t1, p1) = keys[i];
+        let (t2, p2) = keys[i + 1];
+        match self.interpolation {
+            Interpolation::Linear => p1.mix(p2, (frame - t1) / (t2 - t1)),
+            Interpolation::Smooth => {
+                // The ends carry on as they started: a phantom keyframe mirrors the neighbour.
+                let (t0, p0) = if i > 0 {
+                    keys[i - 1]
+                } else {
+                    (2.0 * t1 - t2, p2.mix(p1, 2.0))
+                };
+                let (t3, p3) = if i + 1 < last {
+                    keys[i + 2]
+                } else {
+                    (2.0 * t2 - t1, p1.mix(p2, 2.0))
+                };
+                catmull_rom([(t0, p0), (t1, p1), (t2, p2), (t3, p3)], frame)
+            }
+        }
+    }
+}
+
+/// The Barry and Goldman pyramid, which evaluates a Catmull-Rom spline with unevenly spaced
+/// keyframes by blending alone, so it works as well for rotations as for positions.
+fn catmull_rom<V: Interpolate>(keys: [(f64, V); 4], t: f64) -> V {
+    let blend = |(ta, a): (f64, V), (tb, b): (f64, V)| a.mix(b, (t - ta) / (tb - ta));
+    let [k0, k1, k2, k3] = keys;
+    let a1 = blend(k0, k1);
+    let a2 = blend(k1, k2);
+    let a3 = blend(k2, k3);
+    let b1 = blend((k0.0, a1), (k2.0, a2));
+    let b2 = blend((k1.0, a2), (k3.0, a3));
+    blend((k1.0, b1), (k2.0, b2))
+}
+
+/// Parses an inclusive range of frames such as `1-48`, or a single frame.
+pub fn parse_frame_range(text: &str) -> Option<(u32, u32)> {
+    let mut ends = text.splitn(2, '-');
+    let first = ends.next()?.trim().parse().ok()?;
+    let last = match ends.next() {
+        Some(last) => last.trim().parse().ok()?,
+        None => first,
+    };
+    Some((first, last)).filter(|(first, last)| first <= last)
+}
+
+/// `pattern` with its run of `#`s replaced by the frame number, padded with zeros to as many
+/// digits, e.g. `frame-####.png` becomes `frame-0007.png`. `None` if there is no `#`.
+pub fn frame_path(pattern: &str, frame: u32) -> Option<String> {
+    let start = pattern.find('#')?;
+    let width = pattern[start..]
+        .find(|c| c != '#')
+        .unwrap_or(pattern.len() - start);
+    Some(format!(
+        "{}{:0width$}{}",
+        &pattern[..start],
+        frame,
+        &pattern[start + width..],
+        width = width
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(interpolation: Interpolation) -> Track<f64> {
+        Track::new(
+            vec![(10.0, 4.0), (0.0, 0.0), (20.0, 0.0), (5.0, 1.0)],
+            interpolation,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_linear() {
+        let track = track(Interpolation::Linear);
+        assert_eq!(track.at(-3.0), 0.0);
+        assert_eq!(track.at(2.5), 0.5);
+        assert_eq!(track.at(7.5), 2.5);
+        assert_eq!(track.at(15.0), 2.0);
+        assert_eq!(track.at(25.0), 0.0);
+    }
+
+    #[test]
+    fn test_smooth_passes_through_keyframes_without_corners() {
+        let track = track(Interpolation::Smooth);
+        for &(frame, value) in &[(0.0, 0.0), (5.0, 1.0), (10.0, 4.0), (20.0, 0.0)] {
+            assert!((track.at(frame) - value).abs() < 1e-12);
+        }
+        // The slope is the same either side of a keyframe.
+        let h = 1e-4;
+        let before = (track.at(10.0) - track.at(10.0 - h)) / h;
+        let after = (track.at(10.0 + h) - track.at(10.0)) / h;
+        assert!((before - after).abs() < 1e-2, "{} {}", before, after);
+        // Evenly spaced keyframes on a line are followed at a steady rate.
+        let line = Track::new(
+            vec![(0.0, 0.0), (1.0, 2.0), (2.0, 4.0)],
+            Interpolation::Smooth,
+        )
+        .unwrap();
+        assert!((line.at(0.25) - 0.5).abs() < 1e-12);
+        assert!((line.at(1.5) - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_rotations() {
+        let axis = Vec3::new(0.0, 1.0, 0.0);
+        let track = Track::new(
+            vec![
+                (0.0, Quaternion::identity()),
+                (10.0, Quaternion::from_axis_angle(axis, 1.0)),
+                (20.0, Quaternion::from_axis_angle(axis, 2.0)),
+            ],
+            Interpolation::Smooth,
+        )
+        .unwrap();
+        let expected = Quaternion::from_axis_angle(axis, 1.5);
+        assert!(track.at(15.0).dot(expected) > 1.0 - 1e-12);
+    }
+
+    #[test]
+    fn test_invalid_tracks() {
+        assert_eq!(Track::<f64>::new(vec![], Interpolation::Linear), None);
+        assert_eq!(
+            Track::new(vec![(1.0, 0.0), (1.0, 2.0)], Interpolation::Linear),
+            None
+        );
+    }
+
+    #[test]
+    fn test_frame_ranges_and_paths() {
+        assert_eq!(parse_frame_range("1-48"), Some((1, 48)));
+        assert_eq!(parse_frame_range("7"), Some((7, 7)));
+        assert_eq!(parse_frame_range("9-3"), None);
+        assert_eq!(parse_frame_range("a-3"), None);
+        assert_eq!(
+            frame_path("out/frame-####.png", 7),
+            Some("out/frame-0007.png".to_string())
+        );
+        assert_eq!(frame_path("#.png", 123), Some("123.png".to_string()));
+        assert_eq!(frame_path("f##", 123), Some("f123".to_string()));
+        assert_eq!(frame_path("frame.png", 1), None);
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+use image::hdr::HDRDecoder;
+
+use background::exr;
+use sampling::Distribution2D;
+use vec3::{Float, Vec3};
+
+/// An equirectangular (latitude-longitude) image of the light arriving from every direction.
+/// The top row is straight up, and the middle of the image is straight ahead down -z.
+pub struct EnvironmentMap<T> {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3<T>>,
+    /// Radians about the y axis.
+    rotation: T,
+    intensity: T,
+    /// Picks pixels in proportion to the light they contribute.
+    distribution: Distribution2D,
+}
+
+impl<T: Float> EnvironmentMap<T> {
+    /// `pixels` run left to right, then top to bottom. `rotation` turns the map about the y axis
+    /// by that many degrees, and `intensity` scales its brightness.
+    pub fn new(
+        width: usize,
+        height: usize,
+        pixels: Vec<Vec3<T>>,
+        rotation: T,
+        intensity: T,
+    ) -> EnvironmentMap<T> {
+        // Rows near the poles are squeezed into smaller solid angles.
+        let weights: Vec<f64> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, pixel)| {
+                let pixel = pixel.to_f64();
+                let theta = std::f64::consts::PI * ((i / width) as f64 + 0.5) / height as f64;
+                (0.2126 * pixel.x + 0.7152 * pixel.y + 0.0722 * pixel.z) * theta.sin()
+            })
+            .collect();
+        EnvironmentMap {
+            width,
+            height,
+            distribution: Distribution2D::new(&weights, width),
+            pixels,
+            rotation: rotation * T::PI / T::from_f64(180.0),
+            intensity,
+        }
+    }
+
+    /// Loads a Radiance `.hdr` or OpenEXR `.exr` file.
+    pub fn load(path: &Path, rotation: T, intensity: T) -> Result<EnvironmentMap<T>, String> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        let (width, height, pixels) = match extension.as_deref() {
+            Some("hdr") => {
+                let file = File::open(path).map_err(|error| error.to_string())?;
+                let decoder =
+                    HDRDecoder::new(BufReader::new(file)).map_err(|error| error.to_string())?;
+                let metadata = decoder.metadata();
+                let pixels = decoder
+                    .read_image_hdr()
+                    .map_err(|error| error.to_string())?
+                    .iter()
+                    .map(|pixel| Vec3::new(pixel[0], pixel[1], pixel[2]))
+                    .collect();
+                (metadata.width as usize, metadata.height as usize, pixels)
+            }
+            Some("exr") => exr::decode(&fs::read(path).map_err(|error| error.to_string())?)?,
+            _ => return Err("expected a .hdr or .exr file".to_string()),
+        };
+        let pixels = pixels
+            .into_iter()
+            .map(|pixel: Vec3<f32>| Vec3::from_f64(pixel.to_f64()))
+            .collect();
+        Ok(EnvironmentMap::new(
+            width, height, pixels, rotation, intensity,
+        ))
+    }
+
+    pub fn radiance(&self, direction: Vec3<T>) -> Vec3<T> {
+        let (u, v) = self.direction_to_uv(direction);
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x] * self.intensity
+    }
+
+    /// Picks a direction, favouring bright parts of the map, from two uniform random numbers.
+    /// Returns it with the light from it and its density per steradian.
+    pub fn sample(&self, u1: T, u2: T) -> (Vec3<T>, Vec3<T>, T) {
+        let ((u, v), pdf) = self.distribution.sample(u1.to_f64(), u2.to_f64());
+        let direction = self.uv_to_direction(u, v);
+        (
+            direction,
+            self.radiance(direction),
+            T::from_f64(pdf_to_solid_angle(pdf, v)),
+        )
+    }
+
+    /// The density per steradian with which `sample` picks `direction`.
+    pub fn pdf(&self, direction: Vec3<T>) -> T {
+        let (u, v) = self.direction_to_uv(direction);
+        T::from_f64(pdf_to_solid_angle(self.distribution.pdf(u, v), v))
+    }
+
+    fn direction_to_uv(&self, direction: Vec3<T>) -> (f64, f64) {
+        let direction = direction.unit().to_f64();
+        let phi = direction.x.atan2(-direction.z) - self.rotation.to_f64();
+        let u = 0.5 + phi / (2.0 * std::f64::consts::PI);
+        (
+            u - u.floor(),
+            direction.y.clamp(-1.0, 1.0).acos() / std::f64::consts::PI,
+        )
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3<T> {
+        let phi = 2.0 * std::f64::consts::PI * (u - 0.5) + self.rotation.to_f64();
+        let theta = std::f64::consts::PI * v;
+        Vec3::from_f64(Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        ))
+    }
+}
+
+/// Converts a density over the image to one over directions: the image is stretched over 2π by
+/// π radians, and a row's pixels cover solid angle in proportion to `sin θ`.
+fn pdf_to_solid_angle(pdf: f64, v: f64) -> f64 {
+    let sin_theta = (std::f64::consts::PI * v).sin();
+    if sin_theta > 0.0 {
+        pdf / (2.0 * std::f64::consts::PI * std::f64::consts::PI * sin_theta)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::hdr::HDREncoder;
+    use image::Rgb;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::env;
+
+    /// A dim map with one bright pixel.
+    fn map(rotation: f64) -> EnvironmentMap<f64> {
+        let mut pixels = vec![Vec3::new(0.1, 0.1, 0.1); 8 * 4];
+        pixels[8 + 2] = Vec3::new(50.0, 40.0, 30.0);
+        EnvironmentMap::new(8, 4, pixels, rotation, 2.0)
+    }
+
+    #[test]
+    fn test_lookup() {
+        let environment = map(0.0);
+        assert_eq!(
+            environment.radiance(Vec3::new(0.0, 1.0, 0.0)),
+            Vec3::new(0.2, 0.2, 0.2)
+        );
+        // The bright pixel is up and to the left of straight ahead.
+        let direction = environment.uv_to_direction(2.5 / 8.0, 1.5 / 4.0);
+        assert!(direction.y > 0.0 && direction.x < 0.0 && direction.z < 0.0);
+        assert_eq!(
+            environment.radiance(direction),
+            Vec3::new(100.0, 80.0, 60.0)
+        );
+        let (u, v) = environment.direction_to_uv(direction);
+        assert!((u - 2.5 / 8.0).abs() < 1e-12 && (v - 1.5 / 4.0).abs() < 1e-12);
+
+        // Turning the map by 90 degrees brings the bright pixel round to the front.
+        let rotated = map(90.0);
+        let direction = rotated.uv_to_direction(2.5 / 8.0, 1.5 / 4.0);
+        assert!(direction.z < 0.0 && direction.x.abs() < 0.4);
+        assert_eq!(rotated.radiance(direction), Vec3::new(100.0, 80.0, 60.0));
+    }
+
+    #[test]
+    fn test_importance_sampling() {
+        let environment = map(30.0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let samples = 20_000;
+        let mut bright = 0;
+        let mut estimate = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            let (direction, radiance, pdf) = environment.sample(rng.gen(), rng.gen());
+            assert!((pdf - environment.pdf(direction)).abs() < 1e-9 * pdf);
+            if radiance.x > 1.0 {
+                bright += 1;
+            }
+            estimate = estimate + radiance / pdf;
+        }
+        // Most samples land on the bright pixel.
+        assert!(bright > samples * 9 / 10, "{}", bright);
+        // The estimate of the total light agrees with summing pixels over their solid angles.
+        let mut total = Vec3::new(0.0, 0.0, 0.0);
+        for y in 0..4 {
+            let solid_angle = 2.0 * std::f64::consts::PI / 8.0
+                * ((std::f64::consts::PI * y as f64 / 4.0).cos()
+                    - (std::f64::consts::PI * (y + 1) as f64 / 4.0).cos());
+            for x in 0..8 {
+                total = total + environment.pixels[y * 8 + x] * 2.0 * solid_angle;
+            }
+        }
+        let estimate = estimate / samples as f64;
+        assert!(
+            (estimate.x - total.x).abs() < 0.05 * total.x,
+            "{} {}",
+            estimate,
+            total
+        );
+    }
+
+    #[test]
+    fn test_load_hdr() {
+        let path = env::temp_dir().join("rustray_test_environment.hdr");
+        let pixels: Vec<Rgb<f32>> = (0..6).map(|i| Rgb([i as f32, 0.5, 4.0])).collect();
+        HDREncoder::new(File::create(&path).unwrap())
+            .encode(&pixels, 3, 2)
+            .unwrap();
+        let map: EnvironmentMap<f64> = EnvironmentMap::load(&path, 0.0, 1.0).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((map.width, map.height), (3, 2));
+        assert_eq!(map.pixels[5], Vec3::new(5.0, 0.5, 4.0));
+        assert_eq!(
+            EnvironmentMap::<f64>::load(Path::new("sky.png"), 0.0, 1.0).err(),
+            Some("expected a .hdr or .exr file".to_string())
+        );
+    }
+}
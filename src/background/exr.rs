@@ -0,0 +1,469 @@
+extern crate inflate;
+
+use vec3::Vec3;
+
+/// Bits of the version field for features other than single part scanline images.
+const TILED: u32 = 0x200;
+const DEEP: u32 = 0x800;
+const MULTIPART: u32 = 0x1000;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Compression {
+    None,
+    Rle,
+    /// Zlib, one scanline at a time.
+    Zips,
+    /// Zlib, sixteen scanlines at a time.
+    Zip,
+}
+
+impl Compression {
+    fn lines_per_chunk(self) -> usize {
+        match self {
+            Compression::Zip => 16,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum PixelType {
+    Uint,
+    Half,
+    Float,
+}
+
+impl PixelType {
+    fn size(self) -> usize {
+        match self {
+            PixelType::Half => 2,
+            _ => 4,
+        }
+    }
+}
+
+struct Channel {
+    name: String,
+    pixel_type: PixelType,
+}
+
+/// Decodes an OpenEXR image into its width, height and pixels, top row first. Only the common
+/// case of a single part scanline image, uncompressed or RLE or zip compressed, is supported;
+/// colour comes from the R, G and B channels, or a greyscale Y channel.
+pub fn decode(bytes: &[u8]) -> Result<(usize, usize, Vec<Vec3<f32>>), String> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.u32()? != 20_000_630 {
+        return Err("not an OpenEXR file".to_string());
+    }
+    let version = reader.u32()?;
+    if version & TILED != 0 {
+        return Err("tiled OpenEXR images are not supported".to_string());
+    }
+    if version & (DEEP | MULTIPART) != 0 {
+        return Err("deep and multi-part OpenEXR images are not supported".to_string());
+    }
+
+    let mut channels = None;
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = reader.string()?;
+        if name.is_empty() {
+            break;
+        }
+        let attribute_type = reader.string()?;
+        let size = reader.u32()? as usize;
+        let mut value = Reader {
+            bytes: reader.take(size)?,
+            position: 0,
+        };
+        match (name.as_str(), attribute_type.as_str()) {
+            ("channels", "chlist") => channels = Some(read_channels(&mut value)?),
+            ("compression", "compression") => {
+                compression = Some(match value.u8()? {
+                    0 => Compression::None,
+                    1 => Compression::Rle,
+                    2 => Compression::Zips,
+                    3 => Compression::Zip,
+                    other => {
+                        return Err(format!(
+                            "OpenEXR compression method {} is not supported; save as \
+                             uncompressed, RLE or zip",
+                            other
+                        ))
+                    }
+                })
+            }
+            ("dataWindow", "box2i") => {
+                let x_min = value.i32()?;
+                let y_min = value.i32()?;
+                let x_max = value.i32()?;
+                let y_max = value.i32()?;
+                if x_max < x_min || y_max < y_min {
+                    return Err("empty OpenEXR data window".to_string());
+                }
+                data_window = Some((
+                    (x_max - x_min) as usize + 1,
+                    (y_max - y_min) as usize + 1,
+                    y_min,
+                ))
+            }
+            _ => {}
+        }
+    }
+    let channels = channels.ok_or("missing OpenEXR channels")?;
+    let compression = compression.ok_or("missing OpenEXR compression")?;
+    let (width, height, y_min) = data_window.ok_or("missing OpenEXR data window")?;
+
+    let find = |name: &str| channels.iter().position(|channel| channel.name == name);
+    let rgb = match (find("R"), find("G"), find("B"), find("Y")) {
+        (Some(r), Some(g), Some(b), _) => [r, g, b],
+        (_, _, _, Some(y)) => [y, y, y],
+        _ => return Err("OpenEXR image has neither R, G and B nor Y channels".to_string()),
+    };
+    let line_size: usize = channels
+        .iter()
+        .map(|channel| channel.pixel_type.size() * width)
+        .sum();
+
+    let lines_per_chunk = compression.lines_per_chunk();
+    let chunks = height.div_ceil(lines_per_chunk);
+    let mut pixels = vec![Vec3::new(0.0, 0.0, 0.0); width * height];
+    for _ in 0..chunks {
+        let offset = reader.u64()? as usize;
+        let mut chunk = Reader {
+            bytes: bytes
+                .get(offset..)
+                .ok_or("OpenEXR chunk offset out of range")?,
+            position: 0,
+        };
+        let first_line = (chunk.i32()? - y_min) as usize;
+        let lines = lines_per_chunk.min(height.saturating_sub(first_line));
+        let size = chunk.u32()? as usize;
+        let data = chunk.take(size)?;
+        let expected = line_size * lines;
+        let data = if size == expected {
+            // Chunks that would not shrink are stored as they are.
+            data.to_vec()
+        } else {
+            let compressed = match compression {
+                Compression::None => return Err("OpenEXR chunk has the wrong size".to_string()),
+                Compression::Rle => decode_run_lengths(data)?,
+                Compression::Zips | Compression::Zip => inflate::inflate_bytes_zlib(data)
+                    .map_err(|error| format!("corrupt OpenEXR zip data: {}", error))?,
+            };
+            unpredict(compressed)
+        };
+        if data.len() != expected {
+            return Err("OpenEXR chunk has the wrong size".to_string());
+        }
+
+        for (line, data) in data.chunks(line_size).enumerate() {
+            // Each line holds every value of the first channel, then of the second, and so on.
+            let mut values = vec![[0.0; 3]; width];
+            let mut channel_start = 0;
+            for (index, channel) in channels.iter().enumerate() {
+                let size = channel.pixel_type.size();
+                for component in (0..3).filter(|&component| rgb[component] == index) {
+                    for (x, value) in values.iter_mut().enumerate() {
+                        let start = channel_start + x * size;
+                        value[component] =
+                            read_value(channel.pixel_type, &data[start..start + size]);
+                    }
+                }
+                channel_start += size * width;
+            }
+            let row = (first_line + line) * width;
+            for (x, value) in values.iter().enumerate() {
+                pixels[row + x] = Vec3::new(value[0], value[1], value[2]);
+            }
+        }
+    }
+    Ok((width, height, pixels))
+}
+
+fn read_channels(reader: &mut Reader) -> Result<Vec<Channel>, String> {
+    let mut channels = vec![];
+    loop {
+        let name = reader.string()?;
+        if name.is_empty() {
+            return Ok(channels);
+        }
+        let pixel_type = match reader.u32()? {
+            0 => PixelType::Uint,
+            1 => PixelType::Half,
+            2 => PixelType::Float,
+            other => return Err(format!("unknown OpenEXR pixel type {}", other)),
+        };
+        // Linear flag and padding.
+        reader.take(4)?;
+        if reader.i32()? != 1 || reader.i32()? != 1 {
+            return Err("subsampled OpenEXR channels are not supported".to_string());
+        }
+        channels.push(Channel { name, pixel_type });
+    }
+}
+
+fn read_value(pixel_type: PixelType, bytes: &[u8]) -> f32 {
+    match pixel_type {
+        PixelType::Half => half_to_f32(u16::from(bytes[0]) | u16::from(bytes[1]) << 8),
+        PixelType::Float => f32::from_bits(little_endian_u32(bytes)),
+        PixelType::Uint => little_endian_u32(bytes) as f32,
+    }
+}
+
+fn little_endian_u32(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .take(4)
+        .rev()
+        .fold(0, |value, &byte| value << 8 | u32::from(byte))
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from(half >> 10 & 0x1f);
+    let mantissa = f32::from(half & 0x3ff);
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Expands OpenEXR's run length encoding: a negative count introduces that many literal bytes,
+/// and any other count repeats the following byte one more time than it says.
+fn decode_run_lengths(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut output = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let count = data[i] as i8;
+        i += 1;
+        if count < 0 {
+            let literal = data
+                .get(i..i + (-i32::from(count)) as usize)
+                .ok_or("corrupt OpenEXR RLE data")?;
+            output.extend_from_slice(literal);
+            i += literal.len();
+        } else {
+            let byte = *data.get(i).ok_or("corrupt OpenEXR RLE data")?;
+            output.extend(std::iter::repeat_n(byte, count as usize + 1));
+            i += 1;
+        }
+    }
+    Ok(output)
+}
+
+/// Undoes the preprocessing that helps RLE and zip compress: each byte is stored as its
+/// difference from the one before, and the even and odd bytes are split into two halves.
+fn unpredict(mut data: Vec<u8>) -> Vec<u8> {
+    for i in 1..data.len() {
+        data[i] = data[i - 1].wrapping_add(data[i]).wrapping_sub(128);
+    }
+    let half = data.len().div_ceil(2);
+    (0..data.len())
+        .map(|i| {
+            if i % 2 == 0 {
+                data[i / 2]
+            } else {
+                data[half + i / 2]
+            }
+        })
+        .collect()
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + size)
+            .ok_or("unexpected end of OpenEXR file")?;
+        self.position += size;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(little_endian_u32(self.take(4)?))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(self.u32()? as i32)
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let low = u64::from(self.u32()?);
+        let high = u64::from(self.u32()?);
+        Ok(high << 32 | low)
+    }
+
+    /// A null terminated string.
+    fn string(&mut self) -> Result<String, String> {
+        let length = self.bytes[self.position..]
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or("unexpected end of OpenEXR file")?;
+        let string = String::from_utf8_lossy(self.take(length)?).into_owned();
+        self.take(1)?;
+        Ok(string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attribute(bytes: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(attribute_type.as_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(value);
+    }
+
+    /// A 2x2 image with half float B, G and R channels and the given compression, whose chunks
+    /// are made from each line's channel data by `encode`.
+    fn image(compression: u8, encode: &dyn Fn(Vec<u8>) -> Vec<u8>) -> Vec<u8> {
+        let mut bytes = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+        let mut channels = vec![];
+        for name in &["B", "G", "R"] {
+            channels.extend_from_slice(name.as_bytes());
+            channels.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        }
+        channels.push(0);
+        attribute(&mut bytes, "channels", "chlist", &channels);
+        attribute(&mut bytes, "compression", "compression", &[compression]);
+        let window: Vec<u8> = [0i32, 0, 1, 1]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+        attribute(&mut bytes, "dataWindow", "box2i", &window);
+        attribute(&mut bytes, "lineOrder", "lineOrder", &[0]);
+        bytes.push(0);
+
+        // Halves: 1.0, 0.5, 2.0 and 0.0, one pixel of each channel per value.
+        let line = |y: usize| -> Vec<u8> {
+            let halves: [[u16; 2]; 3] = if y == 0 {
+                [[0x3c00, 0x3800], [0x4000, 0x0000], [0x3800, 0x3c00]]
+            } else {
+                [[0x0000, 0x0000], [0x3c00, 0x3c00], [0x4000, 0x4000]]
+            };
+            halves
+                .iter()
+                .flat_map(|channel| channel.iter().flat_map(|h| h.to_le_bytes().to_vec()))
+                .collect()
+        };
+        let chunks: Vec<(i32, Vec<u8>)> = if compression == 3 {
+            vec![(0, encode([line(0), line(1)].concat()))]
+        } else {
+            vec![(0, encode(line(0))), (1, encode(line(1)))]
+        };
+        let mut offset = bytes.len() + 8 * chunks.len();
+        for (_, data) in &chunks {
+            bytes.extend_from_slice(&(offset as u64).to_le_bytes());
+            offset += 8 + data.len();
+        }
+        for (y, data) in chunks {
+            bytes.extend_from_slice(&y.to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&data);
+        }
+        bytes
+    }
+
+    /// The inverse of `unpredict`, then a zlib stream of one uncompressed block.
+    fn zip(data: Vec<u8>) -> Vec<u8> {
+        let half = data.len().div_ceil(2);
+        let mut split = vec![0; data.len()];
+        for (i, &byte) in data.iter().enumerate() {
+            split[if i % 2 == 0 { i / 2 } else { half + i / 2 }] = byte;
+        }
+        let mut predicted = split.clone();
+        for i in 1..split.len() {
+            predicted[i] = split[i].wrapping_sub(split[i - 1]).wrapping_add(128);
+        }
+        let length = predicted.len() as u16;
+        let mut stream = vec![0x78, 0x01, 0x01];
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(&predicted);
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in &predicted {
+            a = (a + u32::from(byte)) % 65521;
+            b = (b + a) % 65521;
+        }
+        stream.extend_from_slice(&(b << 16 | a).to_be_bytes());
+        stream
+    }
+
+    fn expected() -> Vec<Vec3<f32>> {
+        vec![
+            Vec3::new(0.5, 2.0, 1.0),
+            Vec3::new(1.0, 0.0, 0.5),
+            Vec3::new(2.0, 1.0, 0.0),
+            Vec3::new(2.0, 1.0, 0.0),
+        ]
+    }
+
+    #[test]
+    fn test_decode() {
+        for &compression in &[0, 2, 3] {
+            let encode: &dyn Fn(Vec<u8>) -> Vec<u8> =
+                if compression == 0 { &|data| data } else { &zip };
+            assert_eq!(
+                decode(&image(compression, encode)),
+                Ok((2, 2, expected())),
+                "compression {}",
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_lengths() {
+        assert_eq!(
+            decode_run_lengths(&[2, 7, 0xfe, 1, 2]),
+            Ok(vec![7, 7, 7, 1, 2])
+        );
+        assert!(decode_run_lengths(&[0xfd, 1]).is_err());
+    }
+
+    #[test]
+    fn test_half_to_f32() {
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+        assert!(half_to_f32(0x7c00).is_infinite());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut tiled = image(0, &|data| data);
+        tiled[5] = 0x02;
+        assert_eq!(
+            decode(&tiled),
+            Err("tiled OpenEXR images are not supported".to_string())
+        );
+        let mut piz = image(0, &|data| data);
+        let position = piz.windows(12).position(|w| w == b"compression\0").unwrap();
+        piz[position + 12 + 12 + 4] = 4;
+        assert!(decode(&piz)
+            .unwrap_err()
+            .starts_with("OpenEXR compression method 4"));
+        assert_eq!(
+            decode(b"P6 not an exr"),
+            Err("not an OpenEXR file".to_string())
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use vec3::{Float, Vec3};
+
+use self::environment_map::EnvironmentMap;
+use self::sky::Sky;
+
+pub mod environment_map;
+mod exr;
+pub mod sky;
+
+/// The light arriving from far away, seen by rays that leave the scene without hitting
+/// anything.
+pub enum Background<T> {
+    Solid(Vec3<T>),
+    /// Blends from `bottom`, looking straight down, to `top`, looking straight up.
+    Gradient {
+        top: Vec3<T>,
+        bottom: Vec3<T>,
+    },
+    Map(EnvironmentMap<T>),
+    Sky(Sky<T>),
+}
+
+impl<T: Float> Default for Background<T> {
+    /// White fading to sky blue.
+    fn default() -> Background<T> {
+        Background::Gradient {
+            top: Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0)),
+            bottom: Vec3::from_f64(Vec3::new(0.5, 0.7, 1.0)),
+        }
+    }
+}
+
+impl<T: Float> Background<T> {
+    pub fn radiance(&self, direction: Vec3<T>) -> Vec3<T> {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { top, bottom } => {
+                let t = T::from_f64(0.5) * (direction.unit().y() + T::from_f64(1.0));
+                *top * t + *bottom * (T::from_f64(1.0) - t)
+            }
+            Background::Map(map) => map.radiance(direction),
+            Background::Sky(sky) => sky.radiance(direction),
+        }
+    }
+
+    /// Picks a direction to look for light in from two uniform random numbers, for backgrounds
+    /// bright enough in places to be worth aiming at. Returns the direction, the light from it
+    /// and its density per steradian.
+    pub fn sample(&self, u1: T, u2: T) -> Option<(Vec3<T>, Vec3<T>, T)> {
+        match self {
+            Background::Map(map) => Some(map.sample(u1, u2)),
+            _ => None,
+        }
+    }
+
+    /// The density per steradian with which `sample` picks `direction`.
+    pub fn pdf(&self, direction: Vec3<T>) -> T {
+        match self {
+            Background::Map(map) => map.pdf(direction),
+            _ => T::from_f64(0.0),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use spectrum::xyz_to_rgb;
+use vec3::{Float, Vec3};
+
+/// Converts the model's luminance, in thousands of candela per square metre, to the scale of
+/// the rest of the renderer: with the sun 60° up in a clear sky, the zenith is about as bright
+/// as a white surface.
+const LUMINANCE_SCALE: f64 = 0.1;
+
+/// The Preetham analytic daylight model: the colour of a clear sky lit by the sun, varying with
+/// the sun's direction and the haziness of the air. See Preetham, Shirley and Smits, "A
+/// Practical Analytic Model for Daylight" (1999). The sun's disc itself is not included.
+pub struct Sky<T> {
+    sun_direction: Vec3<T>,
+    /// The Perez distribution coefficients A to E for each of Y, x and y.
+    coefficients: [[f64; 5]; 3],
+    /// Y, x and y at the zenith, divided by the Perez function there so the distribution can
+    /// be scaled directly.
+    zenith: [f64; 3],
+    intensity: T,
+}
+
+impl<T: Float> Sky<T> {
+    /// `turbidity` runs from about 2 for a very clear sky to 10 for a hazy one.
+    pub fn new(sun_direction: Vec3<T>, turbidity: T, intensity: T) -> Sky<T> {
+        let sun_direction = sun_direction.unit();
+        let t = turbidity.to_f64();
+        let coefficients = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        // The sun is kept at or above the horizon, where the model holds.
+        let theta_s = sun_direction.y().to_f64().clamp(0.0, 1.0).acos();
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f64::consts::PI - 2.0 * theta_s);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |rows: [[f64; 4]; 3]| {
+            let cubic = |c: [f64; 4]| ((c[0] * theta_s + c[1]) * theta_s + c[2]) * theta_s + c[3];
+            t * t * cubic(rows[0]) + t * cubic(rows[1]) + cubic(rows[2])
+        };
+        let x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        let mut zenith = [luminance, x, y];
+        for (value, coefficients) in zenith.iter_mut().zip(coefficients.iter()) {
+            *value /= perez(coefficients, 1.0, theta_s);
+        }
+        Sky {
+            sun_direction,
+            coefficients,
+            zenith,
+            intensity,
+        }
+    }
+
+    /// The sky's colour in linear sRGB. Below the horizon, the horizon's colour.
+    pub fn radiance(&self, direction: Vec3<T>) -> Vec3<T> {
+        let zero = T::from_f64(0.0);
+        let direction = if direction.y() < zero {
+            Vec3::new(direction.x(), zero, direction.z()).unit()
+        } else {
+            direction.unit()
+        };
+        let cos_theta = direction.y().to_f64().max(1e-3);
+        let gamma = direction
+            .dot(self.sun_direction)
+            .to_f64()
+            .clamp(-1.0, 1.0)
+            .acos();
+        let value = |i: usize| self.zenith[i] * perez(&self.coefficients[i], cos_theta, gamma);
+        let (luminance, x, y) = (value(0) * LUMINANCE_SCALE, value(1), value(2));
+        let rgb = xyz_to_rgb((x / y * luminance, luminance, (1.0 - x - y) / y * luminance));
+        Vec3::from_f64(Vec3::new(rgb[0].max(0.0), rgb[1].max(0.0), rgb[2].max(0.0)))
+            * self.intensity
+    }
+}
+
+/// The Perez sky luminance distribution, for a direction at angle θ from the zenith and γ from
+/// the sun.
+fn perez(c: &[f64; 5], cos_theta: f64, gamma: f64) -> f64 {
+    (1.0 + c[0] * (c[1] / cos_theta).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos() * gamma.cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sky() {
+        let sky = Sky::new(Vec3::new(0.0, 3f64.sqrt(), -1.0), 3.0, 1.0);
+        let zenith = sky.radiance(Vec3::new(0.0, 1.0, 0.0));
+        assert!(zenith.y > 0.7 && zenith.y < 1.3, "{}", zenith);
+        // A clear sky is blue.
+        assert!(zenith.z > zenith.x, "{}", zenith);
+        // Brighter towards the sun than away from it.
+        let towards = sky.radiance(Vec3::new(0.0, 1.0, -1.0));
+        let away = sky.radiance(Vec3::new(0.0, 1.0, 1.0));
+        assert!(towards.y > 2.0 * away.y, "{} {}", towards, away);
+        // Below the horizon is the horizon.
+        assert_eq!(
+            sky.radiance(Vec3::new(1.0, -0.5, 0.0)),
+            sky.radiance(Vec3::new(1.0, -1e-6, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_sunset_is_dimmer_and_redder() {
+        let noon: Sky<f64> = Sky::new(Vec3::new(0.0, 1.0, -0.2), 3.0, 1.0);
+        let sunset: Sky<f64> = Sky::new(Vec3::new(0.0, 0.05, -1.0), 3.0, 1.0);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        assert!(sunset.radiance(up).y < 0.5 * noon.radiance(up).y);
+        let direction = Vec3::new(0.0, 0.3, -1.0);
+        let (noon, sunset) = (noon.radiance(direction), sunset.radiance(direction));
+        assert!(sunset.x / sunset.z > noon.x / noon.z, "{} {}", noon, sunset);
+    }
+}
@@ -0,0 +1,429 @@
+use std::ops::Range;
+
+use ray::Ray;
+#[cfg(target_arch = "x86_64")]
+use simd::{F32x4, F32x8};
+use simd::{
+    Lanes, PackedBox, PackedSphere, PacketHits, PacketLanes, Portable4, RayPacket, SimdLevel,
+};
+use vec3::aabb::Aabb;
+use vec3::{Float, Vec3};
+
+/// Leaves hold at most this many objects, unless the objects cannot be told apart.
+const MAX_LEAF_SIZE: usize = 4;
+/// Candidate split planes per axis when building.
+const BUCKETS: usize = 12;
+/// Deeper than this everything left becomes a leaf, so traversal's stack cannot overflow.
+const MAX_DEPTH: usize = 60;
+/// The cost of visiting an interior node relative to testing an object.
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// A bounding volume hierarchy over objects' boxes, so a ray need only be tested against the
+/// objects whose boxes it passes through. Built top down, splitting where the surface area
+/// heuristic predicts the cheapest traversal; see Physically Based Rendering (3rd edition),
+/// section 4.3. The boxes are of objects over their whole motion, so one hierarchy serves rays
+/// at any time.
+pub struct Bvh<T> {
+    /// Depth first, each interior node followed by its first child.
+    nodes: Vec<Node<T>>,
+    /// The objects' indices, those of each leaf together.
+    indices: Vec<usize>,
+}
+
+struct Node<T> {
+    bounds: Aabb<T>,
+    /// `bounds` for the packet traversal.
+    packed: PackedBox,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Leaf {
+        start: usize,
+        count: usize,
+    },
+    /// The first child follows the node; `axis` is the one the children were split along.
+    Interior {
+        second_child: usize,
+        axis: usize,
+    },
+}
+
+impl<T: Float> Bvh<T> {
+    /// Builds a hierarchy over the objects with the given indices and boxes.
+    pub fn new(mut objects: Vec<(usize, Aabb<T>)>) -> Bvh<T> {
+        let mut bvh = Bvh {
+            nodes: vec![],
+            indices: Vec::with_capacity(objects.len()),
+        };
+        if !objects.is_empty() {
+            bvh.build(&mut objects, 0);
+        }
+        bvh
+    }
+
+    fn build(&mut self, objects: &mut [(usize, Aabb<T>)], depth: usize) {
+        let bounds = objects
+            .iter()
+            .skip(1)
+            .fold(objects[0].1, |bounds, (_, aabb)| bounds.union(*aabb));
+        let split = if objects.len() > 1 && depth < MAX_DEPTH {
+            split(objects, bounds)
+        } else {
+            None
+        };
+        match split {
+            Some((axis, middle)) => {
+                let node = self.nodes.len();
+                self.nodes.push(Node {
+                    bounds,
+                    packed: PackedBox::new(&bounds),
+                    kind: NodeKind::Leaf { start: 0, count: 0 },
+                });
+                let (first, second) = objects.split_at_mut(middle);
+                self.build(first, depth + 1);
+                let second_child = self.nodes.len();
+                self.build(second, depth + 1);
+                self.nodes[node].kind = NodeKind::Interior { second_child, axis };
+            }
+            None => {
+                self.nodes.push(Node {
+                    bounds,
+                    packed: PackedBox::new(&bounds),
+                    kind: NodeKind::Leaf {
+                        start: self.indices.len(),
+                        count: objects.len(),
+                    },
+                });
+                self.indices.extend(objects.iter().map(|(index, _)| *index));
+            }
+        }
+    }
+
+    /// Calls `visit` with the index of each object whose box `ray` passes through between
+    /// `min_t` and `max_t`, and the current `max_t`, nearer boxes first. `visit` returns the
+    /// new `max_t`, e.g. the distance to a hit so farther boxes are skipped, or `None` to stop.
+    pub fn traverse<F>(&self, ray: &Ray<T>, min_t: T, mut max_t: T, mut visit: F)
+    where
+        F: FnMut(usize, T) -> Option<T>,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let direction = *ray.direction();
+        let mut stack = [0; MAX_DEPTH + 1];
+        let mut stacked = 0;
+        let mut node = 0;
+        loop {
+            let current = &self.nodes[node];
+            if current.bounds.hit(ray, min_t, max_t).is_some() {
+                match current.kind {
+                    NodeKind::Leaf { start, count } => {
+                        for &index in &self.indices[start..start + count] {
+                            match visit(index, max_t) {
+                                Some(t) => max_t = t,
+                                None => return,
+                            }
+                        }
+                    }
+                    NodeKind::Interior { second_child, axis } => {
+                        // Visit the child on the side the ray comes from first.
+                        let (near, far) = if component(direction, axis) < T::from_f64(0.0) {
+                            (second_child, node + 1)
+                        } else {
+                            (node + 1, second_child)
+                        };
+                        stack[stacked] = far;
+                        stacked += 1;
+                        node = near;
+                        continue;
+                    }
+                }
+            }
+            if stacked == 0 {
+                return;
+            }
+            stacked -= 1;
+            node = stack[stacked];
+        }
+    }
+
+    /// Finds the nearest hit of each ray in `packet`, beyond `min_t` and nearer than the hit
+    /// already in `hits`, and records it there. The lanes are tested against each box together,
+    /// so a node is visited once for the whole packet, and against the objects' single precision
+    /// `spheres`, by object index, with the SIMD kernels. Objects without one are handed to
+    /// `visit` with the lanes to try them against.
+    pub fn traverse_packet<F>(
+        &self,
+        level: SimdLevel,
+        packet: &RayPacket,
+        spheres: &[Option<PackedSphere>],
+        min_t: f32,
+        hits: &mut PacketHits,
+        mut visit: F,
+    ) where
+        F: FnMut(usize, Range<usize>, &mut PacketHits),
+    {
+        match level {
+            // The AVX kernel may only run where the CPU has AVX, whichever level was asked for.
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Avx if is_x86_feature_detected!("avx") => unsafe {
+                self.traverse_packet_avx(packet, spheres, min_t, hits, &mut visit)
+            },
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Avx | SimdLevel::Sse => {
+                for first_lane in (0..packet.len).step_by(4) {
+                    let lanes = PacketLanes::<F32x4>::load(packet, first_lane, min_t);
+                    self.traverse_lanes(packet, lanes, spheres, hits, &mut visit);
+                }
+            }
+            _ => {
+                for first_lane in (0..packet.len).step_by(4) {
+                    let lanes = PacketLanes::<Portable4>::load(packet, first_lane, min_t);
+                    self.traverse_lanes(packet, lanes, spheres, hits, &mut visit);
+                }
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx")]
+    unsafe fn traverse_packet_avx<F>(
+        &self,
+        packet: &RayPacket,
+        spheres: &[Option<PackedSphere>],
+        min_t: f32,
+        hits: &mut PacketHits,
+        visit: &mut F,
+    ) where
+        F: FnMut(usize, Range<usize>, &mut PacketHits),
+    {
+        let lanes = PacketLanes::<F32x8>::load(packet, 0, min_t);
+        self.traverse_lanes(packet, lanes, spheres, hits, visit);
+    }
+
+    /// `traverse`, with the nearer child chosen by the direction of the first ray of `lanes`.
+    #[inline(always)]
+    fn traverse_lanes<L: Lanes, F>(
+        &self,
+        packet: &RayPacket,
+        lanes: PacketLanes<L>,
+        spheres: &[Option<PackedSphere>],
+        hits: &mut PacketHits,
+        visit: &mut F,
+    ) where
+        F: FnMut(usize, Range<usize>, &mut PacketHits),
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let first = lanes.first_lane;
+        let mut stack = [0; MAX_DEPTH + 1];
+        let mut stacked = 0;
+        let mut node = 0;
+        loop {
+            let current = &self.nodes[node];
+            if lanes.hit_box(&current.packed, hits) {
+                match current.kind {
+                    NodeKind::Leaf { start, count } => {
+                        for &index in &self.indices[start..start + count] {
+                            match spheres[index] {
+                                Some(ref sphere) => lanes.hit_sphere(sphere, index, hits),
+                                None => visit(index, lanes.lanes(), hits),
+                            }
+                        }
+                    }
+                    NodeKind::Interior { second_child, axis } => {
+                        let (near, far) = if packet.direction[axis][first] < 0.0 {
+                            (second_child, node + 1)
+                        } else {
+                            (node + 1, second_child)
+                        };
+                        stack[stacked] = far;
+                        stacked += 1;
+                        node = near;
+                        continue;
+                    }
+                }
+            }
+            if stacked == 0 {
+                return;
+            }
+            stacked -= 1;
+            node = stack[stacked];
+        }
+    }
+}
+
+/// Picks the cheapest split of `objects`, if any is cheaper than a leaf, and partitions them
+/// around it. Returns the axis and the number of objects in the first half.
+fn split<T: Float>(objects: &mut [(usize, Aabb<T>)], bounds: Aabb<T>) -> Option<(usize, usize)> {
+    let centroids = objects.iter().skip(1).fold(
+        point_box(objects[0].1.centroid()),
+        |centroids, (_, aabb)| centroids.union(point_box(aabb.centroid())),
+    );
+    let axis = centroids.longest_axis();
+    let low = component(centroids.min, axis).to_f64();
+    let extent = component(centroids.max, axis).to_f64() - low;
+    if extent <= 0.0 {
+        // Every centroid is in the same place, so no plane separates them.
+        return None;
+    }
+    let bucket = |aabb: &Aabb<T>| {
+        let offset = (component(aabb.centroid(), axis).to_f64() - low) / extent;
+        ((offset * BUCKETS as f64) as usize).min(BUCKETS - 1)
+    };
+    let mut buckets: Vec<Option<(usize, Aabb<T>)>> = vec![None; BUCKETS];
+    for (_, aabb) in objects.iter() {
+        let slot = &mut buckets[bucket(aabb)];
+        *slot = Some(match *slot {
+            Some((count, bounds)) => (count + 1, bounds.union(*aabb)),
+            None => (1, *aabb),
+        });
+    }
+
+    // The expected cost of splitting after each bucket, from the area of each side.
+    // Flat boxes, e.g. of points on a line, have no area to divide by.
+    let area = bounds.surface_area().to_f64().max(f64::MIN_POSITIVE);
+    let sweep = |buckets: &mut dyn Iterator<Item = &Option<(usize, Aabb<T>)>>| {
+        let mut side: Option<(usize, Aabb<T>)> = None;
+        buckets
+            .map(|bucket| {
+                if let Some((count, bounds)) = *bucket {
+                    side = Some(match side {
+                        Some((side_count, side_bounds)) => {
+                            (side_count + count, side_bounds.union(bounds))
+                        }
+                        None => (count, bounds),
+                    });
+                }
+                side.map_or(0.0, |(count, bounds)| {
+                    count as f64 * bounds.surface_area().to_f64()
+                })
+            })
+            .collect::<Vec<f64>>()
+    };
+    let below = sweep(&mut buckets.iter());
+    let mut above = sweep(&mut buckets.iter().rev());
+    above.reverse();
+    let (best, cost) = (0..BUCKETS - 1)
+        .map(|i| (i, TRAVERSAL_COST + (below[i] + above[i + 1]) / area))
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())?;
+    if objects.len() <= MAX_LEAF_SIZE && cost >= objects.len() as f64 {
+        return None;
+    }
+
+    let mut middle = 0;
+    for i in 0..objects.len() {
+        if bucket(&objects[i].1) <= best {
+            objects.swap(i, middle);
+            middle += 1;
+        }
+    }
+    if middle == 0 || middle == objects.len() {
+        None
+    } else {
+        Some((axis, middle))
+    }
+}
+
+fn point_box<T: Float>(point: Vec3<T>) -> Aabb<T> {
+    Aabb::new(point, point)
+}
+
+fn component<T: Copy>(v: Vec3<T>, axis: usize) -> T {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn cube(center: Vec3<f64>, half: f64) -> Aabb<f64> {
+        let half = Vec3::new(half, half, half);
+        Aabb::new(center - half, center + half)
+    }
+
+    #[test]
+    fn test_traverse_finds_every_box_on_the_ray() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let boxes: Vec<Aabb<f64>> = (0..500)
+            .map(|_| {
+                let center = Vec3::new(
+                    rng.gen_range(-10.0, 10.0),
+                    rng.gen_range(-10.0, 10.0),
+                    rng.gen_range(-10.0, 10.0),
+                );
+                cube(center, rng.gen_range(0.05, 1.0))
+            })
+            .collect();
+        let bvh = Bvh::new(boxes.iter().cloned().enumerate().collect());
+        for _ in 0..200 {
+            let ray = Ray::new(
+                Vec3::new(
+                    rng.gen_range(-12.0, 12.0),
+                    rng.gen_range(-12.0, 12.0),
+                    -15.0,
+                ),
+                Vec3::new(rng.gen_range(-0.5, 0.5), rng.gen_range(-0.5, 0.5), 1.0),
+            );
+            let mut visited = vec![];
+            bvh.traverse(&ray, 0.0, f64::MAX, |index, max_t| {
+                visited.push(index);
+                Some(max_t)
+            });
+            visited.sort();
+            let expected: Vec<usize> = (0..boxes.len())
+                .filter(|&i| boxes[i].hit(&ray, 0.0, f64::MAX).is_some())
+                .collect();
+            assert_eq!(visited, expected);
+        }
+    }
+
+    #[test]
+    fn test_traverse_skips_boxes_beyond_max_t() {
+        // A row of boxes along the ray; once the first is "hit", only boxes sharing its leaf
+        // can still be visited.
+        let bvh = Bvh::new(
+            (0..20)
+                .map(|i| (i, cube(Vec3::new(0.0, 0.0, i as f64 * 3.0), 1.0)))
+                .collect(),
+        );
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut visited = vec![];
+        bvh.traverse(&ray, 0.0, f64::MAX, |index, max_t| {
+            visited.push(index);
+            Some(if index == 0 { 4.5 } else { max_t })
+        });
+        assert!(visited.contains(&0));
+        assert!(
+            visited.iter().all(|&index| index < MAX_LEAF_SIZE),
+            "{:?}",
+            visited
+        );
+
+        let mut count = 0;
+        bvh.traverse(&ray, 0.0, f64::MAX, |_, _| {
+            count += 1;
+            None
+        });
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_identical_boxes_share_a_leaf() {
+        let bvh = Bvh::new(
+            (0..10)
+                .map(|i| (i, cube(Vec3::new(1.0, 2.0, 3.0), 1.0)))
+                .collect(),
+        );
+        assert_eq!(bvh.nodes.len(), 1);
+        assert_eq!(Bvh::<f64>::new(vec![]).nodes.len(), 0);
+    }
+}
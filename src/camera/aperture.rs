@@ -0,0 +1,165 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use image;
+use rand::Rng;
+
+use sampling::Distribution2D;
+
+/// The shape of the opening in a lens, which out of focus highlights (bokeh) take on.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Aperture {
+    Circle,
+    /// A regular polygon with `blades` sides, as left by the blades of an iris, turned
+    /// `rotation` degrees counterclockwise from having a corner at the top.
+    Polygon {
+        blades: u32,
+        rotation: f64,
+    },
+    /// An image whose brightness is how much light each part of the opening lets through.
+    Image(Arc<ApertureImage>),
+}
+
+impl Aperture {
+    /// A point on the opening, where the opening fits in a circle of radius 1 about the origin
+    /// and y is up. Points are picked in proportion to the light let through there.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> (f64, f64) {
+        match self {
+            Aperture::Circle => loop {
+                let (x, y) = (rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+                if x * x + y * y < 1.0 {
+                    return (x, y);
+                }
+            },
+            Aperture::Polygon { blades, rotation } => {
+                // A triangle between the centre and one side, picked uniformly.
+                let side = rng.gen_range(0, *blades) as f64;
+                let corner = |i: f64| {
+                    let angle = rotation.to_radians()
+                        + 2.0 * std::f64::consts::PI * i / f64::from(*blades)
+                        + std::f64::consts::FRAC_PI_2;
+                    (angle.cos(), angle.sin())
+                };
+                let (a, b) = (corner(side), corner(side + 1.0));
+                let s = rng.gen::<f64>().sqrt();
+                let t = rng.gen::<f64>();
+                (
+                    s * ((1.0 - t) * a.0 + t * b.0),
+                    s * ((1.0 - t) * a.1 + t * b.1),
+                )
+            }
+            Aperture::Image(image) => image.sample(rng.gen(), rng.gen()),
+        }
+    }
+}
+
+/// A greyscale mask of the opening, fitted into the lens with its longer side across it.
+pub struct ApertureImage {
+    width: usize,
+    height: usize,
+    /// How much light each pixel lets through, left to right and then top to bottom.
+    transmission: Vec<f64>,
+    distribution: Distribution2D,
+}
+
+impl ApertureImage {
+    /// Fails if no light gets through.
+    pub fn new(width: usize, height: usize, transmission: Vec<f64>) -> Option<ApertureImage> {
+        if !transmission.iter().any(|&value| value > 0.0) {
+            return None;
+        }
+        Some(ApertureImage {
+            width,
+            height,
+            distribution: Distribution2D::new(&transmission, width),
+            transmission,
+        })
+    }
+
+    /// Loads any image the `image` crate reads, taking its brightness as the transmission.
+    pub fn load(path: &Path) -> Result<ApertureImage, String> {
+        let image = image::open(path)
+            .map_err(|error| error.to_string())?
+            .to_luma();
+        let transmission = image
+            .pixels()
+            .map(|pixel| f64::from(pixel[0]) / 255.0)
+            .collect();
+        ApertureImage::new(
+            image.width() as usize,
+            image.height() as usize,
+            transmission,
+        )
+        .ok_or_else(|| "the image is black, so lets no light through".to_string())
+    }
+
+    fn sample(&self, u1: f64, u2: f64) -> (f64, f64) {
+        let ((u, v), _) = self.distribution.sample(u1, u2);
+        let longest = self.width.max(self.height) as f64;
+        (
+            (2.0 * u - 1.0) * self.width as f64 / longest,
+            (1.0 - 2.0 * v) * self.height as f64 / longest,
+        )
+    }
+}
+
+/// Images are equal if their pixels are, whatever was loaded to get them.
+impl PartialEq for ApertureImage {
+    fn eq(&self, other: &ApertureImage) -> bool {
+        (self.width, self.height) == (other.width, other.height)
+            && self.transmission == other.transmission
+    }
+}
+
+impl fmt::Debug for ApertureImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ApertureImage({}x{})", self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_polygon_samples_lie_inside_it() {
+        let mut rng = StdRng::seed_from_u64(1);
+        // A square standing on a corner: inside it |x| + |y| <= 1.
+        let square = Aperture::Polygon {
+            blades: 4,
+            rotation: 0.0,
+        };
+        for _ in 0..1000 {
+            let (x, y) = square.sample(&mut rng);
+            assert!(x.abs() + y.abs() <= 1.0 + 1e-12, "{} {}", x, y);
+        }
+        // Turned by 45° it has sides at x and y = ±√½, and fills them out.
+        let turned = Aperture::Polygon {
+            blades: 4,
+            rotation: 45.0,
+        };
+        let samples: Vec<(f64, f64)> = (0..1000).map(|_| turned.sample(&mut rng)).collect();
+        let half = 0.5_f64.sqrt() + 1e-12;
+        assert!(samples
+            .iter()
+            .all(|(x, y)| x.abs() <= half && y.abs() <= half));
+        assert!(samples.iter().any(|(x, y)| x.abs() + y.abs() > 1.1));
+    }
+
+    #[test]
+    fn test_image_samples_where_light_gets_through() {
+        let mut rng = StdRng::seed_from_u64(2);
+        // Two pixels wide and one high: only the right hand one is open.
+        let image = ApertureImage::new(2, 1, vec![0.0, 1.0]).unwrap();
+        let aperture = Aperture::Image(Arc::new(image));
+        for _ in 0..100 {
+            let (x, y) = aperture.sample(&mut rng);
+            assert!((0.0..=1.0).contains(&x), "{}", x);
+            assert!(y.abs() <= 0.5, "{}", y);
+        }
+        assert!(ApertureImage::new(1, 1, vec![0.0]).is_none());
+    }
+}
@@ -0,0 +1,609 @@
+pub mod aperture;
+
+use random;
+use ray::Ray;
+use vec3::{Float, Vec3};
+
+use self::aperture::Aperture;
+
+/// Turns points on the image into rays into the scene. `s` runs from 0 at the left of the
+/// image to 1 at the right, and `t` from 0 at the bottom to 1 at the top.
+pub trait Camera<T: Float>: Sync {
+    fn get_ray(&self, s: T, t: T) -> Ray<T>;
+
+    /// A ray, with the fraction of the light along it that reaches the image: 0 if the lens
+    /// barrel blocks it.
+    fn get_weighted_ray(&self, s: T, t: T) -> (Ray<T>, T) {
+        (self.get_ray(s, t), T::from_f64(1.0))
+    }
+}
+
+/// The camera a scene is rendered with: one of the projections below.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Projection<T> {
+    Perspective(Perspective<T>),
+    Orthographic(Orthographic<T>),
+    Fisheye(Fisheye<T>),
+    Equirectangular(Equirectangular<T>),
+    CubeMap(CubeMap<T>),
+}
+
+impl<T: Float> Camera<T> for Projection<T> {
+    fn get_ray(&self, s: T, t: T) -> Ray<T> {
+        match self {
+            Projection::Perspective(camera) => camera.get_ray(s, t),
+            Projection::Orthographic(camera) => camera.get_ray(s, t),
+            Projection::Fisheye(camera) => camera.get_ray(s, t),
+            Projection::Equirectangular(camera) => camera.get_ray(s, t),
+            Projection::CubeMap(camera) => camera.get_ray(s, t),
+        }
+    }
+
+    fn get_weighted_ray(&self, s: T, t: T) -> (Ray<T>, T) {
+        match self {
+            Projection::Perspective(camera) => camera.get_weighted_ray(s, t),
+            _ => (self.get_ray(s, t), T::from_f64(1.0)),
+        }
+    }
+}
+
+impl<T: Float> Projection<T> {
+    /// A ray through (`s`, `t`) unaffected by depth of field, for picking objects.
+    #[cfg(feature = "window")]
+    pub fn get_pinhole_ray(&self, s: T, t: T) -> Ray<T> {
+        match self {
+            Projection::Perspective(camera) => camera.get_pinhole_ray(s, t),
+            _ => self.get_ray(s, t),
+        }
+    }
+
+    /// The distance the camera is focused at, if it has a lens to focus.
+    #[cfg(feature = "window")]
+    pub fn focus_distance(&self) -> Option<T> {
+        match self {
+            Projection::Perspective(camera) => Some(camera.focus_distance()),
+            _ => None,
+        }
+    }
+}
+
+/// When the camera's shutter is open, in scene time. Moving objects are at their start at time
+/// 0 and their end at time 1, so by default the shutter is open for the whole of the motion.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+}
+
+impl Default for Shutter {
+    fn default() -> Shutter {
+        Shutter {
+            open: 0.0,
+            close: 1.0,
+        }
+    }
+}
+
+impl Shutter {
+    /// A time while the shutter is open, from a uniform random number.
+    pub fn time<T: Float>(self, u: f64) -> T {
+        T::from_f64(self.open + (self.close - self.open) * u)
+    }
+}
+
+/// The orientation of a camera at `location` looking at `look_at`: `u` points right, `v` up
+/// and `w` backwards.
+fn basis<T: Float>(location: Vec3<T>, look_at: Vec3<T>, vup: Vec3<T>) -> [Vec3<T>; 3] {
+    let w = (location - look_at).unit();
+    let u = vup.cross(w).unit();
+    [u, w.cross(u), w]
+}
+
+/// A thin lens camera: a perspective view with `vfov` degrees from the bottom of the image to
+/// the top, whose aperture blurs everything not `focus_distance` away. Further lens effects
+/// are added with the `with_` methods.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Perspective<T> {
+    pub location: Vec3<T>,
+    pub lower_left: Vec3<T>,
+    pub horizontal: Vec3<T>,
+    pub vertical: Vec3<T>,
+    pub u: Vec3<T>,
+    pub v: Vec3<T>,
+    pub w: Vec3<T>,
+    pub lens_radius: T,
+    pub focus_distance: T,
+    pub aperture: Aperture,
+    /// How far off centre the lens barrel is at the corners of the image, in lens radii.
+    pub optical_vignetting: T,
+    /// The tangent of the angle from the view direction to the corners, before any shift.
+    half_diagonal: T,
+    /// The normal of the plane in focus: `w` unless the lens is tilted.
+    focal_plane_normal: Vec3<T>,
+}
+
+impl<T: Float> Perspective<T> {
+    pub fn new(
+        location: Vec3<T>,
+        look_at: Vec3<T>,
+        vup: Vec3<T>,
+        vfov: T,
+        aspect: T,
+        aperture: T,
+        focus_distance: T,
+    ) -> Perspective<T> {
+        let two = T::from_f64(2.0);
+        let theta = vfov * T::PI / T::from_f64(180.0);
+        let half_height = (theta / two).tan();
+        let half_width = aspect * half_height;
+        let [u, v, w] = basis(location, look_at, vup);
+        Perspective {
+            location,
+            lower_left: location
+                - half_width * focus_distance * u
+                - half_height * focus_distance * v
+                - focus_distance * w,
+            horizontal: two * half_width * focus_distance * u,
+            vertical: two * half_height * focus_distance * v,
+            u,
+            v,
+            w,
+            lens_radius: aperture / two,
+            focus_distance,
+            aperture: Aperture::Circle,
+            optical_vignetting: T::from_f64(0.0),
+            half_diagonal: (half_width * half_width + half_height * half_height).sqrt(),
+            focal_plane_normal: w,
+        }
+    }
+
+    /// Refocuses the camera, keeping its position, orientation and field of view.
+    #[cfg(any(feature = "window", test))]
+    pub fn with_focus_distance(self, focus_distance: T) -> Perspective<T> {
+        let scale = focus_distance / self.focus_distance;
+        Perspective {
+            lower_left: self.location + (self.lower_left - self.location) * scale,
+            horizontal: self.horizontal * scale,
+            vertical: self.vertical * scale,
+            focus_distance,
+            ..self
+        }
+    }
+
+    /// Gives the lens opening a shape other than a circle, for shaped bokeh.
+    pub fn with_aperture(self, aperture: Aperture) -> Perspective<T> {
+        Perspective { aperture, ..self }
+    }
+
+    /// Lets the lens barrel cut off light coming in at an angle, darkening the corners of the
+    /// image and squeezing bokeh there into cat's eyes. `amount` is how far off centre the
+    /// barrel is at the corners, in lens radii: at 2 the corners are black.
+    pub fn with_optical_vignetting(self, amount: T) -> Perspective<T> {
+        Perspective {
+            optical_vignetting: amount,
+            ..self
+        }
+    }
+
+    /// Tilts the plane in focus, as a tilt-shift lens does: by `tilt` degrees about the
+    /// horizontal, its top leaning away from the camera, and by `swing` degrees about the
+    /// vertical, its right side leaning away. It still crosses the view direction
+    /// `focus_distance` away.
+    pub fn with_tilt(self, tilt: T, swing: T) -> Perspective<T> {
+        let radians = T::PI / T::from_f64(180.0);
+        let (tilt, swing) = (tilt * radians, swing * radians);
+        Perspective {
+            focal_plane_normal: self.u * (swing.sin() * tilt.cos())
+                + self.v * tilt.sin()
+                + self.w * (tilt.cos() * swing.cos()),
+            ..self
+        }
+    }
+
+    /// Shifts the image off the view direction without turning the camera, as a shift lens
+    /// does, e.g. to keep the verticals of a tall building parallel. `x` and `y` are fractions
+    /// of the width and height of the image.
+    pub fn with_shift(self, x: T, y: T) -> Perspective<T> {
+        Perspective {
+            lower_left: self.lower_left + self.horizontal * x + self.vertical * y,
+            ..self
+        }
+    }
+
+    pub fn focus_distance(&self) -> T {
+        self.focus_distance
+    }
+
+    pub fn location(&self) -> Vec3<T> {
+        self.location
+    }
+
+    /// A ray through the centre of the lens, unaffected by depth of field.
+    pub fn get_pinhole_ray(&self, s: T, t: T) -> Ray<T> {
+        Ray::new(
+            self.location,
+            self.lower_left + (s * self.horizontal) + (t * self.vertical) - self.location,
+        )
+    }
+
+    /// The focus distance that would bring `point` into focus: its distance along the view
+    /// direction, unless the lens is tilted.
+    #[cfg(any(feature = "window", test))]
+    pub fn depth_of(&self, point: Vec3<T>) -> T {
+        -(point - self.location).dot(self.focal_plane_normal) / self.w.dot(self.focal_plane_normal)
+    }
+
+    /// Where the plane in focus meets the ray from the centre of the lens in `direction`, as
+    /// a multiple of `direction`, or `None` if they never meet.
+    fn focus_along(&self, direction: Vec3<T>) -> Option<T> {
+        if self.focal_plane_normal == self.w {
+            // The image plane is in focus, and `direction` reaches it.
+            return Some(T::from_f64(1.0));
+        }
+        let along = -self.focus_distance * self.w.dot(self.focal_plane_normal)
+            / direction.dot(self.focal_plane_normal);
+        // Also rules out infinities and NaNs, from directions parallel to the plane.
+        Some(along).filter(|along| *along > T::from_f64(0.0) && *along < T::MAX)
+    }
+
+    /// Whether the lens barrel lets light from the point `x`, `y` of the lens (in lens radii)
+    /// through in `direction`.
+    fn unvignetted(&self, x: T, y: T, direction: Vec3<T>) -> bool {
+        if self.optical_vignetting == T::from_f64(0.0) {
+            return true;
+        }
+        // The barrel is off centre towards the middle of the image, in proportion to how far
+        // from the view direction the light arrives.
+        let forward = -direction.dot(self.w);
+        let scale = self.optical_vignetting / (forward * self.half_diagonal);
+        let dx = x + direction.dot(self.u) * scale;
+        let dy = y + direction.dot(self.v) * scale;
+        dx * dx + dy * dy <= T::from_f64(1.0)
+    }
+}
+
+impl<T: Float> Camera<T> for Perspective<T> {
+    fn get_ray(&self, s: T, t: T) -> Ray<T> {
+        self.get_weighted_ray(s, t).0
+    }
+
+    fn get_weighted_ray(&self, s: T, t: T) -> (Ray<T>, T) {
+        let direction = self.get_pinhole_ray(s, t).direction;
+        let (x, y) = self.aperture.sample(&mut random::thread_rng());
+        let (x, y) = (T::from_f64(x), T::from_f64(y));
+        let offset = (self.u * x + self.v * y) * self.lens_radius;
+        let ray = match self.focus_along(direction) {
+            Some(along) => Ray::new(self.location + offset, direction * along - offset),
+            // Focused at infinity.
+            None => Ray::new(self.location + offset, direction),
+        };
+        let weight = if self.unvignetted(x, y, direction) {
+            T::from_f64(1.0)
+        } else {
+            T::from_f64(0.0)
+        };
+        (ray, weight)
+    }
+}
+
+/// Parallel rays from a window `height` units tall centred on `location`, so sizes do not
+/// shrink with distance, as in an architectural elevation.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Orthographic<T> {
+    lower_left: Vec3<T>,
+    horizontal: Vec3<T>,
+    vertical: Vec3<T>,
+    direction: Vec3<T>,
+}
+
+impl<T: Float> Orthographic<T> {
+    pub fn new(
+        location: Vec3<T>,
+        look_at: Vec3<T>,
+        vup: Vec3<T>,
+        height: T,
+        aspect: T,
+    ) -> Orthographic<T> {
+        let [u, v, w] = basis(location, look_at, vup);
+        let horizontal = u * (height * aspect);
+        let vertical = v * height;
+        Orthographic {
+            lower_left: location - (horizontal + vertical) / T::from_f64(2.0),
+            horizontal,
+            vertical,
+            direction: -w,
+        }
+    }
+}
+
+impl<T: Float> Camera<T> for Orthographic<T> {
+    fn get_ray(&self, s: T, t: T) -> Ray<T> {
+        Ray::new(
+            self.lower_left + (s * self.horizontal) + (t * self.vertical),
+            self.direction,
+        )
+    }
+}
+
+/// An equidistant fisheye: the angle from the view direction grows in proportion to the
+/// distance from the middle of the image, reaching half of `fov` degrees at the top and bottom
+/// edges. Wider images see further round to the sides, up to straight behind.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Fisheye<T> {
+    location: Vec3<T>,
+    basis: [Vec3<T>; 3],
+    half_fov: T,
+    aspect: T,
+}
+
+impl<T: Float> Fisheye<T> {
+    pub fn new(location: Vec3<T>, look_at: Vec3<T>, vup: Vec3<T>, fov: T, aspect: T) -> Fisheye<T> {
+        Fisheye {
+            location,
+            basis: basis(location, look_at, vup),
+            half_fov: fov * T::PI / T::from_f64(360.0),
+            aspect,
+        }
+    }
+}
+
+impl<T: Float> Camera<T> for Fisheye<T> {
+    fn get_ray(&self, s: T, t: T) -> Ray<T> {
+        let (one, two) = (T::from_f64(1.0), T::from_f64(2.0));
+        let [u, v, w] = self.basis;
+        let x = (two * s - one) * self.aspect;
+        let y = two * t - one;
+        let r = (x * x + y * y).sqrt();
+        let theta = (r * self.half_fov).min(T::PI);
+        let sideways = if r > T::from_f64(0.0) {
+            (u * x + v * y) * (theta.sin() / r)
+        } else {
+            Vec3::new(T::from_f64(0.0), T::from_f64(0.0), T::from_f64(0.0))
+        };
+        Ray::new(self.location, sideways - w * theta.cos())
+    }
+}
+
+/// A 360° panorama in equirectangular form, for VR viewers: longitude runs across the image
+/// with the view direction in the middle, and latitude up it from straight down to straight
+/// up. The image should be twice as wide as it is tall.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Equirectangular<T> {
+    location: Vec3<T>,
+    basis: [Vec3<T>; 3],
+}
+
+impl<T: Float> Equirectangular<T> {
+    pub fn new(location: Vec3<T>, look_at: Vec3<T>, vup: Vec3<T>) -> Equirectangular<T> {
+        Equirectangular {
+            location,
+            basis: basis(location, look_at, vup),
+        }
+    }
+}
+
+impl<T: Float> Camera<T> for Equirectangular<T> {
+    fn get_ray(&self, s: T, t: T) -> Ray<T> {
+        let half = T::from_f64(0.5);
+        let [u, v, w] = self.basis;
+        let longitude = (s - half) * T::from_f64(2.0) * T::PI;
+        let latitude = (t - half) * T::PI;
+        let around = u * longitude.sin() - w * longitude.cos();
+        Ray::new(self.location, around * latitude.cos() + v * latitude.sin())
+    }
+}
+
+/// The six faces of a cube around `location`, each a 90° square view, side by side in the
+/// order front, right, back, left, up and down. The image should be six times as wide as it is
+/// tall. The up and down faces have the front at their bottom and top edges respectively.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct CubeMap<T> {
+    location: Vec3<T>,
+    basis: [Vec3<T>; 3],
+}
+
+impl<T: Float> CubeMap<T> {
+    pub fn new(location: Vec3<T>, look_at: Vec3<T>, vup: Vec3<T>) -> CubeMap<T> {
+        CubeMap {
+            location,
+            basis: basis(location, look_at, vup),
+        }
+    }
+}
+
+impl<T: Float> Camera<T> for CubeMap<T> {
+    fn get_ray(&self, s: T, t: T) -> Ray<T> {
+        let (one, two) = (T::from_f64(1.0), T::from_f64(2.0));
+        let [u, v, w] = self.basis;
+        let across = s * T::from_f64(6.0);
+        let face = (across.to_f64().max(0.0) as usize).min(5);
+        let a = two * (across - T::from_f64(face as f64)) - one;
+        let b = two * t - one;
+        // The view direction, and the directions to the right and to the top of the face.
+        let (forward, right, up) = match face {
+            0 => (-w, u, v),
+            1 => (u, w, v),
+            2 => (w, -u, v),
+            3 => (-u, -w, v),
+            4 => (v, u, w),
+            _ => (-v, u, -w),
+        };
+        Ray::new(self.location, forward + right * a + up * b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Perspective<f64> {
+        Perspective::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+            0.1,
+            1.0,
+        )
+    }
+
+    fn assert_direction(ray: Ray<f64>, expected: Vec3<f64>) {
+        let direction = ray.direction().unit();
+        assert!(
+            (direction - expected).length() < 1e-9,
+            "{} != {}",
+            direction,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_pinhole_ray_reaches_focus_plane() {
+        let camera = camera().with_focus_distance(3.0);
+        let ray = camera.get_pinhole_ray(0.5, 0.5);
+        assert_eq!(camera.depth_of(ray.point_at_parameter(1.0)), 3.0);
+    }
+
+    #[test]
+    fn test_with_focus_distance_keeps_field_of_view() {
+        let near = camera();
+        let far = near.clone().with_focus_distance(4.0);
+        assert_eq!(far.focus_distance(), 4.0);
+        assert_eq!(far.location, near.location);
+        assert_eq!(
+            far.get_pinhole_ray(0.0, 0.0).direction().unit(),
+            near.get_pinhole_ray(0.0, 0.0).direction().unit()
+        );
+    }
+
+    #[test]
+    fn test_tilted_focal_plane() {
+        let camera = camera().with_focus_distance(5.0).with_tilt(30.0, 0.0);
+        // Every ray through a point on the image meets in the plane in focus.
+        let focus = |s: f64, t: f64| {
+            let points: Vec<Vec3<f64>> = (0..10)
+                .map(|_| camera.get_ray(s, t).point_at_parameter(1.0))
+                .collect();
+            for point in &points {
+                assert!((*point - points[0]).length() < 1e-9);
+            }
+            points[0]
+        };
+        let (top, middle, bottom) = (focus(0.5, 0.9), focus(0.5, 0.5), focus(0.5, 0.1));
+        assert!((middle - Vec3::new(0.0, 0.0, -5.0)).length() < 1e-9);
+        assert!(-top.z > 5.0 && -bottom.z < 5.0);
+        // The plane leans back by the tilt.
+        let slope = (top - bottom).unit();
+        assert!((slope.y - 30.0_f64.to_radians().cos()).abs() < 1e-9);
+        assert!((camera.depth_of(top) - 5.0).abs() < 1e-9);
+        // Looking up past where the plane meets the horizon, the lens is focused at infinity.
+        let steep = camera.clone().with_tilt(80.0, 0.0);
+        let ray = steep.get_ray(0.5, 1.0);
+        let pinhole = steep.get_pinhole_ray(0.5, 1.0);
+        assert!((ray.direction().unit() - pinhole.direction().unit()).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_shift() {
+        let camera = camera();
+        let shifted = camera.clone().with_shift(0.0, 0.25);
+        assert_direction(
+            shifted.get_pinhole_ray(0.5, 0.5),
+            camera.get_pinhole_ray(0.5, 0.75).direction().unit(),
+        );
+    }
+
+    #[test]
+    fn test_optical_vignetting() {
+        let blocked = |amount: f64, s: f64, t: f64| {
+            let camera = camera().with_optical_vignetting(amount);
+            let samples = 20000;
+            let passed: f64 = (0..samples).map(|_| camera.get_weighted_ray(s, t).1).sum();
+            1.0 - passed / f64::from(samples)
+        };
+        assert_eq!(blocked(1.0, 0.5, 0.5), 0.0);
+        assert_eq!(blocked(2.0, 1.0, 1.0), 1.0);
+        // Two circles of radius 1 a radius apart overlap over 39% of either.
+        let overlap =
+            (2.0 * std::f64::consts::PI / 3.0 - 3.0_f64.sqrt() / 2.0) / std::f64::consts::PI;
+        assert!((blocked(1.0, 0.0, 0.0) - (1.0 - overlap)).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_orthographic_rays_are_parallel() {
+        let camera = Orthographic::new(
+            Vec3::new(0.0, 1.0, 5.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            4.0,
+            1.5,
+        );
+        let corner = camera.get_ray(0.0, 0.0);
+        assert_eq!(*corner.origin(), Vec3::new(-3.0, -1.0, 5.0));
+        assert_eq!(*corner.direction(), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(*camera.get_ray(1.0, 1.0).origin(), Vec3::new(3.0, 3.0, 5.0));
+        assert_eq!(*camera.get_ray(1.0, 1.0).direction(), *corner.direction());
+    }
+
+    #[test]
+    fn test_fisheye() {
+        let camera = Fisheye::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            180.0,
+            2.0,
+        );
+        assert_direction(camera.get_ray(0.5, 0.5), Vec3::new(0.0, 0.0, -1.0));
+        // Half the fov at the top edge, and twice that at the side of a 2:1 image.
+        assert_direction(camera.get_ray(0.5, 1.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_direction(camera.get_ray(1.0, 0.5), Vec3::new(0.0, 0.0, 1.0));
+        assert_direction(camera.get_ray(0.75, 0.5), Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_equirectangular() {
+        let camera = Equirectangular::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        assert_direction(camera.get_ray(0.5, 0.5), Vec3::new(0.0, 0.0, -1.0));
+        assert_direction(camera.get_ray(0.75, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert_direction(camera.get_ray(0.0, 0.5), Vec3::new(0.0, 0.0, 1.0));
+        assert_direction(camera.get_ray(0.3, 1.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_direction(camera.get_ray(0.5, 0.25), Vec3::new(0.0, -1.0, -1.0).unit());
+    }
+
+    #[test]
+    fn test_cube_map_faces() {
+        let camera = CubeMap::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let centre = |face: f64| camera.get_ray((face + 0.5) / 6.0, 0.5);
+        assert_direction(centre(0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_direction(centre(1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_direction(centre(2.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_direction(centre(3.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert_direction(centre(4.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_direction(centre(5.0), Vec3::new(0.0, -1.0, 0.0));
+        // Neighbouring faces meet: the right edge of the front is the left edge of the right.
+        assert_direction(
+            camera.get_ray(1.0 / 6.0 - 1e-12, 0.5),
+            Vec3::new(1.0, 0.0, -1.0).unit(),
+        );
+        assert_direction(
+            camera.get_ray(1.0 / 6.0, 0.5),
+            Vec3::new(1.0, 0.0, -1.0).unit(),
+        );
+        // The bottom of the up face meets the top of the front.
+        assert_direction(
+            camera.get_ray(4.5 / 6.0, 0.0),
+            Vec3::new(0.0, 1.0, -1.0).unit(),
+        );
+        assert_direction(camera.get_ray(1.0, 1.0), Vec3::new(1.0, -1.0, -1.0).unit());
+    }
+}
@@ -0,0 +1,381 @@
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use image_data::{ImageData, Region};
+use vec3::Vec3;
+
+const MAGIC: &[u8; 8] = b"RUSTRAYC";
+const VERSION: u32 = 2;
+
+/// The state of a render part way through, enough to carry on from where it stopped: the sum
+/// of the samples taken for every pixel, how many there were, and the seed of the random
+/// numbers. Each row of each pass draws from a stream of its own decided by the seed, so the
+/// passes taken after resuming are the ones the render would have taken had it not stopped.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Checkpoint {
+    /// The fingerprint of the scene file rendered.
+    pub scene: u64,
+    /// The fingerprint of everything else that decides the image: its size, the sample count,
+    /// the camera and so on.
+    pub settings: u64,
+    pub width: usize,
+    pub height: usize,
+    /// Complete passes over the image, or its region.
+    pub passes: u32,
+    /// The render's seed, which the passes after `passes` carry on drawing from.
+    pub seed: u64,
+    /// Sample counts and sums, bottom row first like the canvas.
+    pub pixels: Vec<(u32, Vec3<f64>)>,
+}
+
+impl Checkpoint {
+    /// Takes a checkpoint of an image after `passes` complete passes over `region`, or over the
+    /// whole image if there is none, of a render seeded with `seed`. Pixels outside the region
+    /// have no samples.
+    pub fn capture(
+        image_data: &ImageData<Vec3<f64>>,
+        region: Option<Region>,
+        passes: u32,
+        seed: u64,
+        scene: u64,
+        settings: u64,
+    ) -> Checkpoint {
+        let region = region.unwrap_or_else(|| image_data.region());
+        let pixels = (0..image_data.y_size())
+            .flat_map(|y| (0..image_data.x_size()).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                if region.contains(x, y) && passes > 0 {
+                    (passes, image_data.get_pixel(x, y) * f64::from(passes))
+                } else {
+                    (0, Vec3::new(0.0, 0.0, 0.0))
+                }
+            })
+            .collect();
+        Checkpoint {
+            scene,
+            settings,
+            width: image_data.x_size(),
+            height: image_data.y_size(),
+            passes,
+            seed,
+            pixels,
+        }
+    }
+
+    /// Fails, saying why, unless the checkpoint was taken of the same scene with the same
+    /// settings.
+    pub fn check(&self, scene: u64, settings: u64) -> Result<(), String> {
+        if self.scene != scene {
+            Err("the scene file has changed since the checkpoint was taken".to_string())
+        } else if self.settings != settings {
+            Err("the render settings have changed since the checkpoint was taken".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Adds the samples of `other`, a render of the same scene and region with the same
+    /// settings, e.g. on another machine. Each pixel's average is then weighted by how many
+    /// samples each render took of it. The seed stays this render's: resuming carries on from
+    /// the merged passes, past any this render took. Renders of different regions are refused,
+    /// as their pixels would have different numbers of samples, and a resumed render weights
+    /// every pixel by the passes.
+    pub fn add(&mut self, other: &Checkpoint) -> Result<(), String> {
+        if (other.width, other.height) != (self.width, self.height) {
+            return Err(format!(
+                "the image is {}x{}, not {}x{}",
+                other.width, other.height, self.width, self.height
+            ));
+        }
+        if other.scene != self.scene {
+            return Err("it is of a different scene file".to_string());
+        }
+        if other.settings != self.settings {
+            return Err("it was rendered with different settings".to_string());
+        }
+        let sampled = |checkpoint: &Checkpoint| {
+            checkpoint
+                .pixels
+                .iter()
+                .map(|&(count, _)| count > 0)
+                .collect::<Vec<_>>()
+        };
+        if self.passes > 0 && other.passes > 0 && sampled(self) != sampled(other) {
+            return Err("it is of a different region of the image".to_string());
+        }
+        self.passes += other.passes;
+        for (pixel, &(count, sum)) in self.pixels.iter_mut().zip(&other.pixels) {
+            *pixel = (pixel.0 + count, pixel.1 + sum);
+        }
+        Ok(())
+    }
+
+    /// Puts the average of each pixel's samples back into `image_data`, which must be the
+    /// checkpoint's size. Pixels without samples are left as they are.
+    pub fn restore(&self, image_data: &ImageData<Vec3<f64>>) {
+        for (index, &(count, sum)) in self.pixels.iter().enumerate() {
+            if count > 0 {
+                image_data.update_pixel(index % self.width, index / self.width, |_| {
+                    sum / f64::from(count)
+                });
+            }
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(52 + 28 * self.pixels.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.scene.to_le_bytes());
+        bytes.extend_from_slice(&self.settings.to_le_bytes());
+        bytes.extend_from_slice(&(self.width as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.passes.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        for (count, sum) in &self.pixels {
+            bytes.extend_from_slice(&count.to_le_bytes());
+            for channel in &[sum.x, sum.y, sum.z] {
+                bytes.extend_from_slice(&channel.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Checkpoint, String> {
+        if !bytes.starts_with(MAGIC) {
+            return Err("not a rustray checkpoint".to_string());
+        }
+        let mut reader = Reader {
+            bytes,
+            position: MAGIC.len(),
+        };
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(format!("checkpoint version {} is not supported", version));
+        }
+        let scene = reader.u64()?;
+        let settings = reader.u64()?;
+        let width = reader.u32()? as usize;
+        let height = reader.u32()? as usize;
+        let passes = reader.u32()?;
+        let seed = reader.u64()?;
+        let pixels: Vec<(u32, Vec3<f64>)> = (0..width * height)
+            .map(|_| {
+                let count = reader.u32()?;
+                let sum = Vec3::new(reader.f64()?, reader.f64()?, reader.f64()?);
+                Ok((count, sum))
+            })
+            .collect::<Result<_, String>>()?;
+        if reader.position != bytes.len() {
+            return Err("unexpected data at the end of the checkpoint".to_string());
+        }
+        if pixels
+            .iter()
+            .any(|&(count, _)| count != 0 && count != passes)
+        {
+            return Err("its pixels do not all have the samples of its passes".to_string());
+        }
+        Ok(Checkpoint {
+            scene,
+            settings,
+            width,
+            height,
+            passes,
+            seed,
+            pixels,
+        })
+    }
+
+    /// Writes the checkpoint under a temporary name first, so that a render killed while
+    /// saving leaves the previous checkpoint intact.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let partial = path.with_file_name(format!(
+            "{}.partial",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        fs::write(&partial, self.encode())?;
+        fs::rename(&partial, path)
+    }
+
+    pub fn load(path: &Path) -> Result<Checkpoint, String> {
+        let bytes = fs::read(path).map_err(|error| error.to_string())?;
+        Checkpoint::decode(&bytes)
+    }
+}
+
+/// A 64 bit FNV-1a hash of `bytes`. Unlike the standard library's hasher it is the same in
+/// every build, so checkpoints stay usable after rustray is rebuilt.
+pub fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + size)
+            .ok_or("unexpected end of checkpoint")?;
+        self.position += size;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> ImageData<Vec3<f64>> {
+        let image_data = ImageData::new_blank(3, 2, Vec3::new(0.0, 0.0, 0.0));
+        image_data.update_pixel(1, 0, |_| Vec3::new(0.5, 0.25, 2.0));
+        image_data.update_pixel(2, 1, |_| Vec3::new(1.0, 0.0, 0.125));
+        image_data
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let checkpoint = Checkpoint::capture(&image(), None, 4, 3, 1, 2);
+        assert_eq!(checkpoint.pixels[1], (4, Vec3::new(2.0, 1.0, 8.0)));
+        let decoded = Checkpoint::decode(&checkpoint.encode()).unwrap();
+        assert_eq!(decoded, checkpoint);
+
+        let restored = ImageData::new_blank(3, 2, Vec3::new(9.0, 9.0, 9.0));
+        decoded.restore(&restored);
+        let expected: Vec<Vec3<f64>> = (&image()).into();
+        let restored: Vec<Vec3<f64>> = (&restored).into();
+        assert_eq!(restored, expected);
+    }
+
+    #[test]
+    fn test_region_only() {
+        let region = Region {
+            x: 1,
+            y: 0,
+            width: 2,
+            height: 1,
+        };
+        let checkpoint = Checkpoint::capture(&image(), Some(region), 2, 3, 1, 2);
+        let counts: Vec<u32> = checkpoint.pixels.iter().map(|(count, _)| *count).collect();
+        assert_eq!(counts, vec![0, 2, 2, 0, 0, 0]);
+        // Pixels without samples keep what they had, e.g. from a base image.
+        let restored = ImageData::new_blank(3, 2, Vec3::new(9.0, 9.0, 9.0));
+        checkpoint.restore(&restored);
+        assert_eq!(restored.get_pixel(0, 0), Vec3::new(9.0, 9.0, 9.0));
+        assert_eq!(restored.get_pixel(1, 0), Vec3::new(0.5, 0.25, 2.0));
+    }
+
+    #[test]
+    fn test_add() {
+        let mut merged = Checkpoint::capture(&image(), None, 3, 3, 1, 2);
+        let other = ImageData::new_blank(3, 2, Vec3::new(1.0, 1.0, 1.0));
+        merged
+            .add(&Checkpoint::capture(&other, None, 1, 4, 1, 2))
+            .unwrap();
+        assert_eq!((merged.passes, merged.seed), (4, 3));
+        // Three samples averaging 0.5, 0.25, 2 and one of 1.
+        assert_eq!(merged.pixels[1], (4, Vec3::new(2.5, 1.75, 7.0)));
+        assert_eq!(merged.pixels[0], (4, Vec3::new(1.0, 1.0, 1.0)));
+
+        let different_scene = Checkpoint::capture(&other, None, 1, 3, 5, 2);
+        assert_eq!(
+            merged.add(&different_scene),
+            Err("it is of a different scene file".to_string())
+        );
+        let smaller = ImageData::new_blank(2, 2, Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(
+            merged.add(&Checkpoint::capture(&smaller, None, 1, 3, 1, 2)),
+            Err("the image is 2x2, not 3x2".to_string())
+        );
+        assert_eq!(merged.passes, 4);
+    }
+
+    #[test]
+    fn test_add_refuses_other_regions() {
+        let region = |x| {
+            Some(Region {
+                x,
+                y: 0,
+                width: 2,
+                height: 1,
+            })
+        };
+        let mut merged = Checkpoint::capture(&image(), region(0), 3, 3, 1, 2);
+        merged
+            .add(&Checkpoint::capture(&image(), region(0), 2, 4, 1, 2))
+            .unwrap();
+        assert_eq!(merged.pixels[1].0, 5);
+        assert_eq!(
+            merged.add(&Checkpoint::capture(&image(), region(1), 2, 4, 1, 2)),
+            Err("it is of a different region of the image".to_string())
+        );
+        // A render stopped before its first pass has nothing to add, but nothing wrong either.
+        merged
+            .add(&Checkpoint::capture(&image(), None, 0, 4, 1, 2))
+            .unwrap();
+        assert_eq!(merged.passes, 5);
+        assert_eq!(Checkpoint::decode(&merged.encode()), Ok(merged));
+    }
+
+    #[test]
+    fn test_check_and_bad_files() {
+        let checkpoint = Checkpoint::capture(&image(), None, 1, 3, fingerprint(b"scene"), 7);
+        assert_eq!(checkpoint.check(fingerprint(b"scene"), 7), Ok(()));
+        assert!(checkpoint
+            .check(fingerprint(b"scene!"), 7)
+            .unwrap_err()
+            .contains("scene file"));
+        assert!(checkpoint
+            .check(fingerprint(b"scene"), 8)
+            .unwrap_err()
+            .contains("settings"));
+
+        let bytes = checkpoint.encode();
+        assert_eq!(
+            Checkpoint::decode(&bytes[..bytes.len() - 1]),
+            Err("unexpected end of checkpoint".to_string())
+        );
+        let mut old = bytes.clone();
+        old[8..12].copy_from_slice(&1u32.to_le_bytes());
+        assert_eq!(
+            Checkpoint::decode(&old),
+            Err("checkpoint version 1 is not supported".to_string())
+        );
+        assert_eq!(
+            Checkpoint::decode(b"{}"),
+            Err("not a rustray checkpoint".to_string())
+        );
+        let mut uneven = checkpoint.clone();
+        uneven.pixels[2].0 = 2;
+        assert_eq!(
+            Checkpoint::decode(&uneven.encode()),
+            Err("its pixels do not all have the samples of its passes".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        // Published FNV-1a test vectors.
+        assert_eq!(fingerprint(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fingerprint(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
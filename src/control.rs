@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use image_data::Region;
+
+/// Called with the number of passes so far and the region they covered, if not the whole image.
+pub type AfterPass = Box<dyn FnMut(u32, Option<Region>) + Send>;
+
+/// Cancellation token, pause switch and region of interest shared between the tracer and
+/// whoever drives it (e.g. the viewer window). The tracer checks it between rows, so pausing and
+/// cancelling take effect promptly; a new region is picked up after the current pass.
+pub struct RenderControl {
+    pub cancelled: AtomicBool,
+    pub paused: Mutex<bool>,
+    pub resumed: Condvar,
+    /// The only part of the image to render, if not all of it.
+    pub region: Mutex<Option<Region>>,
+    /// Called by the tracer after each complete pass, e.g. to save a checkpoint.
+    pub after_pass: Mutex<Option<AfterPass>>,
+}
+
+impl RenderControl {
+    pub fn new() -> RenderControl {
+        RenderControl {
+            cancelled: AtomicBool::new(false),
+            paused: Mutex::new(false),
+            resumed: Condvar::new(),
+            region: Mutex::new(None),
+            after_pass: Mutex::new(None),
+        }
+    }
+
+    pub fn region(&self) -> Option<Region> {
+        *self.region.lock().unwrap()
+    }
+
+    /// Restricts rendering to `region`, or lifts the restriction if it is `None`. Sampling of
+    /// the new region starts again from scratch; pixels outside it are left as they are.
+    pub fn set_region(&self, region: Option<Region>) {
+        *self.region.lock().unwrap() = region;
+    }
+
+    /// Has `hook` called after each complete pass. The image is not touched while it runs, so
+    /// it sees every pixel rendered with the same number of samples.
+    pub fn set_after_pass(&self, hook: AfterPass) {
+        *self.after_pass.lock().unwrap() = Some(hook);
+    }
+
+    pub fn pass_complete(&self, passes: u32, region: Option<Region>) {
+        if let Some(hook) = self.after_pass.lock().unwrap().as_mut() {
+            hook(passes, region);
+        }
+    }
+
+    /// Stops the render. Also releases any thread waiting for a paused render to resume.
+    pub fn cancel(&self) {
+        // Hold the lock so a thread about to wait cannot miss the notification.
+        let _paused = self.paused.lock().unwrap();
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.resumed.notify_all();
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    #[cfg(any(feature = "window", test))]
+    pub fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap()
+    }
+
+    /// Flips between paused and running, returning whether the render is now paused.
+    #[cfg(any(feature = "window", test))]
+    pub fn toggle_pause(&self) -> bool {
+        let mut paused = self.paused.lock().unwrap();
+        *paused = !*paused;
+        self.resumed.notify_all();
+        *paused
+    }
+
+    /// Blocks the calling thread while the render is paused and not cancelled.
+    pub fn wait_while_paused(&self) {
+        let mut paused = self.paused.lock().unwrap();
+        while *paused && !self.is_cancelled() {
+            paused = self.resumed.wait(paused).unwrap();
+        }
+    }
+}
+
+impl Default for RenderControl {
+    fn default() -> RenderControl {
+        RenderControl::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_toggle_pause() {
+        let control = RenderControl::new();
+        assert!(!control.is_paused());
+        assert!(control.toggle_pause());
+        assert!(control.is_paused());
+        assert!(!control.toggle_pause());
+    }
+
+    #[test]
+    fn test_cancel_releases_paused_threads() {
+        let control = Arc::new(RenderControl::new());
+        control.toggle_pause();
+
+        let waiting = {
+            let control = Arc::clone(&control);
+            thread::spawn(move || control.wait_while_paused())
+        };
+        control.cancel();
+        waiting.join().unwrap();
+
+        assert!(control.is_cancelled());
+    }
+
+    #[test]
+    fn test_after_pass() {
+        let control = RenderControl::new();
+        control.pass_complete(1, None);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        control.set_after_pass(Box::new(move |passes, _| sender.send(passes).unwrap()));
+        control.pass_complete(2, None);
+        control.pass_complete(3, None);
+        assert_eq!(receiver.try_iter().collect::<Vec<u32>>(), vec![2, 3]);
+    }
+}
@@ -0,0 +1,364 @@
+extern crate image;
+
+pub mod terminal;
+#[cfg(feature = "window")]
+pub mod window;
+
+use std::fmt;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::thread::JoinHandle;
+#[cfg(feature = "window")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::png::PNGEncoder;
+use image::{ColorType, ImageBuffer, ImageFormat, Rgba};
+
+use camera::Projection;
+use control::RenderControl;
+use hittable::World;
+use image_data::{ImageData, Region};
+use stats::RenderStats;
+#[cfg(feature = "window")]
+use stats::{format_duration, format_rate};
+use vec3::Vec3;
+
+/// Somewhere to watch a render as it progresses.
+pub trait Display {
+    /// Starts showing `session` on a background thread. A display may cancel the render, e.g.
+    /// when its window is closed, but must not stop it just because it cannot show anything.
+    fn spawn(&self, session: RenderSession) -> JoinHandle<()>;
+}
+
+/// Everything a display may read or influence while a render is running.
+#[derive(Clone)]
+pub struct RenderSession {
+    pub image_data: Arc<ImageData<Vec3<f64>>>,
+    pub camera: Arc<RwLock<Projection<f64>>>,
+    pub world: Arc<World<f64>>,
+    pub stats: Arc<RenderStats>,
+    pub control: Arc<RenderControl>,
+    pub tone_mapping: ToneMapping,
+}
+
+/// Shows nothing, for headless machines and tests.
+pub struct NullDisplay;
+
+impl Display for NullDisplay {
+    fn spawn(&self, _session: RenderSession) -> JoinHandle<()> {
+        thread::spawn(|| {})
+    }
+}
+
+/// The window if this build has one, otherwise progress on the terminal.
+pub fn default_display() -> Box<dyn Display> {
+    #[cfg(feature = "window")]
+    {
+        Box::new(window::WindowDisplay)
+    }
+    #[cfg(not(feature = "window"))]
+    {
+        Box::new(terminal::TerminalDisplay::default())
+    }
+}
+
+/// How accumulated radiance is turned into displayable 8 bit colour: every channel is
+/// normalised against the brightest channel in the image, then gamma corrected.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ToneMapping {
+    pub gamma: f64,
+}
+
+impl ToneMapping {
+    pub fn new(gamma: f64) -> ToneMapping {
+        ToneMapping { gamma }
+    }
+
+    #[inline]
+    pub fn apply(self, value: f64, max_intensity: f64) -> u8 {
+        f64_to_u8(gamma_correction(
+            normalise(value, max_intensity),
+            self.gamma,
+        ))
+    }
+
+    /// Undoes `apply`, as far as it can be undone, for an image whose brightest channel was 1.
+    pub fn invert(self, value: u8) -> f64 {
+        (f64::from(value) / 255.0).powf(self.gamma)
+    }
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping::new(2.0)
+    }
+}
+
+impl fmt::Display for ToneMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "normalise to brightest, gamma {:.1}", self.gamma)
+    }
+}
+
+/// Render progress and settings, one line each, for overlays and status output.
+#[cfg(feature = "window")]
+pub fn status_lines(
+    image_data: &ImageData<Vec3<f64>>,
+    stats: &RenderStats,
+    tone_mapping: ToneMapping,
+    max_intensity: f64,
+    complete: f64,
+) -> Vec<String> {
+    vec![
+        format!("Samples per pixel: {}", stats.samples_per_pixel()),
+        format!("Rays per second: {}", format_rate(stats.rays_per_second())),
+        format!(
+            "Elapsed: {}  Remaining: {}",
+            format_duration(stats.elapsed()),
+            stats
+                .estimated_remaining(complete)
+                .map_or_else(|| "unknown".to_string(), format_duration)
+        ),
+        format!("Threads: {}", stats.threads()),
+        format!(
+            "Resolution: {}x{}",
+            image_data.x_size(),
+            image_data.y_size()
+        ),
+        format!(
+            "Tone mapping: {} (brightest {:.2})",
+            tone_mapping, max_intensity
+        ),
+    ]
+}
+
+/// Writes the image as accumulated so far to a timestamped PNG in the working directory.
+/// Returns a message describing the outcome.
+#[cfg(feature = "window")]
+pub fn save_snapshot(image_data: &ImageData<Vec3<f64>>, tone_mapping: ToneMapping) -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0);
+    let path = format!("rustray-{}.png", seconds);
+    match save_image(image_data, tone_mapping, Path::new(&path), ImageFormat::PNG) {
+        Ok(()) => format!("Saved {}", path),
+        Err(error) => format!("Could not save {}: {}", path, error),
+    }
+}
+
+/// Writes the image as accumulated so far to `path`.
+pub fn save_image(
+    image_data: &ImageData<Vec3<f64>>,
+    tone_mapping: ToneMapping,
+    path: &Path,
+    format: ImageFormat,
+) -> io::Result<()> {
+    let pixels: Vec<Vec3<f64>> = image_data.into();
+    let max_intensity = max_intensity_from(&pixels);
+    to_rgba_image(
+        &pixels,
+        image_data.x_size() as u32,
+        image_data.y_size() as u32,
+        tone_mapping,
+        max_intensity,
+    )
+    .save_with_format(path, format)
+}
+
+/// The image as it is so far, encoded as a PNG.
+pub fn encode_png(
+    image_data: &ImageData<Vec3<f64>>,
+    tone_mapping: ToneMapping,
+) -> io::Result<Vec<u8>> {
+    let pixels: Vec<Vec3<f64>> = image_data.into();
+    let (width, height) = (image_data.x_size() as u32, image_data.y_size() as u32);
+    let max_intensity = max_intensity_from(&pixels);
+    let image = to_rgba_image(&pixels, width, height, tone_mapping, max_intensity);
+    let mut bytes = vec![];
+    PNGEncoder::new(&mut bytes).encode(&image.into_raw(), width, height, ColorType::RGBA(8))?;
+    Ok(bytes)
+}
+
+/// Tone maps the canvas, which is stored bottom row first, into an image with the top row first.
+pub fn to_rgba_image(
+    pixels: &[Vec3<f64>],
+    width: u32,
+    height: u32,
+    tone_mapping: ToneMapping,
+    max_intensity: f64,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let p = pixels[((height - 1 - y) * width + x) as usize];
+        Rgba([
+            tone_mapping.apply(p.r(), max_intensity),
+            tone_mapping.apply(p.g(), max_intensity),
+            tone_mapping.apply(p.b(), max_intensity),
+            255u8,
+        ])
+    })
+}
+
+/// Reads back an image written earlier, e.g. to fill in the pixels outside a render region.
+/// Returns the pixels bottom row first, like the canvas. Fails unless the image is
+/// `width` by `height`.
+pub fn load_image(
+    path: &Path,
+    width: usize,
+    height: usize,
+    tone_mapping: ToneMapping,
+) -> Result<Vec<Vec3<f64>>, String> {
+    let image = image::open(path)
+        .map_err(|error| error.to_string())?
+        .to_rgb();
+    if (image.width() as usize, image.height() as usize) != (width, height) {
+        return Err(format!(
+            "the image is {}x{}, not {}x{}",
+            image.width(),
+            image.height(),
+            width,
+            height
+        ));
+    }
+    Ok((0..height)
+        .rev()
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let pixel = image.get_pixel(x as u32, y as u32);
+            Vec3::new(
+                tone_mapping.invert(pixel[0]),
+                tone_mapping.invert(pixel[1]),
+                tone_mapping.invert(pixel[2]),
+            )
+        })
+        .collect())
+}
+
+/// Images are saved with their brightest channel as white, so a base image read back with
+/// `load_image` has lost its exposure. This brings the pixels outside `region` back into line
+/// with the ones rendered inside it, taking the brightest channel in the region as the one the
+/// base was saved with. That is exact if the region holds the brightest part of the image.
+pub fn match_base_exposure(image_data: &ImageData<Vec3<f64>>, region: Region) {
+    let rendered: Vec<Vec3<f64>> = (region.y..region.y + region.height)
+        .flat_map(|y| (region.x..region.x + region.width).map(move |x| (x, y)))
+        .map(|(x, y)| image_data.get_pixel(x, y))
+        .collect();
+    let exposure = max_intensity_from(&rendered);
+    for y in 0..image_data.y_size() {
+        for x in (0..image_data.x_size()).filter(|&x| !region.contains(x, y)) {
+            image_data.update_pixel(x, y, |pixel| pixel * exposure);
+        }
+    }
+}
+
+pub fn max_intensity_from(pixels: &[Vec3<f64>]) -> f64 {
+    pixels.iter().fold(1.0, |max_so_far, colour| {
+        let mut max = max_so_far;
+        if colour.r() > max {
+            max = colour.r()
+        };
+        if colour.g() > max {
+            max = colour.g()
+        };
+        if colour.b() > max {
+            max = colour.b()
+        };
+        max
+    })
+}
+
+#[inline]
+fn normalise(value: f64, max_intensity: f64) -> f64 {
+    value / max_intensity
+}
+
+#[inline]
+fn gamma_correction(value: f64, gamma: f64) -> f64 {
+    value.powf(1.0 / gamma)
+}
+
+#[inline]
+fn f64_to_u8(value: f64) -> u8 {
+    (value * 255.0) as u8
+}
+
+pub fn output_ppm(image_data: Arc<ImageData<Vec3<f64>>>, tone_mapping: ToneMapping) {
+    println!("P3");
+    println!("{} {}", image_data.x_size(), image_data.y_size());
+    println!("255");
+    let pixels: Vec<Vec3<f64>> = image_data.deref().into();
+    let max_intensity = max_intensity_from(&pixels);
+    pixels
+        .chunks(image_data.x_size())
+        .rev()
+        .flatten()
+        .for_each(|p| {
+            println!(
+                "{} {} {}",
+                tone_mapping.apply(p.r(), max_intensity),
+                tone_mapping.apply(p.g(), max_intensity),
+                tone_mapping.apply(p.b(), max_intensity),
+            );
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_rgba_image_puts_top_row_first() {
+        let bottom = Vec3::new(1.0, 0.0, 0.0);
+        let top = Vec3::new(0.0, 0.0, 1.0);
+        let image = to_rgba_image(
+            &[bottom, bottom, top, top],
+            2,
+            2,
+            ToneMapping::default(),
+            1.0,
+        );
+        assert_eq!(image.get_pixel(1, 0), &Rgba([0, 0, 255, 255]));
+        assert_eq!(image.get_pixel(0, 1), &Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_load_image_reads_back_a_saved_one() {
+        let pixels = [
+            Vec3::new(1.0, 0.25, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.5, 0.5, 0.5),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        let path = std::env::temp_dir().join("rustray_test_load_image.png");
+        let tone_mapping = ToneMapping::default();
+        to_rgba_image(&pixels, 2, 2, tone_mapping, 1.0)
+            .save(&path)
+            .unwrap();
+        let loaded = load_image(&path, 2, 2, tone_mapping).unwrap();
+        for (loaded, pixel) in loaded.iter().zip(pixels.iter()) {
+            assert!((*loaded - *pixel).length() < 0.01, "{} {}", loaded, pixel);
+        }
+        assert_eq!(
+            load_image(&path, 3, 2, tone_mapping).err().unwrap(),
+            "the image is 2x2, not 3x2"
+        );
+    }
+
+    #[test]
+    fn test_match_base_exposure() {
+        let image_data = ImageData::new_blank(2, 1, Vec3::new(0.5, 0.5, 0.5));
+        image_data.update_pixel(1, 0, |_| Vec3::new(4.0, 2.0, 0.0));
+        let region = Region {
+            x: 1,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+        match_base_exposure(&image_data, region);
+        assert_eq!(image_data.get_pixel(0, 0), Vec3::new(2.0, 2.0, 2.0));
+        assert_eq!(image_data.get_pixel(1, 0), Vec3::new(4.0, 2.0, 0.0));
+    }
+}
@@ -0,0 +1,198 @@
+use std::io::{stderr, IsTerminal, Write};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use display::{max_intensity_from, Display, RenderSession, ToneMapping};
+use stats::{format_duration, format_rate, RenderStats};
+use vec3::Vec3;
+
+/// Reports progress on stderr (stdout carries the image), optionally with a low resolution
+/// preview drawn with ANSI truecolour half blocks that is refreshed after every pass.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct TerminalDisplay {
+    pub preview_columns: Option<usize>,
+    pub refresh_interval: Duration,
+}
+
+impl TerminalDisplay {
+    pub fn new(preview_columns: Option<usize>) -> TerminalDisplay {
+        TerminalDisplay {
+            preview_columns,
+            refresh_interval: Duration::from_millis(250),
+        }
+    }
+
+    /// Reports on the calling thread until the render completes or is cancelled.
+    pub fn run(self, session: RenderSession) {
+        let interactive = stderr().is_terminal();
+        let mut preview_height = 0;
+        let mut last_pass = None;
+        loop {
+            let complete = session.image_data.get_complete();
+            let finished = complete >= 1.0 || session.control.is_cancelled();
+            let pass = session.stats.samples_per_pixel();
+            let mut output = String::new();
+            if interactive {
+                match self.preview_columns {
+                    Some(columns) if last_pass != Some(pass) || finished => {
+                        let lines = preview_lines(&session, columns);
+                        if preview_height > 0 {
+                            // Back over the previous preview and its status line.
+                            output.push_str(&format!("\r\x1b[{}A", preview_height));
+                        }
+                        preview_height = lines.len();
+                        for line in lines {
+                            output.push_str(&line);
+                            output.push('\n');
+                        }
+                    }
+                    _ => {}
+                }
+                output.push_str(&format!(
+                    "\r\x1b[2K{}",
+                    status_line(&session.stats, complete)
+                ));
+                if finished {
+                    output.push('\n');
+                }
+            } else if last_pass != Some(pass) || finished {
+                output.push_str(&status_line(&session.stats, complete));
+                output.push('\n');
+            }
+            last_pass = Some(pass);
+            let mut stderr = stderr();
+            let _ = stderr.write_all(output.as_bytes());
+            let _ = stderr.flush();
+            if finished {
+                break;
+            }
+            thread::sleep(self.refresh_interval);
+        }
+    }
+}
+
+impl Default for TerminalDisplay {
+    fn default() -> TerminalDisplay {
+        TerminalDisplay::new(None)
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn spawn(&self, session: RenderSession) -> JoinHandle<()> {
+        let display = *self;
+        thread::spawn(move || display.run(session))
+    }
+}
+
+fn status_line(stats: &RenderStats, complete: f64) -> String {
+    format!(
+        "{} {:3.0}% {} spp {} rays/s elapsed {} ETA {}",
+        progress_bar(complete, 30),
+        complete * 100.0,
+        stats.samples_per_pixel(),
+        format_rate(stats.rays_per_second()),
+        format_duration(stats.elapsed()),
+        stats
+            .estimated_remaining(complete)
+            .map_or_else(|| "unknown".to_string(), format_duration)
+    )
+}
+
+fn progress_bar(complete: f64, width: usize) -> String {
+    let filled = ((complete.clamp(0.0, 1.0) * width as f64) as usize).min(width);
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(width - filled))
+}
+
+fn preview_lines(session: &RenderSession, columns: usize) -> Vec<String> {
+    let pixels: Vec<Vec3<f64>> = session.image_data.as_ref().into();
+    let max_intensity = max_intensity_from(&pixels);
+    half_block_lines(
+        &pixels,
+        session.image_data.x_size(),
+        session.image_data.y_size(),
+        columns,
+        session.tone_mapping,
+        max_intensity,
+    )
+}
+
+/// Downsamples the canvas (bottom row first) to `columns` wide, drawing two pixels per character
+/// cell: the upper one as the foreground of `▀`, the lower one as the background.
+fn half_block_lines(
+    pixels: &[Vec3<f64>],
+    x_size: usize,
+    y_size: usize,
+    columns: usize,
+    tone_mapping: ToneMapping,
+    max_intensity: f64,
+) -> Vec<String> {
+    let columns = columns.max(1).min(x_size);
+    let rows = ((columns * y_size) as f64 / x_size as f64).round().max(2.0) as usize;
+    let cell = |column: usize, row: usize| -> [u8; 3] {
+        // Average the block of canvas pixels covered by this preview pixel, rows top down.
+        let x_range = (column * x_size / columns)..((column + 1) * x_size / columns);
+        let y_range = (row * y_size / rows)..((row + 1) * y_size / rows);
+        let mut sum = Vec3::new(0.0, 0.0, 0.0);
+        let mut count = 0.0_f64;
+        for y in y_range {
+            for x in x_range.clone() {
+                sum = sum + pixels[(y_size - 1 - y) * x_size + x];
+                count += 1.0;
+            }
+        }
+        let average = sum / count.max(1.0);
+        [
+            tone_mapping.apply(average.r(), max_intensity),
+            tone_mapping.apply(average.g(), max_intensity),
+            tone_mapping.apply(average.b(), max_intensity),
+        ]
+    };
+    (0..rows / 2)
+        .map(|line| {
+            let mut text = String::new();
+            for column in 0..columns {
+                let upper = cell(column, 2 * line);
+                let lower = cell(column, 2 * line + 1);
+                text.push_str(&format!(
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                    upper[0], upper[1], upper[2], lower[0], lower[1], lower[2]
+                ));
+            }
+            text.push_str("\x1b[0m");
+            text
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(progress_bar(0.0, 4), "[----]");
+        assert_eq!(progress_bar(0.5, 4), "[##--]");
+        assert_eq!(progress_bar(1.5, 4), "[####]");
+    }
+
+    #[test]
+    fn test_half_block_lines() {
+        let black = Vec3::new(0.0, 0.0, 0.0);
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        // Bottom row black, top row white.
+        let lines = half_block_lines(
+            &[black, black, white, white],
+            2,
+            2,
+            2,
+            ToneMapping::default(),
+            1.0,
+        );
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            lines[0],
+            "\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m\u{2580}".repeat(2) + "\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use std::env;
+use std::ops::Deref;
+use std::panic;
+use std::sync::RwLock;
+use std::thread;
+use std::thread::JoinHandle;
+
+use image::{ImageBuffer, Rgba};
+use piston_window::*;
+
+use camera::Projection;
+use display::terminal::TerminalDisplay;
+use display::{
+    max_intensity_from, save_snapshot, status_lines, to_rgba_image, Display, RenderSession,
+};
+use hittable::World;
+use image_data::Region;
+use vec3::Vec3;
+
+/// How far, in pixels, the mouse must move while the button is down for a click to become a
+/// drag.
+const DRAG_THRESHOLD: f64 = 4.0;
+
+/// A piston window showing the image as it renders, with click to focus, drag to pick a region
+/// to render and an overlay. Falls back to reporting progress on the terminal if no window can
+/// be opened.
+pub struct WindowDisplay;
+
+impl Display for WindowDisplay {
+    fn spawn(&self, session: RenderSession) -> JoinHandle<()> {
+        thread::spawn(move || run_window(session))
+    }
+}
+
+fn run_window(session: RenderSession) {
+    let width = session.image_data.x_size() as u32;
+    let height = session.image_data.y_size() as u32;
+    let mut window: PistonWindow = match open_window(width, height) {
+        Some(window) => window,
+        None => return TerminalDisplay::default().run(session),
+    };
+    let RenderSession {
+        image_data,
+        camera,
+        world,
+        stats,
+        control,
+        tone_mapping,
+    } = session;
+
+    let mut texture_context = TextureContext {
+        factory: window.factory.clone(),
+        encoder: window.factory.create_command_buffer().into(),
+    };
+
+    let mut glyphs = find_folder::Search::ParentsThenKids(1, 1)
+        .for_folder("assets")
+        .ok()
+        .and_then(|assets| window.load_font(assets.join("FiraSans-Regular.ttf")).ok());
+    if glyphs.is_none() {
+        eprintln!("Could not load the font from the assets folder, the overlay is disabled");
+    }
+
+    let initial_empty = ImageBuffer::new(width, height);
+
+    let mut texture: G2dTexture = Texture::from_image(
+        &mut texture_context,
+        &initial_empty,
+        &TextureSettings::new(),
+    )
+    .unwrap();
+
+    let mut cursor = [0.0, 0.0];
+    let mut drag_start: Option<[f64; 2]> = None;
+    let mut overlay: Vec<String> = vec![];
+    let mut show_hud = true;
+    let (x_size, y_size) = (image_data.x_size(), image_data.y_size());
+
+    while let Some(event) = window.next() {
+        if let Some(position) = event.mouse_cursor_args() {
+            cursor = position;
+        }
+        if let Some(Button::Mouse(MouseButton::Left)) = event.press_args() {
+            drag_start = Some(cursor);
+        }
+        if let (Some(Button::Mouse(MouseButton::Left)), Some(start)) =
+            (event.release_args(), drag_start.take())
+        {
+            if is_drag(start, cursor) {
+                let region = Region::between(
+                    window_to_pixel(start, x_size, y_size),
+                    window_to_pixel(cursor, x_size, y_size),
+                );
+                control.set_region(Some(region));
+                overlay = vec![format!("Rendering {}x{}", region.width, region.height)];
+            } else {
+                let (i, j) = window_to_pixel(cursor, x_size, y_size);
+                overlay = pick_and_focus(
+                    &camera,
+                    &world,
+                    (i as f64 + 0.5) / x_size as f64,
+                    (j as f64 + 0.5) / y_size as f64,
+                );
+            }
+        }
+        match event.press_args() {
+            Some(Button::Keyboard(Key::H)) => show_hud = !show_hud,
+            Some(Button::Keyboard(Key::Space)) => {
+                control.toggle_pause();
+            }
+            Some(Button::Keyboard(Key::S)) => {
+                overlay = vec![save_snapshot(&image_data, tone_mapping)];
+            }
+            Some(Button::Keyboard(Key::R)) => {
+                control.set_region(None);
+                overlay = vec!["Rendering the whole image".to_string()];
+            }
+            _ => {}
+        }
+        if event.render_args().is_some() {
+            let complete = image_data.get_complete();
+            let complete_percentage: String = format!("{:.0}%", complete * 100.0);
+            let pixels: Vec<Vec3<f64>> = image_data.deref().into();
+            let max_intensity = max_intensity_from(&pixels);
+            let mut lines: Vec<String> = if show_hud {
+                status_lines(&image_data, &stats, tone_mapping, max_intensity, complete)
+            } else {
+                vec![]
+            };
+            if control.is_paused() {
+                lines.push("Paused".to_string());
+            }
+            lines.extend(overlay.iter().cloned());
+            let buffer: ImageBuffer<Rgba<u8>, Vec<u8>> =
+                to_rgba_image(&pixels, width, height, tone_mapping, max_intensity);
+            texture.update(&mut texture_context, &buffer).unwrap();
+            // The region being dragged out, or else the one being rendered.
+            let outline = match drag_start {
+                Some(start) if is_drag(start, cursor) => Some(Region::between(
+                    window_to_pixel(start, x_size, y_size),
+                    window_to_pixel(cursor, x_size, y_size),
+                )),
+                _ => control.region(),
+            }
+            .map(|region| region_to_window(region, y_size));
+
+            window.draw_2d(&event, |context, graphics, device| {
+                texture_context.encoder.flush(device);
+                clear([1.0; 4], graphics);
+                image(&texture, context.transform, graphics);
+                if let Some(outline) = outline {
+                    Rectangle::new_border([0.0, 1.0, 0.0, 1.0], 1.0).draw(
+                        outline,
+                        &context.draw_state,
+                        context.transform,
+                        graphics,
+                    );
+                }
+
+                if let Some(ref mut glyphs) = glyphs {
+                    draw_lines(&complete_percentage, &lines, glyphs, &context, graphics);
+                    glyphs.factory.encoder.flush(device);
+                }
+            });
+        }
+    }
+    control.cancel();
+}
+
+/// Opens the window, or explains why it could not be opened. Never panics, so a missing display
+/// does not take the render down with it.
+fn open_window(width: u32, height: u32) -> Option<PistonWindow> {
+    if !has_display() {
+        eprintln!("No display available, rendering without a window");
+        return None;
+    }
+    let built = panic::catch_unwind(|| {
+        WindowSettings::new("RustRay!", [width, height])
+            .exit_on_esc(true)
+            .build()
+    });
+    match built {
+        Ok(Ok(window)) => Some(window),
+        Ok(Err(error)) => {
+            eprintln!("Could not open a window ({}), rendering without one", error);
+            None
+        }
+        Err(_) => {
+            eprintln!("Could not open a window, rendering without one");
+            None
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn has_display() -> bool {
+    env::var_os("DISPLAY").is_some() || env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn has_display() -> bool {
+    true
+}
+
+fn draw_lines(
+    heading: &str,
+    lines: &[String],
+    glyphs: &mut Glyphs,
+    context: &Context,
+    graphics: &mut G2d,
+) {
+    text::Text::new_color([0.0, 1.0, 0.0, 1.0], 32)
+        .draw(
+            heading,
+            glyphs,
+            &context.draw_state,
+            context.transform.trans(10.0, 30.0),
+            graphics,
+        )
+        .unwrap();
+    for (line_number, line) in lines.iter().enumerate() {
+        text::Text::new_color([0.0, 1.0, 0.0, 1.0], 18)
+            .draw(
+                line,
+                glyphs,
+                &context.draw_state,
+                context
+                    .transform
+                    .trans(10.0, 60.0 + 24.0 * line_number as f64),
+                graphics,
+            )
+            .unwrap();
+    }
+}
+
+/// Maps a window coordinate to the image pixel drawn there. Window rows run top to bottom,
+/// canvas rows bottom to top.
+fn window_to_pixel(position: [f64; 2], x_size: usize, y_size: usize) -> (usize, usize) {
+    let x = (position[0].max(0.0) as usize).min(x_size - 1);
+    let y = (position[1].max(0.0) as usize).min(y_size - 1);
+    (x, y_size - 1 - y)
+}
+
+/// The rectangle `[x, y, width, height]` of the window that shows `region`.
+fn region_to_window(region: Region, y_size: usize) -> [f64; 4] {
+    [
+        region.x as f64,
+        (y_size - region.y - region.height) as f64,
+        region.width as f64,
+        region.height as f64,
+    ]
+}
+
+fn is_drag(start: [f64; 2], end: [f64; 2]) -> bool {
+    (start[0] - end[0]).abs() > DRAG_THRESHOLD || (start[1] - end[1]).abs() > DRAG_THRESHOLD
+}
+
+/// Casts a ray through (`s`, `t`) and, if it hits something, refocuses the camera on the hit
+/// point if it has a lens. Returns the overlay lines describing what was picked.
+fn pick_and_focus(
+    camera: &RwLock<Projection<f64>>,
+    world: &World<f64>,
+    s: f64,
+    t: f64,
+) -> Vec<String> {
+    let current = camera.read().unwrap().clone();
+    let ray = current.get_pinhole_ray(s, t);
+    let picked = world.pick(ray, 0.0, f64::MAX);
+    let mut focus_distance = current.focus_distance();
+    if let (Some((_, hit)), Projection::Perspective(lens)) = (&picked, current) {
+        let distance = lens.depth_of(ray.point_at_parameter(*hit.t()));
+        *camera.write().unwrap() = Projection::Perspective(lens.with_focus_distance(distance));
+        focus_distance = Some(distance);
+    }
+    let mut lines: Vec<String> = focus_distance
+        .map(|distance| format!("Focus distance: {:.3}", distance))
+        .into_iter()
+        .collect();
+    match picked {
+        Some((object, hit)) => {
+            lines.push(object.describe());
+            lines.push(format!("{}", hit.material()));
+        }
+        None => lines.push("Nothing picked".to_string()),
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_to_pixel() {
+        assert_eq!(window_to_pixel([0.0, 0.0], 4, 3), (0, 2));
+        assert_eq!(window_to_pixel([3.5, 2.5], 4, 3), (3, 0));
+        assert_eq!(window_to_pixel([-1.0, 10.0], 4, 3), (0, 0));
+    }
+
+    #[test]
+    fn test_region_to_window() {
+        // Dragging from window (1, 0) to (2, 1) in a 4x3 image covers canvas rows 1 and 2.
+        let region = Region::between(
+            window_to_pixel([1.0, 0.0], 4, 3),
+            window_to_pixel([2.0, 1.0], 4, 3),
+        );
+        assert_eq!((region.y, region.height), (1, 2));
+        assert_eq!(region_to_window(region, 3), [1.0, 0.0, 2.0, 2.0]);
+        assert!(!is_drag([10.0, 10.0], [12.0, 7.0]));
+        assert!(is_drag([10.0, 10.0], [10.0, 15.0]));
+    }
+}
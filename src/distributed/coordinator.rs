@@ -0,0 +1,329 @@
+use std::collections::VecDeque;
+use std::io::{self, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use control::RenderControl;
+use display::RenderSession;
+use distributed::{Job, Message, PROTOCOL_VERSION};
+use image_data::ImageData;
+use stats::RenderStats;
+use vec3::Vec3;
+
+/// How long a worker may go without finishing a pass before its job is given to another.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often waiting threads look for a cancelled render.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The jobs not yet finished.
+struct Queue {
+    /// Jobs waiting for a worker, including any given back by workers that dropped out.
+    pending: VecDeque<Job>,
+    finished: Vec<bool>,
+    unfinished: usize,
+    /// Unfinished jobs in each batch of passes over the whole image.
+    unfinished_in_batch: Vec<usize>,
+    tiles: usize,
+}
+
+/// Hands out jobs to the workers that connect, and adds up their results in the image. A
+/// worker that drops out, or goes quiet for longer than the timeout, has its job handed to
+/// another.
+pub struct Coordinator {
+    scene: Message,
+    queue: Mutex<Queue>,
+    changed: Condvar,
+    image_data: Arc<ImageData<Vec3<f64>>>,
+    /// The samples taken of each pixel so far.
+    counts: Mutex<Vec<u32>>,
+    stats: Arc<RenderStats>,
+    control: Arc<RenderControl>,
+    timeout: Duration,
+}
+
+impl Coordinator {
+    /// A coordinator for `jobs`, as made by `split_into_jobs`, of the scene in `scene_text` at
+    /// `frame`, with ids counting up from 0. Results are added up in the session's image.
+    pub fn new(
+        scene_text: String,
+        frame: f64,
+        spectral: bool,
+        jobs: Vec<Job>,
+        session: &RenderSession,
+    ) -> Coordinator {
+        // The second batch starts where the first did.
+        let tiles = jobs
+            .iter()
+            .skip(1)
+            .position(|job| job.region == jobs[0].region)
+            .map_or(jobs.len(), |position| position + 1);
+        let unfinished_in_batch = jobs.chunks(tiles.max(1)).map(<[Job]>::len).collect();
+        let image_data = Arc::clone(&session.image_data);
+        let pixels = image_data.x_size() * image_data.y_size();
+        Coordinator {
+            scene: Message::Scene {
+                frame,
+                spectral,
+                text: scene_text,
+            },
+            queue: Mutex::new(Queue {
+                finished: vec![false; jobs.len()],
+                unfinished: jobs.len(),
+                pending: jobs.into_iter().collect(),
+                unfinished_in_batch,
+                tiles,
+            }),
+            changed: Condvar::new(),
+            image_data,
+            counts: Mutex::new(vec![0; pixels]),
+            stats: Arc::clone(&session.stats),
+            control: Arc::clone(&session.control),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(self, timeout: Duration) -> Coordinator {
+        Coordinator { timeout, ..self }
+    }
+
+    /// Accepts workers on `listener` from now on, each served by a thread of its own.
+    pub fn serve(coordinator: Arc<Coordinator>, listener: TcpListener) -> JoinHandle<()> {
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let coordinator = Arc::clone(&coordinator);
+                thread::spawn(move || {
+                    let peer = stream
+                        .peer_addr()
+                        .map_or("?".to_string(), |address| address.to_string());
+                    if let Err(error) = coordinator.serve_worker(stream) {
+                        eprintln!("Worker {} dropped out: {}", peer, error);
+                    }
+                });
+            }
+        })
+    }
+
+    /// Blocks until every job is finished or the render is cancelled.
+    pub fn wait(&self) {
+        let mut queue = self.queue.lock().unwrap();
+        while queue.unfinished > 0 && !self.control.is_cancelled() {
+            queue = self.changed.wait_timeout(queue, POLL_INTERVAL).unwrap().0;
+        }
+    }
+
+    fn serve_worker(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(self.timeout))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+        }
+        .write_to(&mut writer)?;
+        self.scene.write_to(&mut writer)?;
+        while let Some(job) = self.next_job() {
+            Message::Job(job).write_to(&mut writer)?;
+            if let Err(error) = self.wait_for_result(job, &mut reader) {
+                self.give_back(job);
+                return Err(error);
+            }
+        }
+        Message::Done.write_to(&mut writer)
+    }
+
+    fn wait_for_result(&self, job: Job, reader: &mut BufReader<TcpStream>) -> io::Result<()> {
+        let region = job.region;
+        loop {
+            match Message::read_from(reader, region.width * region.height)? {
+                Message::Pass => {}
+                Message::Result { id, rays, sums }
+                    if id == job.id && sums.len() == region.width * region.height =>
+                {
+                    // One infinite or NaN sum would spoil its pixel for good.
+                    if !sums
+                        .iter()
+                        .all(|sum| sum.x.is_finite() && sum.y.is_finite() && sum.z.is_finite())
+                    {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("the result of job {} is not finite", id),
+                        ));
+                    }
+                    self.stats.add_rays(rays);
+                    self.finish(job, &sums);
+                    return Ok(());
+                }
+                Message::Error(message) => return Err(io::Error::other(message)),
+                other => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unexpected {:?}", other),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// The next job to hand out, waiting for one to be given back if all are out with workers.
+    /// `None` once all are finished or the render is cancelled.
+    fn next_job(&self) -> Option<Job> {
+        loop {
+            self.control.wait_while_paused();
+            let mut queue = self.queue.lock().unwrap();
+            if self.control.is_cancelled() || queue.unfinished == 0 {
+                return None;
+            }
+            if let Some(job) = queue.pending.pop_front() {
+                return Some(job);
+            }
+            let _ = self.changed.wait_timeout(queue, POLL_INTERVAL).unwrap();
+        }
+    }
+
+    fn give_back(&self, job: Job) {
+        let mut queue = self.queue.lock().unwrap();
+        // Handed out again first, so a gap is not left in the image.
+        queue.pending.push_front(job);
+        self.changed.notify_all();
+    }
+
+    /// Adds a job's sums to the image, weighting each pixel's average by its samples.
+    fn finish(&self, job: Job, sums: &[Vec3<f64>]) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.finished[job.id] {
+            return;
+        }
+        queue.finished[job.id] = true;
+        queue.unfinished -= 1;
+        {
+            let mut counts = self.counts.lock().unwrap();
+            let region = job.region;
+            for (index, &sum) in sums.iter().enumerate() {
+                let (x, y) = (
+                    region.x + index % region.width,
+                    region.y + index / region.width,
+                );
+                let count = &mut counts[y * self.image_data.x_size() + x];
+                let before = f64::from(*count);
+                *count += job.passes;
+                let after = f64::from(*count);
+                self.image_data
+                    .update_pixel(x, y, |average| (average * before + sum) / after);
+            }
+        }
+        let batch = job.id / queue.tiles;
+        queue.unfinished_in_batch[batch] -= 1;
+        if queue.unfinished_in_batch[batch] == 0 {
+            for _ in 0..job.passes {
+                self.stats.pass_complete();
+            }
+        }
+        let jobs = queue.finished.len();
+        self.image_data
+            .update_complete(|_| (jobs - queue.unfinished) as f64 / jobs as f64);
+        self.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use display::ToneMapping;
+    use distributed::{split_into_jobs, worker};
+    use hittable::World;
+    use scene::default_camera;
+    use std::path::Path;
+    use std::sync::RwLock;
+
+    const SCENE: &str = r#"{"width": 5, "height": 3, "samples": 3,
+        "background": {"type": "solid", "color": [0.25, 0.5, 1]}, "objects": []}"#;
+
+    /// A coordinator of `SCENE` in 2 pixel tiles of 2 passes, serving on the address returned.
+    fn start() -> (RenderSession, Arc<Coordinator>, String) {
+        let session = RenderSession {
+            image_data: Arc::new(ImageData::new_blank(5, 3, Vec3::new(0.0, 0.0, 0.0))),
+            camera: Arc::new(RwLock::new(default_camera(5.0 / 3.0))),
+            world: Arc::new(World::new(vec![])),
+            stats: Arc::new(RenderStats::new()),
+            control: Arc::new(RenderControl::new()),
+            tone_mapping: ToneMapping::default(),
+        };
+        let jobs = split_into_jobs(5, 3, 2, 3, 2);
+        let coordinator = Arc::new(Coordinator::new(
+            SCENE.to_string(),
+            0.0,
+            false,
+            jobs,
+            &session,
+        ));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        Coordinator::serve(Arc::clone(&coordinator), listener);
+        (session, coordinator, address)
+    }
+
+    /// Connects as a worker and takes the first job.
+    fn take_job(address: &str) -> (BufReader<TcpStream>, Job) {
+        let mut reader = BufReader::new(TcpStream::connect(address).unwrap());
+        for _ in 0..2 {
+            Message::read_from(&mut reader, 0).unwrap();
+        }
+        match Message::read_from(&mut reader, 0).unwrap() {
+            Message::Job(job) => (reader, job),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    /// Renders the rest of the image with two workers, and checks it is all the background.
+    fn finish_and_check(session: &RenderSession, coordinator: &Coordinator, address: &str) {
+        let workers: Vec<JoinHandle<Result<(), String>>> = (0..2)
+            .map(|_| {
+                let address = address.to_string();
+                thread::spawn(move || worker::work(&address, Path::new(".")))
+            })
+            .collect();
+        coordinator.wait();
+        for worker in workers {
+            worker.join().unwrap().unwrap();
+        }
+
+        assert_eq!(*coordinator.counts.lock().unwrap(), vec![3; 15]);
+        let pixels: Vec<Vec3<f64>> = session.image_data.as_ref().into();
+        for pixel in pixels {
+            assert!(
+                (pixel - Vec3::new(0.25, 0.5, 1.0)).length() < 1e-12,
+                "{}",
+                pixel
+            );
+        }
+        assert_eq!(session.stats.samples_per_pixel(), 3);
+        assert_eq!(session.image_data.get_complete(), 1.0);
+    }
+
+    #[test]
+    fn test_workers_dropping_out_have_their_jobs_reassigned() {
+        let (session, coordinator, address) = start();
+        // A worker that takes a job and vanishes.
+        assert_eq!(take_job(&address).1.id, 0);
+        finish_and_check(&session, &coordinator, &address);
+    }
+
+    #[test]
+    fn test_results_that_are_not_finite_are_refused() {
+        let (session, coordinator, address) = start();
+        let (mut reader, job) = take_job(&address);
+        let mut sums = vec![Vec3::new(1.0, 1.0, 1.0); job.region.width * job.region.height];
+        sums[1].y = f64::NAN;
+        Message::Result {
+            id: job.id,
+            rays: 1,
+            sums,
+        }
+        .write_to(reader.get_mut())
+        .unwrap();
+        assert!(Message::read_from(&mut reader, 0).is_err());
+        finish_and_check(&session, &coordinator, &address);
+    }
+}
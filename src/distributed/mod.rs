@@ -0,0 +1,307 @@
+//! Rendering on several machines at once: a coordinator splits the image into jobs of a few
+//! passes over a tile each, and hands them out over TCP to workers, which send back the sums of
+//! their samples.
+//!
+//! The protocol is a line of text per message, followed by raw little endian data for the scene
+//! and for results. The coordinator starts with `rustray <version>` and the scene, then sends
+//! jobs one at a time, and finally `done`. A worker answers each job with `pass` after every
+//! pass, so the coordinator can tell it is still alive, and then the result.
+
+pub mod coordinator;
+pub mod worker;
+
+use std::convert::TryInto;
+use std::io::{self, BufRead, Write};
+
+use image_data::Region;
+use vec3::Vec3;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Where the coordinator listens unless told otherwise. Only this machine can reach it, as
+/// anyone who can is sent the scene and may send back whatever they like.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+/// The longest scene accepted, so a bad length cannot make a worker allocate without limit.
+const MAX_SCENE_SIZE: usize = 64 << 20;
+
+/// Some passes over a tile of the image.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Job {
+    pub id: usize,
+    pub region: Region,
+    pub passes: u32,
+}
+
+/// Splits `samples` passes over an image into jobs of `batch` passes over square tiles, the
+/// first passes over every tile coming before the next, so the image sharpens all over at once.
+pub fn split_into_jobs(
+    width: usize,
+    height: usize,
+    tile_size: usize,
+    samples: u32,
+    batch: u32,
+) -> Vec<Job> {
+    let mut jobs = vec![];
+    let mut done = 0;
+    while done < samples {
+        let passes = batch.min(samples - done);
+        for y in (0..height).step_by(tile_size) {
+            for x in (0..width).step_by(tile_size) {
+                jobs.push(Job {
+                    id: jobs.len(),
+                    region: Region {
+                        x,
+                        y,
+                        width: tile_size.min(width - x),
+                        height: tile_size.min(height - y),
+                    },
+                    passes,
+                });
+            }
+        }
+        done += passes;
+    }
+    jobs
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Message {
+    /// The coordinator's greeting.
+    Hello {
+        version: u32,
+    },
+    /// The scene file's text, at `frame`.
+    Scene {
+        frame: f64,
+        spectral: bool,
+        text: String,
+    },
+    Job(Job),
+    /// No more jobs: the image is complete.
+    Done,
+    /// A worker has finished one of the passes of its job.
+    Pass,
+    /// The sums of a job's samples over its tile, bottom row first, and the rays it traced.
+    Result {
+        id: usize,
+        rays: u64,
+        sums: Vec<Vec3<f64>>,
+    },
+    /// A worker could not go on, e.g. because it could not load the scene.
+    Error(String),
+}
+
+impl Message {
+    pub fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Message::Hello { version } => writeln!(writer, "rustray {}", version)?,
+            Message::Scene {
+                frame,
+                spectral,
+                text,
+            } => {
+                writeln!(writer, "scene {} {} {}", frame, spectral, text.len())?;
+                writer.write_all(text.as_bytes())?;
+            }
+            Message::Job(Job { id, region, passes }) => writeln!(
+                writer,
+                "job {} {} {} {} {} {}",
+                id, region.x, region.y, region.width, region.height, passes
+            )?,
+            Message::Done => writeln!(writer, "done")?,
+            Message::Pass => writeln!(writer, "pass")?,
+            Message::Result { id, rays, sums } => {
+                writeln!(writer, "result {} {} {}", id, rays, sums.len())?;
+                let mut bytes = Vec::with_capacity(24 * sums.len());
+                for sum in sums {
+                    for channel in &[sum.x, sum.y, sum.z] {
+                        bytes.extend_from_slice(&channel.to_le_bytes());
+                    }
+                }
+                writer.write_all(&bytes)?;
+            }
+            Message::Error(message) => writeln!(writer, "error {}", message.replace('\n', " "))?,
+        }
+        writer.flush()
+    }
+
+    /// Reads the next message, which may be a result of at most `max_sums` pixels, e.g. those
+    /// of the job a worker was given. Fails with `UnexpectedEof` if the other end has gone away,
+    /// and `InvalidData` if what it sent is not a message, or is larger than allowed. Sizes are
+    /// checked before anything is allocated for the data.
+    pub fn read_from(reader: &mut dyn BufRead, max_sums: usize) -> io::Result<Message> {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the connection was closed",
+            ));
+        }
+        let line = line.trim_end();
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected message '{}'", line),
+            )
+        };
+        let (kind, rest) = match line.find(' ') {
+            Some(space) => (&line[..space], &line[space + 1..]),
+            None => (line, ""),
+        };
+        if kind == "error" {
+            return Ok(Message::Error(rest.to_string()));
+        }
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        let number = |i: usize| -> io::Result<usize> {
+            fields
+                .get(i)
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let too_large = |what: &str, size: usize, limit: usize| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} of {} is more than the {} allowed", what, size, limit),
+            )
+        };
+        let message = match (kind, fields.len()) {
+            ("rustray", 1) => Message::Hello {
+                version: number(0)? as u32,
+            },
+            ("scene", 3) => {
+                let frame = fields[0].parse().map_err(|_| invalid())?;
+                let spectral = fields[1].parse().map_err(|_| invalid())?;
+                let size = number(2)?;
+                if size > MAX_SCENE_SIZE {
+                    return Err(too_large("a scene", size, MAX_SCENE_SIZE));
+                }
+                let mut text = vec![0; size];
+                reader.read_exact(&mut text)?;
+                Message::Scene {
+                    frame,
+                    spectral,
+                    text: String::from_utf8(text).map_err(|_| invalid())?,
+                }
+            }
+            ("job", 6) => Message::Job(Job {
+                id: number(0)?,
+                region: Region {
+                    x: number(1)?,
+                    y: number(2)?,
+                    width: number(3)?,
+                    height: number(4)?,
+                },
+                passes: number(5)? as u32,
+            }),
+            ("done", 0) => Message::Done,
+            ("pass", 0) => Message::Pass,
+            ("result", 3) => {
+                let id = number(0)?;
+                let rays = fields[1].parse().map_err(|_| invalid())?;
+                let count = number(2)?;
+                if count > max_sums {
+                    return Err(too_large("a result", count, max_sums));
+                }
+                let mut bytes = vec![0; 24 * count];
+                reader.read_exact(&mut bytes)?;
+                let channels: Vec<f64> = bytes
+                    .chunks(8)
+                    .map(|channel| f64::from_le_bytes(channel.try_into().unwrap()))
+                    .collect();
+                let sums = channels
+                    .chunks(3)
+                    .map(|sum| Vec3::new(sum[0], sum[1], sum[2]))
+                    .collect();
+                Message::Result { id, rays, sums }
+            }
+            _ => return Err(invalid()),
+        };
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_split_into_jobs() {
+        let jobs = split_into_jobs(5, 3, 2, 5, 2);
+        // Six tiles, and passes in batches of 2, 2 and 1.
+        assert_eq!(jobs.len(), 18);
+        assert_eq!(
+            jobs[5],
+            Job {
+                id: 5,
+                region: Region {
+                    x: 4,
+                    y: 2,
+                    width: 1,
+                    height: 1
+                },
+                passes: 2
+            }
+        );
+        assert_eq!(jobs[17].passes, 1);
+        let samples: u32 = jobs
+            .iter()
+            .filter(|job| job.region.contains(4, 2))
+            .map(|job| job.passes)
+            .sum();
+        assert_eq!(samples, 5);
+    }
+
+    #[test]
+    fn test_messages_round_trip() {
+        let messages = vec![
+            Message::Hello { version: 1 },
+            Message::Scene {
+                frame: 2.5,
+                spectral: true,
+                text: "{\"width\": 2,\n \"height\": 1}".to_string(),
+            },
+            Message::Job(split_into_jobs(4, 4, 2, 1, 1)[3]),
+            Message::Pass,
+            Message::Result {
+                id: 3,
+                rays: 1234,
+                sums: vec![Vec3::new(0.5, 1.0, 2.0), Vec3::new(0.0, 0.25, 3.0)],
+            },
+            Message::Error("no such file".to_string()),
+            Message::Done,
+        ];
+        let mut bytes = vec![];
+        for message in &messages {
+            message.write_to(&mut bytes).unwrap();
+        }
+        let mut reader = Cursor::new(bytes);
+        for message in &messages {
+            assert_eq!(&Message::read_from(&mut reader, 2).unwrap(), message);
+        }
+        assert_eq!(
+            Message::read_from(&mut reader, 2).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+        let error = Message::read_from(&mut Cursor::new("job 1 2\n"), 2).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_oversized_messages_are_refused() {
+        let read = |text: &str| Message::read_from(&mut Cursor::new(text), 4).unwrap_err();
+        let error = read("result 3 10 5\n");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "a result of 5 is more than the 4 allowed"
+        );
+        // Far too large to allocate, or to multiply by the size of a pixel without overflowing.
+        let error = read("result 3 10 18446744073709551615\n");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = read("scene 0 false 1000000000000\n");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("a scene of 1000000000000"));
+    }
+}
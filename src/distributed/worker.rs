@@ -0,0 +1,143 @@
+use std::io::BufReader;
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::RwLock;
+use std::thread;
+use std::time::Duration;
+
+use camera::Projection;
+use control::RenderControl;
+use distributed::{Job, Message, PROTOCOL_VERSION};
+use image_data::ImageData;
+use scene::{self, Scene};
+use stats::RenderStats;
+use tracer::{draw_to_image_data, ColourMode, RenderSettings};
+use vec3::Vec3;
+
+/// How many times, a second apart, to try to reach a coordinator that may not have started.
+const CONNECT_ATTEMPTS: u32 = 30;
+
+/// Connects to the coordinator at `address` and renders the jobs it hands out until it says the
+/// image is done. Files the scene refers to, such as environment maps, are looked for relative
+/// to `directory`.
+pub fn work(address: &str, directory: &Path) -> Result<(), String> {
+    let stream = connect(address)?;
+    let describe = |error: &dyn std::fmt::Display| format!("{}: {}", address, error);
+    let mut reader = BufReader::new(stream.try_clone().map_err(|error| describe(&error))?);
+    let mut writer = stream;
+    // Coordinators never send results, so a worker accepts none.
+    let mut read = move || Message::read_from(&mut reader, 0).map_err(|error| describe(&error));
+
+    match read()? {
+        Message::Hello { version } if version == PROTOCOL_VERSION => {}
+        Message::Hello { version } => {
+            return Err(describe(&format!(
+                "the coordinator speaks version {} of the protocol, not {}",
+                version, PROTOCOL_VERSION
+            )))
+        }
+        other => return Err(describe(&format!("unexpected {:?}", other))),
+    }
+    let (scene, colour_mode) = match read()? {
+        Message::Scene {
+            frame,
+            spectral,
+            text,
+        } => match scene::parse(&text, directory, frame) {
+            Ok(scene) => {
+                let colour_mode = if scene.spectral || spectral {
+                    ColourMode::Spectral
+                } else {
+                    ColourMode::Rgb
+                };
+                (scene, colour_mode)
+            }
+            Err(error) => {
+                let _ = Message::Error(error.to_string()).write_to(&mut writer);
+                return Err(describe(&error));
+            }
+        },
+        other => return Err(describe(&format!("unexpected {:?}", other))),
+    };
+
+    let image_data = ImageData::new_blank(scene.width, scene.height, Vec3::new(0.0, 0.0, 0.0));
+    let camera = RwLock::new(scene.camera.clone());
+    loop {
+        let job = match read()? {
+            Message::Job(job) => job,
+            Message::Done => return Ok(()),
+            other => return Err(describe(&format!("unexpected {:?}", other))),
+        };
+        let region = job.region;
+        if region.x + region.width > scene.width || region.y + region.height > scene.height {
+            let error = format!("job {} is outside the image", job.id);
+            let _ = Message::Error(error.clone()).write_to(&mut writer);
+            return Err(describe(&error));
+        }
+        let heartbeat = writer.try_clone().map_err(|error| describe(&error))?;
+        let result = render_job(&scene, &image_data, &camera, job, colour_mode, heartbeat);
+        result
+            .write_to(&mut writer)
+            .map_err(|error| describe(&error))?;
+    }
+}
+
+fn connect(address: &str) -> Result<TcpStream, String> {
+    let mut attempt = 1;
+    loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => return Ok(stream),
+            Err(error) if attempt == CONNECT_ATTEMPTS => {
+                return Err(format!("Could not connect to {}: {}", address, error))
+            }
+            Err(_) => {
+                attempt += 1;
+                thread::sleep(Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+/// Renders `job`, telling the coordinator through `heartbeat` as each pass is done, and returns
+/// the result to send back.
+fn render_job(
+    scene: &Scene,
+    image_data: &ImageData<Vec3<f64>>,
+    camera: &RwLock<Projection<f64>>,
+    job: Job,
+    colour_mode: ColourMode,
+    mut heartbeat: TcpStream,
+) -> Message {
+    let control = RenderControl::new();
+    control.set_region(Some(job.region));
+    // A failure to send shows up when the result is sent.
+    control.set_after_pass(Box::new(move |_, _| {
+        let _ = Message::Pass.write_to(&mut heartbeat);
+    }));
+    let stats = RenderStats::new();
+    draw_to_image_data(
+        image_data,
+        camera,
+        &scene.world,
+        &scene.lighting,
+        RenderSettings {
+            samples: job.passes as i32,
+            colour_mode,
+            shutter: scene.shutter,
+            start_pass: 0,
+            seed: rand::random(),
+        },
+        &stats,
+        &control,
+    );
+    let region = job.region;
+    let sums = (region.y..region.y + region.height)
+        .flat_map(|y| (region.x..region.x + region.width).map(move |x| (x, y)))
+        .map(|(x, y)| image_data.get_pixel(x, y) * f64::from(job.passes))
+        .collect();
+    Message::Result {
+        id: job.id,
+        rays: stats.rays(),
+        sums,
+    }
+}
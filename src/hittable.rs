@@ -1,14 +1,57 @@
+use bvh::Bvh;
 use material::Material;
-use ray::Ray;
-use vec3::{NumWithVectorOps, Vec3, VectorWithOps};
+use ray::{gamma, Ray};
+use simd::{PackedSphere, PacketHits, RayPacket, SimdLevel, PACKET_WIDTH};
+use vec3::aabb::Aabb;
+use vec3::{Float, NumWithVectorOps, Vec3, VectorWithOps};
 
 pub trait Hittable<T>: Send + Sync {
     fn hit(&self, ray: Ray<T>, min_t: T, max_t: T) -> Option<Hit<T>>;
+
+    /// A short human readable summary of the object, e.g. for the viewer overlay.
+    #[cfg(any(feature = "window", test))]
+    fn describe(&self) -> String;
+
+    /// A box the object stays inside at every time it may be traced, or `None` if it has no
+    /// bounds, like fog filling all of space.
+    fn bounds(&self) -> Option<Aabb<T>>;
+
+    /// Traces many rays at once, e.g. a row of primary rays. Objects that can do better than one
+    /// ray at a time override this.
+    fn hit_packet(&self, rays: &[Ray<T>], min_t: T, max_t: T) -> Vec<Option<Hit<T>>>
+    where
+        T: Copy,
+    {
+        rays.iter()
+            .map(|ray| self.hit(*ray, min_t, max_t))
+            .collect()
+    }
+
+    /// The fraction of light that gets through along `ray` between `min_t` and `max_t`, for
+    /// shadow rays. Surfaces block light entirely; media let some of it through.
+    fn transmittance(&self, ray: Ray<T>, min_t: T, max_t: T) -> T
+    where
+        T: Float,
+    {
+        if self.hit(ray, min_t, max_t).is_some() {
+            T::from_f64(0.0)
+        } else {
+            T::from_f64(1.0)
+        }
+    }
+
+    /// Lets `World` gather its spheres for the SIMD packet kernels.
+    fn as_sphere(&self) -> Option<&Sphere<T>> {
+        None
+    }
 }
 
 pub struct Hit<T> {
     pub t: T,
+    /// The hit point and the outward geometric normal there.
     pub normal: Ray<T>,
+    /// Bounds the floating point error in each coordinate of the hit point.
+    pub error: Vec3<T>,
     pub material: Material<T>,
 }
 
@@ -18,15 +61,17 @@ where
     Vec3<T>: VectorWithOps<T>,
 {
     #[inline]
-    pub fn new(t: T, normal: Ray<T>, material: Material<T>) -> Hit<T> {
+    pub fn new(t: T, normal: Ray<T>, error: Vec3<T>, material: Material<T>) -> Hit<T> {
         Hit {
             t,
             normal,
+            error,
             material,
         }
     }
 
     #[inline]
+    #[cfg(any(feature = "window", test))]
     pub fn t(&self) -> &T {
         &self.t
     }
@@ -42,90 +87,527 @@ where
     }
 }
 
+impl<T: Float> Hit<T> {
+    /// A ray leaving the hit point in `direction` that cannot hit this surface again at `t`
+    /// close to zero, so it can be traced with a `min_t` of zero.
+    pub fn spawn_ray(&self, direction: Vec3<T>) -> Ray<T> {
+        Ray::spawn(
+            *self.normal.origin(),
+            self.error,
+            *self.normal.direction(),
+            direction,
+        )
+    }
+}
+
 pub struct World<T> {
     pub objects: Vec<Box<dyn Hittable<T>>>,
+    /// The objects with bounds, so rays need only be tried against those they pass near.
+    pub bvh: Bvh<T>,
+    /// The indices of the objects without bounds, which every ray is tried against.
+    pub unbounded: Vec<usize>,
+    /// Single precision copies of the static spheres in `objects`, by index, for the packet
+    /// kernels.
+    pub packed_spheres: Vec<Option<PackedSphere>>,
+    pub simd_level: SimdLevel,
 }
 
-impl World<f64> {
-    #[inline]
-    pub fn new(objects: Vec<Box<dyn Hittable<f64>>>) -> World<f64> {
-        World { objects }
+impl<T: Float> World<T> {
+    pub fn new(objects: Vec<Box<dyn Hittable<T>>>) -> World<T> {
+        World::with_simd_level(objects, SimdLevel::detect())
+    }
+
+    pub fn with_simd_level(objects: Vec<Box<dyn Hittable<T>>>, simd_level: SimdLevel) -> World<T> {
+        let mut bounded = vec![];
+        let mut unbounded = vec![];
+        for (index, object) in objects.iter().enumerate() {
+            match object.bounds() {
+                Some(bounds) => bounded.push((index, bounds)),
+                None => unbounded.push(index),
+            }
+        }
+        let packed_spheres = objects
+            .iter()
+            .map(|object| {
+                object.as_sphere().map(|sphere| {
+                    let center = sphere.center().to_f64();
+                    PackedSphere {
+                        center: [center.x as f32, center.y as f32, center.z as f32],
+                        radius: sphere.radius.to_f64() as f32,
+                    }
+                })
+            })
+            .collect();
+        World {
+            objects,
+            bvh: Bvh::new(bounded),
+            unbounded,
+            packed_spheres,
+            simd_level,
+        }
     }
 
     #[inline]
-    pub fn objects(&self) -> &Vec<Box<dyn Hittable<f64>>> {
+    pub fn objects(&self) -> &Vec<Box<dyn Hittable<T>>> {
         &self.objects
     }
+
+    /// Like `hit`, but also returns the object that was hit.
+    #[cfg(any(feature = "window", test))]
+    pub fn pick(&self, ray: Ray<T>, min_t: T, max_t: T) -> Option<(&dyn Hittable<T>, Hit<T>)> {
+        self.nearest(ray, min_t, max_t)
+            .map(|(index, hit)| (self.objects[index].as_ref(), hit))
+    }
+
+    /// The nearest hit along `ray` and the index of the object hit.
+    fn nearest(&self, ray: Ray<T>, min_t: T, max_t: T) -> Option<(usize, Hit<T>)> {
+        let mut nearest = self.nearest_unbounded(ray, min_t, max_t);
+        let max_t = nearest.as_ref().map_or(max_t, |(_, hit)| hit.t);
+        self.bvh.traverse(&ray, min_t, max_t, |index, max_t| {
+            Some(match self.objects[index].hit(ray, min_t, max_t) {
+                Some(hit) => {
+                    let t = hit.t;
+                    nearest = Some((index, hit));
+                    t
+                }
+                None => max_t,
+            })
+        });
+        nearest
+    }
+
+    /// The nearest hit on the objects without bounds, which the BVH leaves out.
+    fn nearest_unbounded(&self, ray: Ray<T>, min_t: T, max_t: T) -> Option<(usize, Hit<T>)> {
+        let mut nearest = None;
+        let mut max_t = max_t;
+        for &index in &self.unbounded {
+            if let Some(hit) = self.objects[index].hit(ray, min_t, max_t) {
+                max_t = hit.t;
+                nearest = Some((index, hit));
+            }
+        }
+        nearest
+    }
 }
 
-impl Hittable<f64> for World<f64> {
-    fn hit(&self, ray: Ray<f64>, min_t: f64, max_t: f64) -> Option<Hit<f64>> {
-        self.objects()
-            .iter()
-            .map(|hittable| hittable.hit(ray, min_t, max_t))
-            .flatten()
-            .min_by(|hit_a, hit_b| hit_a.t().partial_cmp(&hit_b.t()).unwrap())
+impl<T: Float> Hittable<T> for World<T> {
+    fn hit(&self, ray: Ray<T>, min_t: T, max_t: T) -> Option<Hit<T>> {
+        self.nearest(ray, min_t, max_t).map(|(_, hit)| hit)
+    }
+
+    #[cfg(any(feature = "window", test))]
+    fn describe(&self) -> String {
+        format!("World of {} objects", self.objects().len())
+    }
+
+    fn bounds(&self) -> Option<Aabb<T>> {
+        let mut objects = self.objects().iter();
+        let first = objects.next()?.bounds();
+        objects.fold(first, |bounds, object| {
+            Some(bounds?.union(object.bounds()?))
+        })
+    }
+
+    fn transmittance(&self, ray: Ray<T>, min_t: T, max_t: T) -> T {
+        let zero = T::from_f64(0.0);
+        let mut transmittance = T::from_f64(1.0);
+        for &index in &self.unbounded {
+            transmittance = transmittance * self.objects[index].transmittance(ray, min_t, max_t);
+        }
+        if transmittance <= zero {
+            return zero;
+        }
+        self.bvh.traverse(&ray, min_t, max_t, |index, max_t| {
+            transmittance = transmittance * self.objects[index].transmittance(ray, min_t, max_t);
+            Some(max_t).filter(|_| transmittance > zero)
+        });
+        transmittance
+    }
+
+    /// Traces each packet of rays through the BVH together, testing static spheres with the
+    /// SIMD kernels and other objects one ray at a time, then confirms the nearest hit of each
+    /// ray at full precision. Rays whose hit single precision got wrong are traced again by
+    /// `hit`. Single precision may still disagree with `hit` on the odd ray that grazes a
+    /// silhouette.
+    fn hit_packet(&self, rays: &[Ray<T>], min_t: T, max_t: T) -> Vec<Option<Hit<T>>> {
+        let mut hits = Vec::with_capacity(rays.len());
+        for chunk in rays.chunks(PACKET_WIDTH) {
+            let packet = RayPacket::new(chunk);
+            let mut nearest = PacketHits::new(&packet, max_t.to_f64() as f32);
+            self.bvh.traverse_packet(
+                self.simd_level,
+                &packet,
+                &self.packed_spheres,
+                min_t.to_f64() as f32,
+                &mut nearest,
+                |index, lanes, nearest| {
+                    for lane in lanes.filter(|&lane| lane < chunk.len()) {
+                        let max_t = T::from_f64(f64::from(nearest.t[lane]));
+                        if let Some(hit) = self.objects[index].hit(chunk[lane], min_t, max_t) {
+                            nearest.set(lane, hit.t.to_f64() as f32, index);
+                        }
+                    }
+                },
+            );
+            for (lane, ray) in chunk.iter().enumerate() {
+                let hit = match nearest.index(lane) {
+                    Some(index) => match self.objects[index].hit(*ray, min_t, max_t) {
+                        Some(hit) => {
+                            let unbounded = self.nearest_unbounded(*ray, min_t, hit.t);
+                            unbounded.map(|(_, hit)| hit).or(Some(hit))
+                        }
+                        None => self.hit(*ray, min_t, max_t),
+                    },
+                    None => self
+                        .nearest_unbounded(*ray, min_t, max_t)
+                        .map(|(_, hit)| hit),
+                };
+                hits.push(hit);
+            }
+        }
+        hits
     }
 }
 
 pub struct Sphere<T> {
+    /// The centre at time 0.
     pub center: Vec3<T>,
+    /// The centre at time 1. The sphere moves in a straight line between the two, and stays
+    /// put before and after.
+    pub end_center: Vec3<T>,
+    /// Greater than zero: a hit point is the centre plus the radius along the normal, which
+    /// would be on the wrong side for a negative radius and undefined for a zero one.
     pub radius: T,
     pub material: Material<T>,
 }
 
-impl Sphere<f64> {
+impl<T: Float> Sphere<T> {
     #[inline]
-    pub fn new(center: Vec3<f64>, radius: f64, material: Material<f64>) -> Sphere<f64> {
+    pub fn new(center: Vec3<T>, radius: T, material: Material<T>) -> Sphere<T> {
+        Sphere::moving(center, center, radius, material)
+    }
+
+    pub fn moving(
+        center: Vec3<T>,
+        end_center: Vec3<T>,
+        radius: T,
+        material: Material<T>,
+    ) -> Sphere<T> {
         Sphere {
             center,
+            end_center,
             radius,
             material,
         }
     }
 
     #[inline]
-    pub fn center(&self) -> &Vec3<f64> {
+    pub fn center(&self) -> &Vec3<T> {
         &self.center
     }
 
-    #[inline]
-    #[allow(dead_code)]
-    pub fn radius(self) -> f64 {
-        self.radius
+    pub fn center_at(&self, time: T) -> Vec3<T> {
+        let time = time.max(T::from_f64(0.0)).min(T::from_f64(1.0));
+        self.center + (self.end_center - self.center) * time
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.end_center != self.center
     }
 
     #[inline]
-    pub fn material(&self) -> &Material<f64> {
+    pub fn material(&self) -> &Material<T> {
         &self.material
     }
 }
 
-impl Hittable<f64> for Sphere<f64> {
-    fn hit(&self, ray: Ray<f64>, min_t: f64, max_t: f64) -> Option<Hit<f64>> {
-        let oc = *ray.origin() - self.center;
-        let a = ray.direction().dot(*ray.direction());
-        let b = oc.dot(*ray.direction());
+impl<T: Float> Sphere<T> {
+    /// Builds the hit at `t` on the sphere centred at `center`, projecting the computed point
+    /// back onto it and bounding its error so scattered rays can be spawned clear of the
+    /// surface.
+    fn hit_at(&self, ray: Ray<T>, t: T, center: Vec3<T>) -> Hit<T> {
+        let local = ray.point_at_parameter(t) - center;
+        let local = local * (self.radius / local.length());
+        let hit_point = center + local;
+        let error =
+            local.map(Float::abs) * gamma::<T>(5) + hit_point.map(Float::abs) * gamma::<T>(1);
+        Hit::new(
+            t,
+            Ray::new(hit_point, local / self.radius),
+            error,
+            *self.material(),
+        )
+    }
+}
+
+impl<T: Float> Hittable<T> for Sphere<T> {
+    /// Solves the quadratic in the forms from Ray Tracing Gems, chapter 7, which avoid the
+    /// cancellation that loses hits on very large or very small spheres.
+    fn hit(&self, ray: Ray<T>, min_t: T, max_t: T) -> Option<Hit<T>> {
+        let zero = T::from_f64(0.0);
+        let center = self.center_at(ray.time());
+        let direction = *ray.direction();
+        let oc = *ray.origin() - center;
+        let a = direction.dot(direction);
+        let half_b = oc.dot(direction);
         let c = oc.dot(oc) - self.radius * self.radius;
-        let discriminant = b * b - a * c;
-        if discriminant < 0.0 {
+        // b^2 - ac, computed from the ray's closest approach to the center.
+        let closest_approach = oc - direction * (half_b / a);
+        let discriminant = a * (self.radius * self.radius - closest_approach.dot(closest_approach));
+        if discriminant < zero {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let q = if half_b < zero {
+            root - half_b
+        } else {
+            -half_b - root
+        };
+        if q == zero {
+            return None;
+        }
+        let (t0, t1) = (c / q, q / a);
+        let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+        if min_t < near && near < max_t {
+            Some(self.hit_at(ray, near, center))
+        } else if min_t < far && far < max_t {
+            Some(self.hit_at(ray, far, center))
+        } else {
             None
+        }
+    }
+
+    #[cfg(any(feature = "window", test))]
+    fn describe(&self) -> String {
+        if self.is_moving() {
+            format!(
+                "Sphere moving from {:.2} to {:.2}, radius {:.2}",
+                self.center, self.end_center, self.radius
+            )
         } else {
-            let t = (-b - discriminant.sqrt()) / a;
-            if min_t < t && t < max_t {
-                let hit_point = ray.point_at_parameter(t);
-                let normal = (hit_point - *self.center()).unit();
-                Some(Hit::new(t, Ray::new(hit_point, normal), *self.material()))
-            } else {
-                let t = (-b + discriminant.sqrt()) / a;
-                if min_t < t && t < max_t {
-                    let hit_point = ray.point_at_parameter(t);
-                    let normal = (hit_point - *self.center()).unit();
-                    Some(Hit::new(t, Ray::new(hit_point, normal), *self.material()))
-                } else {
-                    None
-                }
+            format!("Sphere at {:.2}, radius {:.2}", self.center(), self.radius)
+        }
+    }
+
+    fn bounds(&self) -> Option<Aabb<T>> {
+        let radius = Vec3::new(self.radius, self.radius, self.radius).map(Float::abs);
+        let around = |center: Vec3<T>| Aabb::new(center - radius, center + radius);
+        Some(around(self.center).union(around(self.end_center)))
+    }
+
+    /// Only static spheres: the SIMD kernels know nothing of time.
+    fn as_sphere(&self) -> Option<&Sphere<T>> {
+        Some(self).filter(|sphere| !sphere.is_moving())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_world(simd_level: SimdLevel) -> World<f64> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let matte = Material::Lambertian {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        };
+        let mut objects: Vec<Box<dyn Hittable<f64>>> = vec![Box::new(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            matte,
+        ))];
+        for _ in 0..200 {
+            let center = Vec3::new(
+                rng.gen_range(-10.0, 10.0),
+                rng.gen_range(0.0, 2.0),
+                rng.gen_range(-10.0, 10.0),
+            );
+            objects.push(Box::new(Sphere::new(
+                center,
+                rng.gen_range(0.1, 1.0),
+                matte,
+            )));
+        }
+        World::with_simd_level(objects, simd_level)
+    }
+
+    /// Rays through jittered pixels of a 256 pixel wide image, a row at a time as the tracer
+    /// sends them.
+    fn primary_rays(count: usize) -> Vec<Ray<f64>> {
+        const WIDTH: usize = 256;
+        let rows = count.div_ceil(WIDTH);
+        let mut rng = StdRng::seed_from_u64(11);
+        (0..count)
+            .map(|i| {
+                let x = ((i % WIDTH) as f64 + rng.gen::<f64>()) / WIDTH as f64;
+                let y = ((i / WIDTH) as f64 + rng.gen::<f64>()) / rows as f64;
+                let direction = Vec3::new(x * 2.0 - 1.0, y - 0.8, -1.0);
+                Ray::new(Vec3::new(0.0, 1.0, 12.0), direction)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hit_packet_matches_hit() {
+        let rays = primary_rays(1001);
+        for level in SimdLevel::available() {
+            let world = random_world(level);
+            let hits = world.hit_packet(&rays, 0.0001, f64::MAX);
+            assert_eq!(hits.len(), rays.len());
+            for (ray, hit) in rays.iter().zip(hits) {
+                assert_eq!(
+                    hit.map(|hit| hit.t),
+                    world.hit(*ray, 0.0001, f64::MAX).map(|hit| hit.t),
+                    "{:?}",
+                    level
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_bvh_matches_trying_every_object() {
+        let world = random_world(SimdLevel::detect());
+        for ray in primary_rays(1001) {
+            let expected = world
+                .objects()
+                .iter()
+                .filter_map(|object| object.hit(ray, 0.0001, f64::MAX))
+                .map(|hit| hit.t)
+                .fold(None, |nearest: Option<f64>, t| {
+                    Some(nearest.map_or(t, |nearest| nearest.min(t)))
+                });
+            assert_eq!(world.hit(ray, 0.0001, f64::MAX).map(|hit| hit.t), expected);
+        }
+    }
+
+    fn random_unit_vector(rng: &mut StdRng) -> Vec3<f64> {
+        Vec3::new(
+            rng.gen_range(-1.0, 1.0),
+            rng.gen_range(-1.0, 1.0),
+            rng.gen_range(-1.0, 1.0),
+        )
+        .unit()
+    }
+
+    /// Hits spheres from random directions and checks that rays spawned from the hit point
+    /// never hit the sphere again straight away: outgoing rays miss, and rays going in pass
+    /// through to the far side.
+    fn check_spawned_rays(center: Vec3<f64>, radius: f64) {
+        let mut rng = StdRng::seed_from_u64(3);
+        let matte = Material::Lambertian {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        };
+        let sphere = Sphere::new(center, radius, matte);
+        for _ in 0..1000 {
+            let origin = center + random_unit_vector(&mut rng) * (radius * 3.0);
+            let target = center + random_unit_vector(&mut rng) * (radius * 0.5);
+            let hit = sphere
+                .hit(Ray::new(origin, target - origin), 0.0, f64::MAX)
+                .unwrap();
+            let normal = *hit.normal().direction();
+
+            let mut outward = random_unit_vector(&mut rng);
+            if outward.dot(normal) < 0.0 {
+                outward = -outward;
             }
+            assert!(
+                sphere.hit(hit.spawn_ray(outward), 0.0, f64::MAX).is_none(),
+                "radius {} self intersected",
+                radius
+            );
+
+            let through = sphere.hit(hit.spawn_ray(-normal), 0.0, f64::MAX).unwrap();
+            assert!(
+                (through.t - 2.0 * radius).abs() < 1e-6 * radius,
+                "radius {} exited at t = {}",
+                radius,
+                through.t
+            );
         }
     }
+
+    #[test]
+    fn test_spawned_rays_on_huge_spheres() {
+        check_spawned_rays(Vec3::new(0.0, -1000.0, 0.0), 1000.0);
+        check_spawned_rays(Vec3::new(3.0e6, 0.0, -1.0e6), 1.0e6);
+    }
+
+    #[test]
+    fn test_spawned_rays_on_tiny_spheres() {
+        check_spawned_rays(Vec3::new(0.0, 0.0, -1.0), 1.0e-5);
+        check_spawned_rays(Vec3::new(100.0, 0.0, 0.0), 1.0e-3);
+    }
+
+    #[test]
+    fn test_hit_on_huge_sphere_is_on_surface() {
+        let matte = Material::Lambertian {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        };
+        let floor = Sphere::new(Vec3::new(0.0, -1.0e6, 0.0), 1.0e6, matte);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -0.01, 1.0));
+        let hit = floor.hit(ray, 0.0, f64::MAX).unwrap();
+        let distance = (*hit.normal().origin() - Vec3::new(0.0, -1.0e6, 0.0)).length();
+        assert!((distance - 1.0e6).abs() <= hit.error.length());
+        assert!(hit.error.length() < 1e-8);
+    }
+
+    #[test]
+    fn test_pick_returns_closest_object() {
+        let matte = Material::Lambertian {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        };
+        let world = World::new(vec![
+            Box::new(Sphere::new(Vec3::new(0.0, 0.0, -4.0), 1.0, matte)),
+            Box::new(Sphere::new(Vec3::new(0.0, 0.0, -2.0), 0.5, matte)),
+        ]);
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let (object, hit) = world.pick(ray, 0.0001, f64::MAX).unwrap();
+        assert_eq!(*hit.t(), 1.5);
+        assert_eq!(
+            object.describe(),
+            "Sphere at (0.00, 0.00, -2.00), radius 0.50"
+        );
+        assert_eq!(world.hit(ray, 0.0001, f64::MAX).map(|hit| hit.t), Some(1.5));
+    }
+
+    #[test]
+    fn test_moving_sphere() {
+        let matte = Material::Lambertian {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        };
+        let sphere = Sphere::moving(
+            Vec3::new(0.0, 0.0, -4.0),
+            Vec3::new(4.0, 0.0, -4.0),
+            1.0,
+            matte,
+        );
+        assert!(sphere.as_sphere().is_none());
+        assert_eq!(
+            sphere.bounds(),
+            Some(Aabb::new(
+                Vec3::new(-1.0, -1.0, -5.0),
+                Vec3::new(5.0, 1.0, -3.0)
+            ))
+        );
+        let world = World::new(vec![Box::new(sphere)]);
+        let ray = Ray::new(Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(world.hit(ray, 0.0001, f64::MAX).is_none());
+        assert_eq!(
+            world
+                .hit(ray.with_time(0.5), 0.0001, f64::MAX)
+                .map(|hit| hit.t),
+            Some(3.0)
+        );
+        // Still at its end after time 1.
+        assert!(world.hit(ray.with_time(2.0), 0.0001, f64::MAX).is_none());
+        assert_eq!(
+            world.hit_packet(&[ray.with_time(0.5)], 0.0001, f64::MAX)[0]
+                .as_ref()
+                .map(|hit| hit.t),
+            Some(3.0)
+        );
+    }
 }
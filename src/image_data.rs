@@ -7,6 +7,60 @@ macro_rules! vec_no_clone {
     }};
 }
 
+/// A rectangle of pixels, in canvas coordinates: `x` counts columns from the left and `y` rows
+/// from the bottom.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    /// The pixels between two corner pixels given in either order, inclusive.
+    pub fn between(a: (usize, usize), b: (usize, usize)) -> Region {
+        Region {
+            x: a.0.min(b.0),
+            y: a.1.min(b.1),
+            width: a.0.max(b.0) - a.0.min(b.0) + 1,
+            height: a.1.max(b.1) - a.1.min(b.1) + 1,
+        }
+    }
+
+    /// Parses `x,y,width,height` with `y` counted down from the top of an image `y_size` rows
+    /// tall, as image editors count it. Fails unless the region is inside the image and not
+    /// empty.
+    pub fn parse(text: &str, x_size: usize, y_size: usize) -> Result<Region, String> {
+        let numbers: Vec<usize> = text
+            .split(',')
+            .map(|number| number.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("expected x,y,width,height, not '{}'", text))?;
+        match numbers[..] {
+            [x, y, width, height]
+                if width > 0 && height > 0 && x + width <= x_size && y + height <= y_size =>
+            {
+                Ok(Region {
+                    x,
+                    y: y_size - y - height,
+                    width,
+                    height,
+                })
+            }
+            [_, _, _, _] => Err(format!(
+                "'{}' is not inside the {}x{} image",
+                text, x_size, y_size
+            )),
+            _ => Err(format!("expected x,y,width,height, not '{}'", text)),
+        }
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
 pub struct ImageData<T> {
     pub x_size: usize,
     pub y_size: usize,
@@ -56,8 +110,18 @@ where
         self.y_size
     }
 
+    /// The whole image.
+    pub fn region(&self) -> Region {
+        Region {
+            x: 0,
+            y: 0,
+            width: self.x_size,
+            height: self.y_size,
+        }
+    }
+
     pub fn get_complete(&self) -> f64 {
-        self.complete.read().unwrap().clone()
+        *self.complete.read().unwrap()
     }
 
     pub fn update_complete<F>(&self, update: F) -> f64
@@ -65,8 +129,8 @@ where
         F: Fn(f64) -> f64,
     {
         let mut value = self.complete.write().unwrap();
-        *value = update((*value).clone());
-        (*value).clone()
+        *value = update(*value);
+        *value
     }
 
     pub fn get_pixel(&self, x: usize, y: usize) -> T {
@@ -93,24 +157,26 @@ where
     }
 }
 
-impl<T> Into<Vec<T>> for ImageData<T>
+impl<T> From<ImageData<T>> for Vec<T>
 where
     T: Clone,
 {
-    fn into(self) -> Vec<T> {
-        self.canvas
+    fn from(image_data: ImageData<T>) -> Vec<T> {
+        image_data
+            .canvas
             .iter()
             .map(|v| v.read().unwrap().clone())
             .collect()
     }
 }
 
-impl<T> Into<Vec<T>> for &ImageData<T>
+impl<T> From<&ImageData<T>> for Vec<T>
 where
     T: Clone,
 {
-    fn into(self) -> Vec<T> {
-        self.canvas
+    fn from(image_data: &ImageData<T>) -> Vec<T> {
+        image_data
+            .canvas
             .iter()
             .map(|v| v.read().unwrap().clone())
             .collect()
@@ -154,6 +220,34 @@ mod tests {
         assert_eq!(canvas.get_pixel(0, 0), 10.0);
     }
 
+    #[test]
+    fn test_regions() {
+        let region = Region::between((5, 1), (2, 3));
+        assert_eq!(
+            region,
+            Region {
+                x: 2,
+                y: 1,
+                width: 4,
+                height: 3
+            }
+        );
+        assert!(region.contains(5, 3) && !region.contains(6, 3) && !region.contains(2, 0));
+        // Rows are counted from the top in the text, and from the bottom in the canvas.
+        assert_eq!(
+            Region::parse("1, 0, 2, 3", 4, 10),
+            Ok(Region {
+                x: 1,
+                y: 7,
+                width: 2,
+                height: 3
+            })
+        );
+        assert!(Region::parse("3,0,2,1", 4, 10).is_err());
+        assert!(Region::parse("0,0,0,1", 4, 10).is_err());
+        assert!(Region::parse("0,0,1", 4, 10).is_err());
+    }
+
     #[test]
     fn test_into_vec() {
         let canvas = ImageData::new_blank(2, 1, 0.0);
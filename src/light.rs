@@ -0,0 +1,375 @@
+use background::Background;
+use ray::Ray;
+use vec3::{Float, Vec3};
+
+/// A light that shines on the scene without being part of it. Its light reaches surfaces by
+/// the tracer sampling it directly. Only sphere lights have a surface for rays to hit, so only
+/// they are seen by the camera, in mirrors and by scattered rays.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Light<T> {
+    /// Shines equally in all directions from `position`, with `intensity` per steradian.
+    Point {
+        position: Vec3<T>,
+        intensity: Vec3<T>,
+    },
+    /// A point light shining within `cone_angle` degrees of `direction`, fading out smoothly
+    /// over the outermost `falloff_angle` degrees of the cone.
+    Spot {
+        position: Vec3<T>,
+        direction: Vec3<T>,
+        intensity: Vec3<T>,
+        cone_angle: T,
+        falloff_angle: T,
+    },
+    /// A light so far away, like the sun, that it arrives from the same `direction` (pointing
+    /// towards the light) everywhere, giving `irradiance` on a surface facing it. Its disc is
+    /// `angular_diameter` degrees across; the bigger it is, the softer the shadows.
+    Directional {
+        direction: Vec3<T>,
+        irradiance: Vec3<T>,
+        angular_diameter: T,
+    },
+    /// A glowing ball, whose surface gives off `radiance` in every direction. It does not cast
+    /// shadows of other lights.
+    Sphere {
+        center: Vec3<T>,
+        radius: T,
+        radiance: Vec3<T>,
+    },
+}
+
+impl<T: Float> Light<T> {
+    /// Picks a direction from `point` towards the light from two uniform random numbers.
+    /// Returns the direction (a unit vector), the distance to the light along it, and the light
+    /// arriving from it divided by the density of picking it, or `None` if no light reaches
+    /// `point`.
+    pub fn sample(&self, point: Vec3<T>, u1: T, u2: T) -> Option<(Vec3<T>, T, Vec3<T>)> {
+        let zero = T::from_f64(0.0);
+        match *self {
+            Light::Point {
+                position,
+                intensity,
+            } => {
+                let (direction, distance) = towards(point, position)?;
+                Some((direction, distance, intensity / (distance * distance)))
+            }
+            Light::Spot {
+                position,
+                direction: spot_direction,
+                intensity,
+                cone_angle,
+                falloff_angle,
+            } => {
+                let (direction, distance) = towards(point, position)?;
+                let cos_outer = radians(cone_angle).cos();
+                let cos_inner = radians((cone_angle - falloff_angle).max(zero)).cos();
+                let cos_theta = -direction.dot(spot_direction.unit());
+                let falloff = if cos_theta >= cos_inner {
+                    T::from_f64(1.0)
+                } else {
+                    smoothstep((cos_theta - cos_outer) / (cos_inner - cos_outer))
+                };
+                Some(direction).filter(|_| falloff > zero).map(|direction| {
+                    (
+                        direction,
+                        distance,
+                        intensity * falloff / (distance * distance),
+                    )
+                })
+            }
+            Light::Directional {
+                direction,
+                irradiance,
+                angular_diameter,
+            } => {
+                let cos_max = radians(angular_diameter / T::from_f64(2.0)).cos();
+                Some((
+                    sample_cone(direction.unit(), cos_max, u1, u2),
+                    T::MAX,
+                    irradiance,
+                ))
+            }
+            Light::Sphere {
+                center,
+                radius,
+                radiance,
+            } => {
+                let (axis, distance) = towards(point, center)?;
+                if distance <= radius {
+                    return None;
+                }
+                // Sample the cone the sphere fills as seen from `point`, uniformly.
+                let sin_max = radius / distance;
+                let cos_max = (T::from_f64(1.0) - sin_max * sin_max).max(zero).sqrt();
+                let direction = sample_cone(axis, cos_max, u1, u2);
+                let along = direction.dot(center - point);
+                let discriminant =
+                    (along * along - (distance * distance - radius * radius)).max(zero);
+                let solid_angle = T::from_f64(2.0) * T::PI * (T::from_f64(1.0) - cos_max);
+                Some((
+                    direction,
+                    along - discriminant.sqrt(),
+                    radiance * solid_angle,
+                ))
+            }
+        }
+    }
+}
+
+impl<T: Float> Light<T> {
+    /// Where `ray` first meets the light from outside, before `max_t`, as a multiple of its
+    /// direction, and the light given off there. Always `None` for lights without a surface.
+    pub fn hit(&self, ray: Ray<T>, max_t: T) -> Option<(T, Vec3<T>)> {
+        match *self {
+            Light::Sphere {
+                center,
+                radius,
+                radiance,
+            } => {
+                let zero = T::from_f64(0.0);
+                let offset = *ray.origin() - center;
+                let a = ray.direction().squared_length();
+                let half_b = offset.dot(*ray.direction());
+                let c = offset.squared_length() - radius * radius;
+                let discriminant = half_b * half_b - a * c;
+                // Light only leaves the outside of the ball.
+                if c <= zero || discriminant < zero {
+                    return None;
+                }
+                let t = (-half_b - discriminant.sqrt()) / a;
+                Some((t, radiance)).filter(|_| t > zero && t < max_t)
+            }
+            _ => None,
+        }
+    }
+
+    /// The density per steradian with which `sample` picks `direction` from `point`, for the
+    /// lights rays can hit.
+    pub fn pdf(&self, point: Vec3<T>, direction: Vec3<T>) -> Option<T> {
+        match *self {
+            Light::Sphere { center, radius, .. } => {
+                let zero = T::from_f64(0.0);
+                let one = T::from_f64(1.0);
+                let (axis, distance) = towards(point, center)?;
+                let sin_max = radius / distance;
+                let cos_max = (one - sin_max * sin_max).max(zero).sqrt();
+                let inside_cone = direction.unit().dot(axis) >= cos_max;
+                Some(if distance > radius && inside_cone {
+                    one / (T::from_f64(2.0) * T::PI * (one - cos_max))
+                } else {
+                    zero
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Where light in a scene comes from: the background, seen by rays that leave the scene, and
+/// the lights.
+pub struct Lighting<T> {
+    pub background: Background<T>,
+    pub lights: Vec<Light<T>>,
+}
+
+impl<T: Float> Default for Lighting<T> {
+    fn default() -> Lighting<T> {
+        Lighting {
+            background: Background::default(),
+            lights: vec![],
+        }
+    }
+}
+
+impl<T: Float> Lighting<T> {
+    /// The nearest light `ray` hits before `max_t`, and the light given off where it does.
+    pub fn hit(&self, ray: Ray<T>, max_t: T) -> Option<(&Light<T>, Vec3<T>)> {
+        let mut nearest = None;
+        let mut max_t = max_t;
+        for light in &self.lights {
+            if let Some((t, radiance)) = light.hit(ray, max_t) {
+                nearest = Some((light, radiance));
+                max_t = t;
+            }
+        }
+        nearest
+    }
+}
+
+/// The unit vector from `from` to `to` and the distance between them, unless they coincide.
+fn towards<T: Float>(from: Vec3<T>, to: Vec3<T>) -> Option<(Vec3<T>, T)> {
+    let offset = to - from;
+    let distance = offset.length();
+    Some((offset / distance, distance)).filter(|_| distance > T::from_f64(0.0))
+}
+
+/// A direction within the cone about the unit vector `axis` whose half angle has cosine
+/// `cos_max`, uniformly by solid angle.
+fn sample_cone<T: Float>(axis: Vec3<T>, cos_max: T, u1: T, u2: T) -> Vec3<T> {
+    let one = T::from_f64(1.0);
+    let cos_theta = one - u1 * (one - cos_max);
+    let sin_theta = (one - cos_theta * cos_theta).max(T::from_f64(0.0)).sqrt();
+    let phi = T::from_f64(2.0) * T::PI * u2;
+    let (tangent, bitangent) = axis.orthonormal_basis();
+    tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta
+}
+
+fn radians<T: Float>(degrees: T) -> T {
+    degrees * T::PI / T::from_f64(180.0)
+}
+
+fn smoothstep<T: Float>(x: T) -> T {
+    let x = x.max(T::from_f64(0.0)).min(T::from_f64(1.0));
+    x * x * (T::from_f64(3.0) - T::from_f64(2.0) * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_point_light_falls_off_with_distance() {
+        let light = Light::Point {
+            position: Vec3::new(0.0, 4.0, 0.0),
+            intensity: Vec3::new(16.0, 8.0, 4.0),
+        };
+        let (direction, distance, radiance) =
+            light.sample(Vec3::new(0.0, 0.0, 0.0), 0.5, 0.5).unwrap();
+        assert_eq!(direction, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(distance, 4.0);
+        assert_eq!(radiance, Vec3::new(1.0, 0.5, 0.25));
+    }
+
+    #[test]
+    fn test_spot_light_cone() {
+        let light = Light::Spot {
+            position: Vec3::new(0.0, 1.0, 0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            intensity: Vec3::new(1.0, 1.0, 1.0),
+            cone_angle: 45.0,
+            falloff_angle: 10.0,
+        };
+        let at = |x: f64| light.sample(Vec3::new(x, 0.0, 0.0), 0.5, 0.5);
+        // 1 unit down, so x is the tangent of the angle off the axis.
+        assert_eq!(at(0.0).unwrap().2, Vec3::new(1.0, 1.0, 1.0));
+        let edge = at(radians(34.0f64).tan()).unwrap();
+        assert!((edge.2.x * edge.1 * edge.1 - 1.0).abs() < 1e-9);
+        let fading = at(radians(40.0f64).tan()).unwrap();
+        let fraction = fading.2.x * fading.1 * fading.1;
+        assert!(fraction > 0.0 && fraction < 1.0, "{}", fraction);
+        assert_eq!(at(radians(46.0f64).tan()), None);
+    }
+
+    #[test]
+    fn test_directional_light_stays_in_its_disc() {
+        let direction = Vec3::new(1.0, 1.0, 0.0).unit();
+        let light = Light::Directional {
+            direction,
+            irradiance: Vec3::new(3.0, 3.0, 3.0),
+            angular_diameter: 10.0,
+        };
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let (sampled, distance, irradiance) = light
+                .sample(Vec3::new(5.0, 0.0, 0.0), rng.gen(), rng.gen())
+                .unwrap();
+            assert!(sampled.dot(direction) >= radians(5.0f64).cos() - 1e-12);
+            assert_eq!((distance, irradiance), (f64::MAX, Vec3::new(3.0, 3.0, 3.0)));
+        }
+    }
+
+    #[test]
+    fn test_sphere_light_irradiance() {
+        // A sphere of radiance L subtending half angle θ gives irradiance π L sin²θ on a
+        // surface facing it.
+        let light = Light::Sphere {
+            center: Vec3::new(0.0, 5.0, 0.0),
+            radius: 1.0,
+            radiance: Vec3::new(2.0, 2.0, 2.0),
+        };
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let mut rng = StdRng::seed_from_u64(2);
+        let samples = 20_000;
+        let mut irradiance = 0.0;
+        for _ in 0..samples {
+            let (direction, distance, radiance) = light
+                .sample(Vec3::new(0.0, 0.0, 0.0), rng.gen(), rng.gen())
+                .unwrap();
+            // The sampled point is on the sphere.
+            assert!(
+                ((direction * distance - Vec3::new(0.0, 5.0, 0.0)).length() - 1.0).abs() < 1e-9
+            );
+            irradiance += radiance.x * direction.dot(normal);
+        }
+        let expected = std::f64::consts::PI * 2.0 / 25.0;
+        assert!(
+            (irradiance / samples as f64 - expected).abs() < 0.002,
+            "{}",
+            irradiance / samples as f64
+        );
+        assert_eq!(light.sample(Vec3::new(0.0, 5.5, 0.0), 0.5, 0.5), None);
+    }
+
+    #[test]
+    fn test_hit_sphere_light() {
+        let near = Light::Sphere {
+            center: Vec3::new(0.0, 5.0, 0.0),
+            radius: 1.0,
+            radiance: Vec3::new(2.0, 2.0, 2.0),
+        };
+        let far = Light::Sphere {
+            center: Vec3::new(0.0, 10.0, 0.0),
+            radius: 2.0,
+            radiance: Vec3::new(3.0, 3.0, 3.0),
+        };
+        let up = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0));
+        assert_eq!(
+            near.hit(up, f64::MAX),
+            Some((2.0, Vec3::new(2.0, 2.0, 2.0)))
+        );
+        assert_eq!(near.hit(up, 1.5), None);
+        // Rays from inside, and lights without a surface, are never hit.
+        let inside = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(near.hit(inside, f64::MAX), None);
+        let point = Light::Point {
+            position: Vec3::new(0.0, 4.0, 0.0),
+            intensity: Vec3::new(1.0, 1.0, 1.0),
+        };
+        assert_eq!(point.hit(up, f64::MAX), None);
+        assert_eq!(
+            point.pdf(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+            None
+        );
+
+        let lighting = Lighting {
+            background: Background::Solid(Vec3::new(0.0, 0.0, 0.0)),
+            lights: vec![far, point, near],
+        };
+        assert_eq!(
+            lighting.hit(up, f64::MAX).map(|(light, _)| *light),
+            Some(near)
+        );
+        let sideways = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(lighting.hit(sideways, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn test_sphere_light_pdf_matches_sample() {
+        let light = Light::Sphere {
+            center: Vec3::new(1.0, 3.0, -2.0),
+            radius: 0.5,
+            radiance: Vec3::new(2.0, 2.0, 2.0),
+        };
+        let point = Vec3::new(0.0, 0.0, 0.0);
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..100 {
+            let (direction, _, radiance) = light.sample(point, rng.gen(), rng.gen()).unwrap();
+            // The sample is the radiance over its density.
+            let pdf = light.pdf(point, direction).unwrap();
+            assert!((radiance.x * pdf - 2.0).abs() < 1e-9, "{}", pdf);
+        }
+        assert_eq!(light.pdf(point, Vec3::new(-1.0, 0.0, 0.0)), Some(0.0));
+    }
+}
@@ -1,104 +1,581 @@
+#[cfg(feature = "window")]
 extern crate find_folder;
 extern crate image;
+#[cfg(feature = "window")]
 extern crate piston_window;
 extern crate rand;
 extern crate rayon;
 
 use rand::prelude::ThreadRng;
 use rand::Rng;
-use std::sync::Arc;
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{self, Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use camera::Camera;
+use animation::{frame_path, parse_frame_range};
+use camera::{Projection, Shutter};
+use checkpoint::{fingerprint, Checkpoint};
+use control::RenderControl;
 use display::output_ppm;
-use display::run_window_thread;
+use display::terminal::TerminalDisplay;
+use display::{
+    default_display, load_image, match_base_exposure, save_image, Display, NullDisplay,
+    RenderSession, ToneMapping,
+};
+use distributed::coordinator::Coordinator;
+use distributed::{split_into_jobs, worker, DEFAULT_ADDRESS};
 use hittable::{Hittable, Sphere, World};
-use image_data::ImageData;
+use image::ImageFormat;
+use image_data::{ImageData, Region};
+use light::Lighting;
 use material::Material;
-use tracer::draw_to_image_data;
+use scene::Scene;
+use server::RenderService;
+use stats::RenderStats;
+use tracer::{draw_to_image_data, ColourMode, RenderSettings};
 use vec3::Vec3;
 
+mod animation;
+mod background;
+mod bvh;
 mod camera;
+mod checkpoint;
+mod control;
 mod display;
+mod distributed;
 mod hittable;
 mod image_data;
+mod light;
 mod material;
+mod medium;
+mod random;
 mod ray;
+mod sampling;
+mod scene;
+mod server;
+mod simd;
+mod spectrum;
+mod stats;
 mod tracer;
+mod transform;
 mod vec3;
 
-fn main() {
-    let nx = 1920usize;
-    let ny = 1080usize;
+/// Where `frames` writes its images unless told otherwise.
+const DEFAULT_FRAME_PATTERN: &str = "frame-####.png";
 
-    let image_data: Arc<ImageData<Vec3<f64>>> =
-        Arc::new(ImageData::new_blank(nx, ny, Vec3::new(0.0, 0.0, 0.0)));
+/// How often `--checkpoint` saves unless told otherwise, in seconds.
+const DEFAULT_CHECKPOINT_INTERVAL: f64 = 60.0;
 
-    let _window_thread = run_window_thread(Arc::clone(&image_data));
+/// The width and height of the tiles `coordinate` hands out unless told otherwise.
+const DEFAULT_TILE_SIZE: usize = 32;
 
-    let camera: Camera<f64> = Camera::new(
-        Vec3::new(7.5, 1.5, -2.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-        60.0,
-        nx as f64 / ny as f64,
-        0.05,
-        4.00,
-    );
+/// The passes `coordinate` hands out at a time unless told otherwise.
+const DEFAULT_BATCH: usize = 16;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("frames") => return render_frames(&args),
+        Some("merge") => return merge_checkpoints(&args),
+        Some("coordinate") => return coordinate(&args),
+        Some("work") => return work(&args),
+        Some("serve") => return serve(&args),
+        _ => {}
+    }
 
-    let world = Arc::new(create_random_world());
+    let scene = match option_value(&args, "--scene") {
+        Some(path) => {
+            let frame = number_option(&args, "--frame").unwrap_or(0.0);
+            scene::load(path, frame).unwrap_or_else(|error| exit_with_error(&error))
+        }
+        None => Scene {
+            width: scene::DEFAULT_WIDTH,
+            height: scene::DEFAULT_HEIGHT,
+            samples: scene::DEFAULT_SAMPLES,
+            spectral: false,
+            camera: scene::default_camera(
+                scene::DEFAULT_WIDTH as f64 / scene::DEFAULT_HEIGHT as f64,
+            ),
+            shutter: Shutter::default(),
+            world: create_random_world(),
+            lighting: Lighting::default(),
+        },
+    };
 
-    let samples = 1024;
-    draw_to_image_data(&image_data, &camera, &world, samples);
-    output_ppm(image_data);
+    let tone_mapping = ToneMapping::default();
+    let display = choose_display(&args, default_display());
+    let (session, _display_thread) = render(scene, &args, display.as_ref(), tone_mapping);
+    if session.control.is_cancelled() {
+        eprintln!(
+            "Render cancelled after {} samples per pixel",
+            session.stats.samples_per_pixel()
+        );
+    }
+    output_ppm(session.image_data, tone_mapping);
     //window_thread.join().unwrap();
 }
 
-fn create_world() -> Box<dyn Hittable<f64>> {
-    let normals = Material::Normal {};
-    let red_matte = Material::Lambertian {
-        albedo: Vec3::new(0.8_f64, 0.3_f64, 0.3_f64),
+/// `rustray frames --scene <file> --frames <first>-<last> [--output <pattern>]` renders each
+/// frame of an animation to its own image. Frames already on disk are skipped, so an
+/// interrupted run carries on where it stopped when started again.
+fn render_frames(args: &[String]) {
+    let path = option_value(args, "--scene")
+        .unwrap_or_else(|| exit_with_error(&"frames: missing --scene <file>"));
+    let (first, last) = option_value(args, "--frames")
+        .and_then(|range| parse_frame_range(range))
+        .unwrap_or_else(|| exit_with_error(&"frames: missing --frames <first>-<last>"));
+    if option_value(args, "--checkpoint").is_some() {
+        exit_with_error(&"frames: --checkpoint is for single images; finished frames are kept");
+    }
+    let pattern = option_value(args, "--output").map_or(DEFAULT_FRAME_PATTERN, String::as_str);
+    if frame_path(pattern, first).is_none() {
+        exit_with_error(&"--output: expected #s to be replaced by the frame number");
+    }
+    let format = ImageFormat::from_path(pattern)
+        .unwrap_or_else(|error| exit_with_error(&format!("--output: {}", error)));
+    // A window would open and close for every frame.
+    let display = choose_display(args, Box::new(TerminalDisplay::default()));
+    let tone_mapping = ToneMapping::default();
+
+    for frame in first..=last {
+        let output = frame_path(pattern, frame).unwrap();
+        let output = Path::new(&output);
+        if output.exists() {
+            eprintln!("Frame {}: {} exists, skipping", frame, output.display());
+            continue;
+        }
+        let scene =
+            scene::load(path, f64::from(frame)).unwrap_or_else(|error| exit_with_error(&error));
+        eprintln!("Frame {} of {}-{}", frame, first, last);
+        let (session, display_thread) = render(scene, args, display.as_ref(), tone_mapping);
+        let _ = display_thread.join();
+        if session.control.is_cancelled() {
+            exit_with_error(&format!("Render cancelled, frame {} not saved", frame));
+        }
+
+        // Written under another name first, so that a frame on disk is always complete.
+        let partial = output.with_file_name(format!(
+            "{}.partial",
+            output.file_name().unwrap().to_string_lossy()
+        ));
+        let saved = output
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| save_image(&session.image_data, tone_mapping, &partial, format))
+            .and_then(|_| fs::rename(&partial, output));
+        if let Err(error) = saved {
+            exit_with_error(&format!("Could not save {}: {}", output.display(), error));
+        }
+        eprintln!("Frame {}: saved {}", frame, output.display());
+    }
+}
+
+/// `rustray merge [--output <image>] [--checkpoint <file>] <checkpoint>...` adds up the
+/// checkpoints of renders of the same scene, e.g. on several machines, into one image. It is
+/// written to `--output`, or as PPM to stdout. `--checkpoint` also saves the merged samples, so
+/// that more can be added to them later.
+fn merge_checkpoints(args: &[String]) {
+    let mut output = None;
+    let mut merged_path = None;
+    let mut inputs = vec![];
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--output" => output = rest.next(),
+            "--checkpoint" => merged_path = rest.next(),
+            _ => inputs.push(arg),
+        }
+    }
+    if inputs.is_empty() {
+        exit_with_error(&"merge: expected the checkpoints to merge");
+    }
+    let load = |path: &String| {
+        Checkpoint::load(Path::new(path))
+            .unwrap_or_else(|error| exit_with_error(&format!("{}: {}", path, error)))
     };
-    let green_matte = Material::Lambertian {
-        albedo: Vec3::new(0.3_f64, 0.8_f64, 0.3_f64),
+    let mut merged = load(inputs[0]);
+    for path in &inputs[1..] {
+        merged.add(&load(path)).unwrap_or_else(|error| {
+            exit_with_error(&format!(
+                "{}: cannot merge with {}: {}",
+                path, inputs[0], error
+            ))
+        });
+    }
+    eprintln!(
+        "Merged {} renders, {} passes in all",
+        inputs.len(),
+        merged.passes
+    );
+
+    if let Some(path) = merged_path {
+        merged.save(Path::new(path)).unwrap_or_else(|error| {
+            exit_with_error(&format!("Could not save {}: {}", path, error))
+        });
+    }
+    let image_data = Arc::new(ImageData::new_blank(
+        merged.width,
+        merged.height,
+        Vec3::new(0.0, 0.0, 0.0),
+    ));
+    merged.restore(&image_data);
+    let tone_mapping = ToneMapping::default();
+    match output {
+        Some(path) => {
+            let saved = ImageFormat::from_path(path)
+                .map_err(|error| error.to_string())
+                .and_then(|format| {
+                    save_image(&image_data, tone_mapping, Path::new(path), format)
+                        .map_err(|error| error.to_string())
+                });
+            if let Err(error) = saved {
+                exit_with_error(&format!("Could not save {}: {}", path, error));
+            }
+        }
+        None => output_ppm(image_data, tone_mapping),
+    }
+}
+
+/// Renders `scene` while `display` shows it, returning once every sample has been taken or the
+/// render was cancelled. Only `--region`, if given, is rendered; the rest of the image is black
+/// or taken from the `--base` image. With `--checkpoint` the render is saved as it goes, and
+/// with `--resume` as well it carries on from the last save.
+fn render(
+    scene: Scene,
+    args: &[String],
+    display: &dyn Display,
+    tone_mapping: ToneMapping,
+) -> (RenderSession, JoinHandle<()>) {
+    let colour_mode = if scene.spectral || has_flag(args, "--spectral") {
+        ColourMode::Spectral
+    } else {
+        ColourMode::Rgb
     };
-    let blue_fuzzy_metal = Material::Metal {
-        albedo: Vec3::new(0.3_f64, 0.3_f64, 0.5_f64),
-        fuzz: 0.5,
+
+    let session = new_session(
+        scene.width,
+        scene.height,
+        scene.camera,
+        scene.world,
+        tone_mapping,
+    );
+    if let Some(path) = option_value(args, "--base") {
+        let pixels = load_image(Path::new(path), scene.width, scene.height, tone_mapping)
+            .unwrap_or_else(|error| exit_with_error(&format!("--base: {}: {}", path, error)));
+        for (index, pixel) in pixels.into_iter().enumerate() {
+            session
+                .image_data
+                .update_pixel(index % scene.width, index / scene.width, |_| pixel);
+        }
+    }
+    if let Some(region) = option_value(args, "--region") {
+        session.control.set_region(Some(
+            Region::parse(region, scene.width, scene.height)
+                .unwrap_or_else(|error| exit_with_error(&format!("--region: {}", error))),
+        ));
+    }
+
+    let mut settings = RenderSettings {
+        samples: scene.samples,
+        colour_mode,
+        shutter: scene.shutter,
+        start_pass: 0,
+        seed: rand::random(),
     };
-    let shiny_metal = Material::Metal {
-        albedo: Vec3::new(0.8_f64, 0.8_f64, 0.8_f64),
-        fuzz: 0.005,
+    if let Some(path) = option_value(args, "--checkpoint") {
+        settings = set_up_checkpoints(Path::new(path), args, &session, settings);
+    }
+    let display_thread = display.spawn(session.clone());
+
+    draw_to_image_data(
+        &session.image_data,
+        &session.camera,
+        session.world.as_ref(),
+        &scene.lighting,
+        settings,
+        &session.stats,
+        &session.control,
+    );
+    if let (Some(_), Some(region)) = (option_value(args, "--base"), session.control.region()) {
+        match_base_exposure(&session.image_data, region);
+    }
+    (session, display_thread)
+}
+
+/// A blank image of a scene, ready to render.
+fn new_session(
+    width: usize,
+    height: usize,
+    camera: Projection<f64>,
+    world: World<f64>,
+    tone_mapping: ToneMapping,
+) -> RenderSession {
+    RenderSession {
+        image_data: Arc::new(ImageData::new_blank(
+            width,
+            height,
+            Vec3::new(0.0, 0.0, 0.0),
+        )),
+        camera: Arc::new(RwLock::new(camera)),
+        world: Arc::new(world),
+        stats: Arc::new(RenderStats::new()),
+        control: Arc::new(RenderControl::new()),
+        tone_mapping,
+    }
+}
+
+/// `rustray coordinate --scene <file> [--listen <address>] [--workers <n>] [--tile <pixels>]
+/// [--batch <passes>] [--timeout <seconds>]` renders a scene on the workers that connect to it,
+/// handing each a few passes over a tile at a time, and writes the image as PPM to stdout.
+/// `--workers` starts that many workers on this machine as well.
+fn coordinate(args: &[String]) {
+    let path = option_value(args, "--scene")
+        .unwrap_or_else(|| exit_with_error(&"coordinate: missing --scene <file>"));
+    let frame = number_option(args, "--frame").unwrap_or(0.0);
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|error| exit_with_error(&format!("Could not read {}: {}", path, error)));
+    // Loaded here too, so that a broken scene is caught before any worker sees it.
+    let scene = scene::load(path, frame).unwrap_or_else(|error| exit_with_error(&error));
+    let at_least_one = |option: &str, default: usize| {
+        let value = number_option(args, option).unwrap_or(default);
+        if value == 0 {
+            exit_with_error(&format!("{}: expected at least 1", option));
+        }
+        value
     };
-    let glass = Material::Dieletric {
-        refractive_index: 1.5,
+    let tile_size = at_least_one("--tile", DEFAULT_TILE_SIZE);
+    let batch = at_least_one("--batch", DEFAULT_BATCH) as u32;
+    let jobs = split_into_jobs(
+        scene.width,
+        scene.height,
+        tile_size,
+        scene.samples.max(0) as u32,
+        batch,
+    );
+
+    let address = option_value(args, "--listen").map_or(DEFAULT_ADDRESS, String::as_str);
+    let listener = TcpListener::bind(address)
+        .unwrap_or_else(|error| exit_with_error(&format!("--listen: {}: {}", address, error)));
+    let port = listener
+        .local_addr()
+        .map(|address| address.port())
+        .unwrap_or(0);
+    eprintln!("Waiting for workers on {}", address);
+
+    let tone_mapping = ToneMapping::default();
+    let session = new_session(
+        scene.width,
+        scene.height,
+        scene.camera,
+        scene.world,
+        tone_mapping,
+    );
+    let spectral = has_flag(args, "--spectral");
+    let mut coordinator = Coordinator::new(text, frame, spectral, jobs, &session);
+    if let Some(seconds) = number_option::<f64>(args, "--timeout") {
+        if seconds <= 0.0 {
+            exit_with_error(&"--timeout: expected a number of seconds above 0");
+        }
+        coordinator = coordinator.with_timeout(Duration::from_secs_f64(seconds));
+    }
+    let coordinator = Arc::new(coordinator);
+    Coordinator::serve(Arc::clone(&coordinator), listener);
+
+    let mut workers: Vec<Child> = (0..number_option(args, "--workers").unwrap_or(0))
+        .map(|_| {
+            let executable = env::current_exe()
+                .unwrap_or_else(|error| exit_with_error(&format!("--workers: {}", error)));
+            Command::new(executable)
+                .args(["work", "--connect", &format!("127.0.0.1:{}", port)])
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap_or_else(|error| exit_with_error(&format!("--workers: {}", error)))
+        })
+        .collect();
+
+    let display = choose_display(args, Box::new(TerminalDisplay::default()));
+    let _display_thread = display.spawn(session.clone());
+    coordinator.wait();
+    for worker in &mut workers {
+        if session.control.is_cancelled() {
+            let _ = worker.kill();
+        }
+        let _ = worker.wait();
+    }
+    output_ppm(session.image_data, tone_mapping);
+}
+
+/// `rustray work --connect <address> [--directory <directory>]` renders for a coordinator.
+/// Files the scene refers to are looked for in `--directory`, or the current directory.
+fn work(args: &[String]) {
+    let address = option_value(args, "--connect")
+        .unwrap_or_else(|| exit_with_error(&"work: missing --connect <address>"));
+    let directory = option_value(args, "--directory").map_or(".", String::as_str);
+    worker::work(address, Path::new(directory)).unwrap_or_else(|error| exit_with_error(&error));
+}
+
+/// `rustray serve [--listen <address>] [--directory <dir>] [--keep <jobs>]` runs an HTTP
+/// service that renders the scenes posted to it one after another. Files the scenes refer to
+/// are looked for in `--directory`, and may not be outside it. `--keep` is how many finished
+/// jobs it remembers.
+fn serve(args: &[String]) {
+    let address = option_value(args, "--listen").map_or(server::DEFAULT_ADDRESS, String::as_str);
+    let directory = option_value(args, "--directory").map_or(".", String::as_str);
+    let listener = TcpListener::bind(address)
+        .unwrap_or_else(|error| exit_with_error(&format!("--listen: {}: {}", address, error)));
+    eprintln!("Listening on http://{}", address);
+    let mut service =
+        RenderService::new(Path::new(directory).to_path_buf(), ToneMapping::default());
+    if let Some(kept_jobs) = number_option(args, "--keep") {
+        service = service.with_kept_jobs(kept_jobs);
+    }
+    let _ = RenderService::serve(Arc::new(service), listener).join();
+}
+
+/// Has the session save a checkpoint to `path` every so often, and after the last pass. With
+/// `--resume`, first restores the image from the checkpoint already there, and returns the
+/// settings with the number of passes it had and its seed. Exits if it is not of the same
+/// scene and settings.
+fn set_up_checkpoints(
+    path: &Path,
+    args: &[String],
+    session: &RenderSession,
+    mut settings: RenderSettings,
+) -> RenderSettings {
+    let scene_path = option_value(args, "--scene").unwrap_or_else(|| {
+        exit_with_error(&"--checkpoint: needs a --scene, the random scene changes every run")
+    });
+    let scene = fs::read(scene_path)
+        .map(|bytes| fingerprint(&bytes))
+        .unwrap_or_else(|error| exit_with_error(&format!("{}: {}", scene_path, error)));
+    let interval = match option_value(args, "--checkpoint-every") {
+        Some(seconds) => seconds
+            .parse()
+            .ok()
+            .filter(|&seconds: &f64| seconds >= 0.0)
+            .unwrap_or_else(|| {
+                exit_with_error(&format!(
+                    "--checkpoint-every: expected seconds, not '{}'",
+                    seconds
+                ))
+            }),
+        None => DEFAULT_CHECKPOINT_INTERVAL,
     };
-    let world: Box<dyn Hittable<f64>> = Box::new(World::new(vec![
-        Box::new(Sphere::new(
-            Vec3::new(-1.0, 0.0, -1.5),
-            0.5,
-            blue_fuzzy_metal,
-        )),
-        Box::new(Sphere::new(Vec3::new(0.0, 2.0, -3.5), 1.5, shiny_metal)),
-        Box::new(Sphere::new(Vec3::new(0.0, 0.0, -1.5), 0.5, normals)),
-        Box::new(Sphere::new(Vec3::new(0.5, -0.25, -1.0), 0.25, red_matte)),
-        Box::new(Sphere::new(Vec3::new(1.0, 0.0, -1.5), 0.5, glass)),
-        Box::new(Sphere::new(
-            Vec3::new(0.0, -200.5, -1.0),
-            200.0,
-            green_matte,
-        )),
-    ]));
-    world
+    let frame = option_value(args, "--frame")
+        .map_or("0", String::as_str)
+        .to_string();
+    let fingerprint_settings = move |region: Option<Region>, camera: &Projection<f64>| {
+        fingerprint(
+            format!(
+                "{} samples, {:?}, {:?}, frame {}, region {:?}, camera {:?}",
+                settings.samples, settings.colour_mode, settings.shutter, frame, region, camera
+            )
+            .as_bytes(),
+        )
+    };
+
+    if has_flag(args, "--resume") {
+        let checkpoint = Checkpoint::load(path)
+            .and_then(|checkpoint| {
+                let size = (session.image_data.x_size(), session.image_data.y_size());
+                if (checkpoint.width, checkpoint.height) != size {
+                    return Err("the image size has changed since the checkpoint was taken".into());
+                }
+                let camera = session.camera.read().unwrap();
+                checkpoint.check(
+                    scene,
+                    fingerprint_settings(session.control.region(), &camera),
+                )?;
+                Ok(checkpoint)
+            })
+            .unwrap_or_else(|error| {
+                exit_with_error(&format!("--resume: {}: {}", path.display(), error))
+            });
+        checkpoint.restore(&session.image_data);
+        settings.start_pass = checkpoint.passes as i32;
+        settings.seed = checkpoint.seed;
+        eprintln!("Resuming after {} samples per pixel", settings.start_pass);
+    }
+
+    let path = path.to_path_buf();
+    let image_data = Arc::clone(&session.image_data);
+    let camera = Arc::clone(&session.camera);
+    let mut last_saved = Instant::now();
+    session
+        .control
+        .set_after_pass(Box::new(move |passes, region| {
+            let last = passes >= settings.samples as u32;
+            if !last && last_saved.elapsed() < Duration::from_secs_f64(interval) {
+                return;
+            }
+            let fingerprint = fingerprint_settings(region, &camera.read().unwrap());
+            let checkpoint = Checkpoint::capture(
+                &image_data,
+                region,
+                passes,
+                settings.seed,
+                scene,
+                fingerprint,
+            );
+            match checkpoint.save(&path) {
+                Ok(()) => last_saved = Instant::now(),
+                Err(error) => eprintln!("Could not save {}: {}", path.display(), error),
+            }
+        }));
+    settings
+}
+
+/// The display asked for on the command line, or `default` if none was.
+fn choose_display(args: &[String], default: Box<dyn Display>) -> Box<dyn Display> {
+    if has_flag(args, "--quiet") {
+        Box::new(NullDisplay)
+    } else if has_flag(args, "--preview") {
+        Box::new(TerminalDisplay::new(Some(80)))
+    } else if has_flag(args, "--headless") {
+        Box::new(TerminalDisplay::default())
+    } else {
+        default
+    }
+}
+
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| arg == flag)
+}
+
+fn option_value<'a>(args: &'a [String], option: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == option)
+        .and_then(|i| args.get(i + 1))
 }
 
-fn create_random_world() -> Box<dyn Hittable<f64>> {
+/// The number given for `option`, if any. Exits if it is not a number.
+fn number_option<T: FromStr>(args: &[String], option: &str) -> Option<T> {
+    option_value(args, option).map(|value| {
+        value.parse().unwrap_or_else(|_| {
+            exit_with_error(&format!("{}: expected a number, not '{}'", option, value))
+        })
+    })
+}
+
+fn exit_with_error(error: &dyn std::fmt::Display) -> ! {
+    eprintln!("{}", error);
+    process::exit(1);
+}
+
+fn create_random_world() -> World<f64> {
     let mut rng: ThreadRng = rand::thread_rng();
     let mut hittables: Vec<Box<dyn Hittable<f64>>> = (0..500)
         .flat_map(|i| {
             let a = (((i / 22) % 22) - 11) as f64;
             let b = (i % 22 - 11) as f64;
             let center = Vec3::new(a + 0.9 * rng.gen::<f64>(), 0.2, b + 0.9 * rng.gen::<f64>());
-            if (center.clone() - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+            if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 let material = create_random_material(rng);
                 let sphere: Box<dyn Hittable<f64>> = Box::new(Sphere::new(center, 0.2, material));
                 vec![sphere]
@@ -117,9 +594,7 @@ fn create_random_world() -> Box<dyn Hittable<f64>> {
     hittables.push(Box::new(Sphere::new(
         Vec3::new(0.0, 1.0, 0.0),
         1.0,
-        Material::Dieletric {
-            refractive_index: 1.5,
-        },
+        Material::dielectric(1.5),
     )));
     hittables.push(Box::new(Sphere::new(
         Vec3::new(-4.0, 1.0, 0.0),
@@ -136,12 +611,12 @@ fn create_random_world() -> Box<dyn Hittable<f64>> {
             fuzz: 0.0,
         },
     )));
-    Box::new(World::new(hittables))
+    World::new(hittables)
 }
 
 fn create_random_material(mut rng: ThreadRng) -> Material<f64> {
     let choose_mat = rng.gen::<f64>();
-    let material = if choose_mat < 0.8 {
+    if choose_mat < 0.8 {
         Material::Lambertian {
             albedo: Vec3::new(
                 rng.gen::<f64>() * rng.gen::<f64>(),
@@ -159,9 +634,6 @@ fn create_random_material(mut rng: ThreadRng) -> Material<f64> {
             fuzz: 0.5 * rng.gen::<f64>(),
         }
     } else {
-        Material::Dieletric {
-            refractive_index: 1.5,
-        }
-    };
-    material
+        Material::dielectric(1.5)
+    }
 }
@@ -0,0 +1,128 @@
+use std::fmt;
+
+use vec3::Float;
+
+/// The wavelengths, in nanometres, of the Fraunhofer d, F and C lines that glass catalogues
+/// quote refractive indices and Abbe numbers at.
+pub const D_LINE: f64 = 587.56;
+const F_LINE: f64 = 486.13;
+const C_LINE: f64 = 656.27;
+
+/// How a transparent material's refractive index varies with wavelength, which splits white
+/// light into its colours.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Dispersion<T> {
+    /// Cauchy's equation, `n = a + b / λ²` with λ in micrometres.
+    Cauchy { a: T, b: T },
+    /// The Sellmeier equation, `n² = 1 + Σ b λ² / (λ² - c)` with λ in micrometres.
+    Sellmeier { b: [T; 3], c: [T; 3] },
+}
+
+impl<T: Float> Dispersion<T> {
+    /// The Cauchy fit through `refractive_index` at the d line with the given Abbe number,
+    /// which is how glass is usually specified: lower Abbe numbers disperse more.
+    pub fn from_abbe_number(refractive_index: T, abbe_number: T) -> Dispersion<T> {
+        let one = T::from_f64(1.0);
+        let inverse_square = |nanometres: f64| T::from_f64(1e6 / (nanometres * nanometres));
+        let b = (refractive_index - one)
+            / (abbe_number * (inverse_square(F_LINE) - inverse_square(C_LINE)));
+        Dispersion::Cauchy {
+            a: refractive_index - b * inverse_square(D_LINE),
+            b,
+        }
+    }
+
+    /// The refractive index at `wavelength` in nanometres.
+    pub fn refractive_index(&self, wavelength: T) -> T {
+        let micrometres = wavelength / T::from_f64(1000.0);
+        let square = micrometres * micrometres;
+        match self {
+            Dispersion::Cauchy { a, b } => *a + *b / square,
+            Dispersion::Sellmeier { b, c } => (T::from_f64(1.0)
+                + b.iter()
+                    .zip(c.iter())
+                    .map(|(&b, &c)| b * square / (square - c))
+                    .fold(T::from_f64(0.0), |sum, term| sum + term))
+            .sqrt(),
+        }
+    }
+
+    /// `(n_d - 1) / (n_F - n_C)`.
+    pub fn abbe_number(&self) -> T {
+        let index = |nanometres| self.refractive_index(T::from_f64(nanometres));
+        (index(D_LINE) - T::from_f64(1.0)) / (index(F_LINE) - index(C_LINE))
+    }
+}
+
+impl<T: Float> fmt::Display for Dispersion<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "abbe number {:.1}", self.abbe_number())
+    }
+}
+
+/// Sellmeier coefficients of some common optical materials, from refractiveindex.info.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Glass {
+    /// Schott N-BK7, the usual crown glass of lenses and prisms.
+    Bk7,
+    FusedSilica,
+    Diamond,
+}
+
+impl Glass {
+    pub fn dispersion(self) -> Dispersion<f64> {
+        let (b, c) = match self {
+            Glass::Bk7 => (
+                [1.039_612_12, 0.231_792_344, 1.010_469_45],
+                [0.006_000_698_67, 0.020_017_914_4, 103.560_653],
+            ),
+            Glass::FusedSilica => (
+                [0.696_166_3, 0.407_942_6, 0.897_479_4],
+                [0.004_679_148_26, 0.013_512_063_1, 97.934_002_5],
+            ),
+            Glass::Diamond => ([0.3306, 4.3356, 0.0], [0.030_625, 0.011_236, 0.0]),
+        };
+        Dispersion::Sellmeier { b, c }
+    }
+
+    /// Looks a glass up by its name in scene files: `"bk7"`, `"fused_silica"` or `"diamond"`.
+    pub fn from_name(name: &str) -> Option<Glass> {
+        match name {
+            "bk7" => Some(Glass::Bk7),
+            "fused_silica" => Some(Glass::FusedSilica),
+            "diamond" => Some(Glass::Diamond),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogue_values() {
+        // Refractive index at the d line and Abbe number, from glass catalogues.
+        for &(glass, index, abbe_number) in &[
+            (Glass::Bk7, 1.5168, 64.17),
+            (Glass::FusedSilica, 1.4585, 67.8),
+            (Glass::Diamond, 2.417, 55.3),
+        ] {
+            let dispersion = glass.dispersion();
+            let d_line = dispersion.refractive_index(D_LINE);
+            assert!((d_line - index).abs() < 2e-3, "{:?} {}", glass, d_line);
+            let abbe = dispersion.abbe_number();
+            assert!((abbe - abbe_number).abs() < 1.5, "{:?} {}", glass, abbe);
+        }
+    }
+
+    #[test]
+    fn test_from_abbe_number() {
+        let dispersion = Dispersion::from_abbe_number(1.6, 30.0);
+        assert!((dispersion.refractive_index(D_LINE) - 1.6).abs() < 1e-9);
+        assert!((dispersion.abbe_number() - 30.0).abs() < 1e-9);
+        // Blue bends more than red.
+        assert!(dispersion.refractive_index(450.0) > dispersion.refractive_index(650.0));
+        assert_eq!(dispersion.to_string(), "abbe number 30.0");
+    }
+}
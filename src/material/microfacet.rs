@@ -0,0 +1,319 @@
+use vec3::{Float, Vec3};
+
+/// The GGX / Trowbridge-Reitz distribution of microfacet normals, in a local frame where the
+/// macroscopic surface normal is +z and the x axis is the direction of anisotropy.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct TrowbridgeReitz<T> {
+    pub alpha_x: T,
+    pub alpha_y: T,
+}
+
+impl<T: Float> TrowbridgeReitz<T> {
+    /// Maps artist friendly roughness (0 for a mirror, 1 for very rough) and anisotropy (0 for
+    /// isotropic, up to 1 for highlights stretched along x) to the distribution's alphas, as in
+    /// the Disney BRDF.
+    pub fn from_roughness(roughness: T, anisotropy: T) -> TrowbridgeReitz<T> {
+        let one = T::from_f64(1.0);
+        let clamp = |value: T| value.max(T::from_f64(0.0)).min(one);
+        let alpha = clamp(roughness) * clamp(roughness);
+        let aspect = (one - T::from_f64(0.9) * clamp(anisotropy)).sqrt();
+        // Perfectly smooth surfaces make D a delta function; keep it just about finite.
+        let min_alpha = T::from_f64(1e-4);
+        TrowbridgeReitz {
+            alpha_x: (alpha / aspect).max(min_alpha),
+            alpha_y: (alpha * aspect).max(min_alpha),
+        }
+    }
+
+    /// The density of microfacet normals `wh`, per unit projected solid angle.
+    pub fn d(&self, wh: Vec3<T>) -> T {
+        if wh.z <= T::from_f64(0.0) {
+            return T::from_f64(0.0);
+        }
+        let x = wh.x / self.alpha_x;
+        let y = wh.y / self.alpha_y;
+        let denominator = x * x + y * y + wh.z * wh.z;
+        T::from_f64(1.0) / (T::PI * self.alpha_x * self.alpha_y * denominator * denominator)
+    }
+
+    /// Smith's auxiliary function: the masked area of microfacets seen from `w`, relative to
+    /// the visible area.
+    pub fn lambda(&self, w: Vec3<T>) -> T {
+        let x = self.alpha_x * w.x;
+        let y = self.alpha_y * w.y;
+        let one = T::from_f64(1.0);
+        ((one + (x * x + y * y) / (w.z * w.z)).sqrt() - one) / T::from_f64(2.0)
+    }
+
+    /// The fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: Vec3<T>) -> T {
+        T::from_f64(1.0) / (T::from_f64(1.0) + self.lambda(w))
+    }
+
+    /// The fraction of microfacets visible from both `wo` and `wi` (height correlated).
+    pub fn g(&self, wo: Vec3<T>, wi: Vec3<T>) -> T {
+        T::from_f64(1.0) / (T::from_f64(1.0) + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal in proportion to how much of it is visible from `wo`
+    /// (which must be above the surface), from two uniform random numbers. See Heitz,
+    /// "Sampling the GGX Distribution of Visible Normals" (2018).
+    pub fn sample_visible_normal(&self, wo: Vec3<T>, u1: T, u2: T) -> Vec3<T> {
+        let zero = T::from_f64(0.0);
+        let one = T::from_f64(1.0);
+        // Stretch to the hemisphere configuration, where the distribution is isotropic.
+        let vh = Vec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).unit();
+        let length_squared = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if length_squared > zero {
+            Vec3::new(-vh.y, vh.x, zero) / length_squared.sqrt()
+        } else {
+            Vec3::new(one, zero, zero)
+        };
+        let t2 = vh.cross(t1);
+        // Sample the projected disk, squashed towards the visible half.
+        let r = u1.sqrt();
+        let phi = T::from_f64(2.0) * T::PI * u2;
+        let p1 = r * phi.cos();
+        let s = T::from_f64(0.5) * (one + vh.z);
+        let p2 = (one - s) * (one - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (one - p1 * p1 - p2 * p2).max(zero).sqrt();
+        // And unstretch.
+        Vec3::new(
+            self.alpha_x * nh.x,
+            self.alpha_y * nh.y,
+            nh.z.max(T::from_f64(1e-6)),
+        )
+        .unit()
+    }
+}
+
+/// The microfacet reflection BSDF times the cosine of `wi`, for Fresnel reflectance `fresnel`
+/// at the half vector `wh`: `F D G / (4 cos θo)`. Zero unless both directions are above the
+/// surface.
+pub fn reflection<T: Float>(
+    distribution: TrowbridgeReitz<T>,
+    fresnel: Vec3<T>,
+    wo: Vec3<T>,
+    wi: Vec3<T>,
+    wh: Vec3<T>,
+) -> Vec3<T> {
+    let zero = T::from_f64(0.0);
+    if wo.z <= zero || wi.z <= zero {
+        return Vec3::new(zero, zero, zero);
+    }
+    fresnel * (distribution.d(wh) * distribution.g(wo, wi) / (T::from_f64(4.0) * wo.z))
+}
+
+/// The density per steradian of reflecting `wo` into the direction with half vector `wh` by
+/// sampling a visible normal: `D_wo(wh) / (4 wo·wh)`, which is `G1 D / (4 cos θo)`.
+pub fn reflection_pdf<T: Float>(distribution: TrowbridgeReitz<T>, wo: Vec3<T>, wh: Vec3<T>) -> T {
+    if wo.z <= T::from_f64(0.0) {
+        return T::from_f64(0.0);
+    }
+    distribution.g1(wo) * distribution.d(wh) / (T::from_f64(4.0) * wo.z)
+}
+
+/// Mirrors `wo` about the microfacet normal `wh`.
+pub fn reflect<T: Float>(wo: Vec3<T>, wh: Vec3<T>) -> Vec3<T> {
+    wh * (T::from_f64(2.0) * wo.dot(wh)) - wo
+}
+
+/// Refracts `wo` through the microfacet normal `wh` (on the same side as `wo`) into a medium
+/// with relative index of refraction `eta` (transmitted over incident), or `None` on total
+/// internal reflection.
+pub fn refract<T: Float>(wo: Vec3<T>, wh: Vec3<T>, eta: T) -> Option<Vec3<T>> {
+    let one = T::from_f64(1.0);
+    let cos_i = wo.dot(wh);
+    let sin2_t = (one - cos_i * cos_i).max(T::from_f64(0.0)) / (eta * eta);
+    if sin2_t >= one {
+        return None;
+    }
+    let cos_t = (one - sin2_t).sqrt();
+    Some(wh * (cos_i / eta - cos_t) - wo / eta)
+}
+
+/// The exact Fresnel reflectance of unpolarised light arriving at angle `cos_i` to the normal
+/// of an interface with relative index of refraction `eta` (transmitted over incident).
+pub fn fresnel_dielectric<T: Float>(cos_i: T, eta: T) -> T {
+    let one = T::from_f64(1.0);
+    let cos_i = cos_i.max(T::from_f64(0.0)).min(one);
+    let sin2_t = (one - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= one {
+        return one;
+    }
+    let cos_t = (one - sin2_t).sqrt();
+    let parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (parallel * parallel + perpendicular * perpendicular) / T::from_f64(2.0)
+}
+
+/// The Fresnel reflectance of a conductor with complex index of refraction `eta + ik`, per
+/// colour channel, following Physically Based Rendering (3rd edition), section 8.2.1.
+pub fn fresnel_conductor<T: Float>(cos_i: T, eta: Vec3<T>, k: Vec3<T>) -> Vec3<T> {
+    let channel = |eta: T, k: T| {
+        let (two, four) = (T::from_f64(2.0), T::from_f64(4.0));
+        let cos_i = cos_i.max(T::from_f64(0.0)).min(T::from_f64(1.0));
+        let cos2 = cos_i * cos_i;
+        let sin2 = T::from_f64(1.0) - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + four * eta * eta * k * k).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (T::from_f64(0.5) * (a2_plus_b2 + t0))
+            .max(T::from_f64(0.0))
+            .sqrt();
+        let t2 = two * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        (rp + rs) / two
+    };
+    Vec3::new(
+        channel(eta.x, k.x),
+        channel(eta.y, k.y),
+        channel(eta.z, k.z),
+    )
+}
+
+/// Complex indices of refraction of common metals at red, green and blue wavelengths (650, 550
+/// and 450 nm), rounded from the measured data on refractiveindex.info.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Metal {
+    Aluminium,
+    Chromium,
+    Copper,
+    Gold,
+    Iron,
+    Silver,
+}
+
+impl Metal {
+    pub const ALL: [Metal; 6] = [
+        Metal::Aluminium,
+        Metal::Chromium,
+        Metal::Copper,
+        Metal::Gold,
+        Metal::Iron,
+        Metal::Silver,
+    ];
+
+    /// `(eta, k)` for red, green and blue.
+    pub fn eta_k(self) -> (Vec3<f64>, Vec3<f64>) {
+        let (eta, k) = match self {
+            Metal::Aluminium => ((1.49, 0.96, 0.62), (7.82, 6.69, 5.47)),
+            Metal::Chromium => ((3.18, 2.35, 1.97), (3.31, 3.28, 3.09)),
+            Metal::Copper => ((0.27, 1.12, 1.17), (3.61, 2.59, 2.40)),
+            Metal::Gold => ((0.17, 0.42, 1.44), (3.68, 2.34, 1.80)),
+            Metal::Iron => ((2.88, 2.91, 2.58), (3.05, 2.93, 2.78)),
+            Metal::Silver => ((0.15, 0.13, 0.15), (4.28, 3.41, 2.55)),
+        };
+        (Vec3::new(eta.0, eta.1, eta.2), Vec3::new(k.0, k.1, k.2))
+    }
+
+    /// Looks a metal up by its lower case English name, e.g. `"gold"`.
+    pub fn from_name(name: &str) -> Option<Metal> {
+        Metal::ALL
+            .iter()
+            .cloned()
+            .find(|metal| format!("{:?}", metal).to_lowercase() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::f64::consts::PI;
+
+    fn uniform_hemisphere(rng: &mut StdRng) -> Vec3<f64> {
+        let z: f64 = rng.gen();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+        let r = (1.0 - z * z).sqrt();
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    #[test]
+    fn test_fresnel_dielectric() {
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-12);
+        assert!((fresnel_dielectric(0.0, 1.5) - 1.0).abs() < 1e-12);
+        // Total internal reflection leaving glass beyond the critical angle.
+        assert_eq!(fresnel_dielectric(0.5, 1.0 / 1.5), 1.0);
+    }
+
+    #[test]
+    fn test_fresnel_conductor_without_absorption_is_dielectric() {
+        let eta = Vec3::new(1.5, 1.5, 1.5);
+        let k = Vec3::new(0.0, 0.0, 0.0);
+        for &cos_i in &[1.0, 0.7, 0.2] {
+            let reflectance = fresnel_conductor(cos_i, eta, k);
+            assert!((reflectance.x - fresnel_dielectric(cos_i, 1.5)).abs() < 1e-9);
+        }
+        // Gold reflects red far better than blue.
+        let (eta, k) = Metal::Gold.eta_k();
+        let gold = fresnel_conductor(1.0, eta, k);
+        assert!(gold.x > 0.9 && gold.z < 0.5, "{}", gold);
+    }
+
+    #[test]
+    fn test_distribution_is_normalised() {
+        // The projected area of the microfacets must equal that of the surface.
+        let mut rng = StdRng::seed_from_u64(5);
+        for &(roughness, anisotropy) in &[(0.3, 0.0), (0.6, 0.8), (0.9, 0.3)] {
+            let distribution = TrowbridgeReitz::from_roughness(roughness, anisotropy);
+            let samples = 200_000;
+            let integral = (0..samples)
+                .map(|_| {
+                    let wh = uniform_hemisphere(&mut rng);
+                    distribution.d(wh) * wh.z * 2.0 * PI
+                })
+                .sum::<f64>()
+                / samples as f64;
+            assert!((integral - 1.0).abs() < 0.02, "{}", integral);
+        }
+    }
+
+    #[test]
+    fn test_visible_normals_match_their_density() {
+        // E[wh.z] under the sampled normals against the integral of wh.z times the density of
+        // visible normals, G1(wo) max(0, wo.wh) D(wh) / wo.z.
+        let mut rng = StdRng::seed_from_u64(9);
+        let distribution = TrowbridgeReitz::from_roughness(0.7, 0.5);
+        let wo = Vec3::new(0.6, -0.3, 0.5).unit();
+        let samples = 200_000;
+        let sampled = (0..samples)
+            .map(|_| {
+                let wh = distribution.sample_visible_normal(wo, rng.gen(), rng.gen());
+                assert!(wh.z > 0.0);
+                wh.z
+            })
+            .sum::<f64>()
+            / samples as f64;
+        let integrated = (0..samples)
+            .map(|_| {
+                let wh = uniform_hemisphere(&mut rng);
+                let density = distribution.g1(wo) * wo.dot(wh).max(0.0) * distribution.d(wh) / wo.z;
+                wh.z * density * 2.0 * PI
+            })
+            .sum::<f64>()
+            / samples as f64;
+        assert!(
+            (sampled - integrated).abs() < 0.01,
+            "{} {}",
+            sampled,
+            integrated
+        );
+    }
+
+    #[test]
+    fn test_refract() {
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        let wo = Vec3::new(0.5_f64.sqrt(), 0.0, 0.5_f64.sqrt());
+        let wi = refract(wo, normal, 1.5).unwrap();
+        // Snell's law: sin t = sin i / eta.
+        assert!((wi.x + 0.5_f64.sqrt() / 1.5).abs() < 1e-12);
+        assert!((wi.length() - 1.0).abs() < 1e-12);
+        assert_eq!(refract(wo, normal, 1.0 / 1.5), None);
+        assert_eq!(reflect(wo, normal), Vec3::new(-wo.x, 0.0, wo.z));
+    }
+}
@@ -0,0 +1,842 @@
+extern crate image;
+extern crate rand;
+
+use rand::Rng;
+use std::fmt;
+
+use medium::HenyeyGreenstein;
+use random::{self, ThreadRng};
+use ray::Ray;
+use vec3::matrix::Mat3;
+use vec3::{Float, Vec3};
+
+use self::dispersion::Dispersion;
+use self::microfacet::{fresnel_conductor, fresnel_dielectric, Metal, TrowbridgeReitz};
+use self::principled::Principled;
+
+pub mod dispersion;
+pub mod microfacet;
+pub mod principled;
+
+#[derive(Debug, PartialEq)]
+pub struct LightInteraction<T> {
+    pub directly_emitted: Vec3<T>,
+    pub scattered_rays: Vec<ScatteredRay<T>>,
+}
+
+impl<T: Float> LightInteraction<T> {
+    #[inline]
+    pub fn new(
+        directly_emitted: Vec3<T>,
+        scattered_rays: Vec<ScatteredRay<T>>,
+    ) -> LightInteraction<T> {
+        LightInteraction {
+            directly_emitted,
+            scattered_rays,
+        }
+    }
+
+    #[inline]
+    pub fn directly_emitted(&self) -> Vec3<T> {
+        self.directly_emitted
+    }
+
+    #[inline]
+    pub fn scattered_rays(&self) -> &Vec<ScatteredRay<T>> {
+        &self.scattered_rays
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ScatteredRay<T> {
+    pub ray: Ray<T>,
+    pub attenuation: Vec3<T>,
+}
+
+impl<T: Float> ScatteredRay<T> {
+    #[inline]
+    pub fn new(ray: Ray<T>, attenuation: Vec3<T>) -> ScatteredRay<T> {
+        ScatteredRay { ray, attenuation }
+    }
+
+    #[inline]
+    pub fn ray(self) -> Ray<T> {
+        self.ray
+    }
+
+    #[inline]
+    pub fn attenuation(self) -> Vec3<T> {
+        self.attenuation
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Material<T> {
+    Normal {},
+    Lambertian {
+        albedo: Vec3<T>,
+    },
+    Metal {
+        albedo: Vec3<T>,
+        fuzz: T,
+    },
+    /// Smooth glass. Light crossing the inside is absorbed at `absorption` per unit distance on
+    /// each channel (Beer-Lambert), so thick glass is more deeply tinted than thin. With
+    /// `dispersion` the refractive index depends on the path's wavelength instead.
+    Dieletric {
+        refractive_index: T,
+        absorption: Vec3<T>,
+        dispersion: Option<Dispersion<T>>,
+    },
+    /// A rough metal: GGX microfacets with Fresnel reflectance from the complex index of
+    /// refraction `eta + ik`. Roughness and anisotropy run from 0 to 1.
+    Conductor {
+        eta: Vec3<T>,
+        k: Vec3<T>,
+        roughness: T,
+        anisotropy: T,
+    },
+    /// Frosted glass: a dielectric with GGX microfacets.
+    RoughDielectric {
+        refractive_index: T,
+        roughness: T,
+        anisotropy: T,
+    },
+    Principled(Principled<T>),
+    /// A point inside a participating medium, which scatters `albedo` of the light reaching it
+    /// according to its phase function.
+    Volume {
+        albedo: Vec3<T>,
+        phase: HenyeyGreenstein<T>,
+    },
+}
+
+impl<T: Float> Material<T> {
+    pub fn conductor(metal: Metal, roughness: T, anisotropy: T) -> Material<T> {
+        let (eta, k) = metal.eta_k();
+        Material::Conductor {
+            eta: Vec3::from_f64(eta),
+            k: Vec3::from_f64(k),
+            roughness,
+            anisotropy,
+        }
+    }
+
+    /// Clear glass that neither absorbs nor disperses.
+    pub fn dielectric(refractive_index: T) -> Material<T> {
+        Material::Dieletric {
+            refractive_index,
+            absorption: Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)),
+            dispersion: None,
+        }
+    }
+
+    /// Whether the material behaves differently at different wavelengths, so a path through it
+    /// must be traced at a single wavelength.
+    pub fn is_dispersive(&self) -> bool {
+        match self {
+            Material::Dieletric { dispersion, .. } => dispersion.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Scatters `ray` off the surface. `wavelength`, in nanometres, is set once the path has
+    /// been narrowed to a single wavelength, and is only used by dispersive materials.
+    pub fn interact(
+        &self,
+        ray: Ray<T>,
+        hit_normal: &Ray<T>,
+        wavelength: Option<T>,
+    ) -> LightInteraction<T> {
+        match self {
+            Material::Normal {} => LightInteraction::new(
+                hit_normal
+                    .direction()
+                    .map(|i: T| -> T { i + T::from_f64(1.0) }),
+                vec![],
+            ),
+            Material::Lambertian { albedo } => interact_with_lambertian(hit_normal, albedo),
+            Material::Metal { albedo, fuzz } => interact_with_metal(ray, hit_normal, albedo, fuzz),
+            Material::Dieletric {
+                refractive_index,
+                absorption,
+                dispersion,
+            } => {
+                let refractive_index = match (dispersion, wavelength) {
+                    (Some(dispersion), Some(wavelength)) => dispersion.refractive_index(wavelength),
+                    _ => *refractive_index,
+                };
+                interact_with_dielectric(ray, hit_normal, refractive_index, *absorption)
+            }
+            Material::Conductor {
+                eta,
+                k,
+                roughness,
+                anisotropy,
+            } => interact_with_conductor(
+                ray,
+                hit_normal,
+                *eta,
+                *k,
+                TrowbridgeReitz::from_roughness(*roughness, *anisotropy),
+            ),
+            Material::RoughDielectric {
+                refractive_index,
+                roughness,
+                anisotropy,
+            } => interact_with_rough_dielectric(
+                ray,
+                hit_normal,
+                *refractive_index,
+                TrowbridgeReitz::from_roughness(*roughness, *anisotropy),
+            ),
+            Material::Principled(principled) => {
+                interact_with_principled(ray, hit_normal, principled)
+            }
+            Material::Volume { albedo, phase } => {
+                let mut rng = random::thread_rng();
+                let direction = phase.sample(
+                    ray.direction().unit(),
+                    T::from_f64(rng.gen()),
+                    T::from_f64(rng.gen()),
+                );
+                LightInteraction::new(
+                    Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)),
+                    vec![ScatteredRay::new(
+                        Ray::new(*hit_normal.origin(), direction),
+                        *albedo,
+                    )],
+                )
+            }
+        }
+    }
+}
+
+impl<T: Float> Material<T> {
+    /// How much of the light arriving from `direction` (a unit vector) the surface sends back
+    /// along `ray`, per unit of irradiance: the BSDF times the cosine of the angle to the
+    /// normal, for sampling lights. `None` for materials that only scatter into a few exact
+    /// directions, such as mirrors and smooth glass, which sampled lights can never reach.
+    pub fn evaluate(
+        &self,
+        ray: Ray<T>,
+        hit_normal: &Ray<T>,
+        direction: Vec3<T>,
+    ) -> Option<Vec3<T>> {
+        let zero = T::from_f64(0.0);
+        let black = Vec3::new(zero, zero, zero);
+        let outward_normal = hit_normal.direction().unit();
+        // Work on the side the ray arrives from.
+        let normal = if ray.direction().dot(outward_normal) < zero {
+            outward_normal
+        } else {
+            -outward_normal
+        };
+        let frame = shading_frame(normal);
+        let wo = frame.transpose() * -ray.direction().unit();
+        let wi = frame.transpose() * direction;
+        let wh = (wo + wi).unit();
+        match self {
+            Material::Normal {} | Material::Metal { .. } | Material::Dieletric { .. } => None,
+            Material::Lambertian { albedo } => Some(*albedo * (wi.z.max(zero) / T::PI)),
+            Material::Conductor {
+                eta,
+                k,
+                roughness,
+                anisotropy,
+            } => Some(microfacet::reflection(
+                TrowbridgeReitz::from_roughness(*roughness, *anisotropy),
+                fresnel_conductor(wo.dot(wh), *eta, *k),
+                wo,
+                wi,
+                wh,
+            )),
+            // Only the reflection off the outside; transmitted light is left to `interact`.
+            Material::RoughDielectric {
+                refractive_index,
+                roughness,
+                anisotropy,
+            } => Some(if normal == outward_normal {
+                let reflectance = fresnel_dielectric(wo.dot(wh), *refractive_index);
+                microfacet::reflection(
+                    TrowbridgeReitz::from_roughness(*roughness, *anisotropy),
+                    Vec3::new(reflectance, reflectance, reflectance),
+                    wo,
+                    wi,
+                    wh,
+                )
+            } else {
+                black
+            }),
+            Material::Principled(principled) => Some(if normal == outward_normal {
+                principled.evaluate(wo, wi)
+            } else {
+                black
+            }),
+            Material::Volume { albedo, phase } => {
+                Some(*albedo * phase.p(ray.direction().unit().dot(direction)))
+            }
+        }
+    }
+
+    /// The density per steradian with which `interact` scatters `ray` into `direction` (a unit
+    /// vector), for weighting light found by scattering against light found by sampling it.
+    /// `None` where only one of the two can find the light: directions `evaluate` does not
+    /// account for, such as those transmitted through the surface, and materials that only
+    /// scatter into a few exact directions.
+    pub fn pdf(&self, ray: Ray<T>, hit_normal: &Ray<T>, direction: Vec3<T>) -> Option<T> {
+        let zero = T::from_f64(0.0);
+        let outward_normal = hit_normal.direction().unit();
+        // Light sampling only reflects off the outside of surfaces.
+        let entering = ray.direction().dot(outward_normal) < zero;
+        let frame = shading_frame(outward_normal);
+        let wo = frame.transpose() * -ray.direction().unit();
+        let wi = frame.transpose() * direction;
+        let wh = (wo + wi).unit();
+        let reflecting = entering && wi.z > zero;
+        match self {
+            Material::Lambertian { .. } => Some(wi.z / T::PI).filter(|_| reflecting),
+            Material::Conductor {
+                roughness,
+                anisotropy,
+                ..
+            } => Some(microfacet::reflection_pdf(
+                TrowbridgeReitz::from_roughness(*roughness, *anisotropy),
+                wo,
+                wh,
+            ))
+            .filter(|_| reflecting),
+            Material::RoughDielectric {
+                refractive_index,
+                roughness,
+                anisotropy,
+            } => Some(
+                microfacet::reflection_pdf(
+                    TrowbridgeReitz::from_roughness(*roughness, *anisotropy),
+                    wo,
+                    wh,
+                ) * fresnel_dielectric(wo.dot(wh), *refractive_index),
+            )
+            .filter(|_| reflecting),
+            Material::Principled(principled) => Some(principled.pdf(wo, wi)).filter(|_| reflecting),
+            Material::Volume { phase, .. } => Some(phase.p(ray.direction().unit().dot(direction))),
+            Material::Normal {} | Material::Metal { .. } | Material::Dieletric { .. } => None,
+        }
+    }
+}
+
+impl<T: Float> fmt::Display for Material<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Material::Normal {} => write!(f, "Normal"),
+            Material::Lambertian { albedo } => write!(f, "Lambertian albedo {:.2}", albedo),
+            Material::Metal { albedo, fuzz } => {
+                write!(f, "Metal albedo {:.2}, fuzz {:.2}", albedo, fuzz)
+            }
+            Material::Dieletric {
+                refractive_index,
+                absorption,
+                dispersion,
+            } => {
+                write!(f, "Dielectric refractive index {:.2}", refractive_index)?;
+                if absorption.squared_length() > T::from_f64(0.0) {
+                    write!(f, ", absorption {:.2}", absorption)?;
+                }
+                match dispersion {
+                    Some(dispersion) => write!(f, ", {}", dispersion),
+                    None => Ok(()),
+                }
+            }
+            Material::Conductor {
+                eta,
+                k,
+                roughness,
+                anisotropy,
+            } => write!(
+                f,
+                "Conductor eta {:.2}, k {:.2}, roughness {:.2}, anisotropy {:.2}",
+                eta, k, roughness, anisotropy
+            ),
+            Material::RoughDielectric {
+                refractive_index,
+                roughness,
+                anisotropy,
+            } => write!(
+                f,
+                "Rough dielectric refractive index {:.2}, roughness {:.2}, anisotropy {:.2}",
+                refractive_index, roughness, anisotropy
+            ),
+            Material::Principled(principled) => write!(f, "{}", principled),
+            Material::Volume { albedo, phase } => {
+                write!(f, "Volume albedo {:.2}, anisotropy {:.2}", albedo, phase.g)
+            }
+        }
+    }
+}
+
+#[inline]
+fn interact_with_lambertian<T: Float>(
+    hit_normal: &Ray<T>,
+    albedo: &Vec3<T>,
+) -> LightInteraction<T> {
+    // A point on the unit sphere resting on the surface, which makes directions cosine
+    // weighted, as `pdf` expects.
+    let target = *hit_normal.origin() + *hit_normal.direction() + random_in_unit_sphere().unit();
+    let scattered_rays = vec![ScatteredRay::new(
+        Ray::new(*hit_normal.origin(), target - *hit_normal.origin()),
+        *albedo,
+    )];
+    LightInteraction::new(Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)), scattered_rays)
+}
+
+#[inline]
+fn interact_with_metal<T: Float>(
+    ray: Ray<T>,
+    hit_normal: &Ray<T>,
+    albedo: &Vec3<T>,
+    fuzz: &T,
+) -> LightInteraction<T> {
+    let reflected = reflect(*ray.direction(), hit_normal.direction());
+
+    let scattered_rays = Some(ScatteredRay::new(
+        Ray::new(
+            *hit_normal.origin(),
+            reflected + *fuzz * random_in_unit_sphere(),
+        ),
+        *albedo,
+    ))
+    .into_iter()
+    .filter(|scattered_ray| {
+        (*scattered_ray.ray().direction()).dot(*hit_normal.direction()) > T::from_f64(0.0)
+    })
+    .collect();
+    LightInteraction::new(Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)), scattered_rays)
+}
+
+#[inline]
+fn reflect<T: Float>(inbound: Vec3<T>, normal: &Vec3<T>) -> Vec3<T> {
+    inbound - T::from_f64(2.0) * inbound.dot(*normal) * *normal
+}
+
+/// The absorption coefficient that leaves light `color` after travelling `distance`, as glTF
+/// and most renderers let artists specify tinted glass.
+pub fn absorption_for_color<T: Float>(color: Vec3<T>, distance: T) -> Vec3<T> {
+    color.map(|channel| -channel.max(T::from_f64(1e-6)).ln() / distance)
+}
+
+#[inline]
+fn interact_with_dielectric<T: Float>(
+    ray: Ray<T>,
+    hit_normal: &Ray<T>,
+    refractive_index: T,
+    absorption: Vec3<T>,
+) -> LightInteraction<T> {
+    let reflected = reflect(*ray.direction(), hit_normal.direction());
+    let hit_normal_unit_vector = hit_normal.direction().unit();
+    let exiting = ray.direction().unit().dot(hit_normal_unit_vector) > T::from_f64(0.0);
+    let (outward_normal, ni_over_nt, cosine) = if exiting {
+        (
+            -hit_normal_unit_vector,
+            refractive_index,
+            refractive_index * ray.direction().dot(hit_normal_unit_vector)
+                / ray.direction().length(),
+        )
+    } else {
+        (
+            hit_normal_unit_vector,
+            T::from_f64(1.0) / refractive_index,
+            -(ray.direction().dot(hit_normal_unit_vector) / ray.direction().length()),
+        )
+    };
+    // A ray hitting the surface from inside has just crossed the interior, from its origin to
+    // here, and is dimmed by how far that was.
+    let transmittance = if exiting {
+        let distance = (*hit_normal.origin() - *ray.origin()).length();
+        absorption.map(|channel| (-channel * distance).exp())
+    } else {
+        Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0))
+    };
+    let scattered = refract(*ray.direction(), &outward_normal, ni_over_nt)
+        .filter(|_| {
+            schlick(cosine, refractive_index) < T::from_f64(random::thread_rng().gen::<f64>())
+        })
+        .or(Some(reflected))
+        .map(|ray_direction| {
+            ScatteredRay::new(Ray::new(*hit_normal.origin(), ray_direction), transmittance)
+        })
+        .into_iter()
+        .collect();
+
+    LightInteraction::new(Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)), scattered)
+}
+
+/// The shading frame at a hit: columns are the tangent (the direction of anisotropy), the
+/// bitangent and `normal`, so it takes local directions to world directions and its transpose
+/// takes them back.
+fn shading_frame<T: Float>(normal: Vec3<T>) -> Mat3<T> {
+    let (tangent, bitangent) = normal.orthonormal_basis();
+    Mat3::from_columns(tangent, bitangent, normal)
+}
+
+/// Samples a reflection off the visible microfacets. The weight, Fresnel times the
+/// shadowing of the outgoing direction, never exceeds the Fresnel term, so no energy is created.
+fn interact_with_conductor<T: Float>(
+    ray: Ray<T>,
+    hit_normal: &Ray<T>,
+    eta: Vec3<T>,
+    k: Vec3<T>,
+    distribution: TrowbridgeReitz<T>,
+) -> LightInteraction<T> {
+    let zero = T::from_f64(0.0);
+    let frame = shading_frame(hit_normal.direction().unit());
+    let wo = frame.transpose() * -ray.direction().unit();
+    let mut rng = random::thread_rng();
+    let scattered_rays = Some(wo)
+        .filter(|wo| wo.z > zero)
+        .and_then(|wo| {
+            let wh = distribution.sample_visible_normal(
+                wo,
+                T::from_f64(rng.gen()),
+                T::from_f64(rng.gen()),
+            );
+            let wi = microfacet::reflect(wo, wh);
+            Some(wi).filter(|wi| wi.z > zero).map(|wi| {
+                let weight = fresnel_conductor(wo.dot(wh), eta, k) * distribution.g(wo, wi)
+                    / distribution.g1(wo);
+                ScatteredRay::new(Ray::new(*hit_normal.origin(), frame * wi), weight)
+            })
+        })
+        .into_iter()
+        .collect();
+    LightInteraction::new(Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)), scattered_rays)
+}
+
+/// Samples a visible microfacet, then reflects or refracts through it in proportion to its
+/// Fresnel reflectance, which leaves a weight of just the shadowing term either way.
+fn interact_with_rough_dielectric<T: Float>(
+    ray: Ray<T>,
+    hit_normal: &Ray<T>,
+    refractive_index: T,
+    distribution: TrowbridgeReitz<T>,
+) -> LightInteraction<T> {
+    let zero = T::from_f64(0.0);
+    let outward_normal = hit_normal.direction().unit();
+    let direction = ray.direction().unit();
+    // Work on the side the ray arrives from, with eta as transmitted over incident.
+    let (normal, eta) = if direction.dot(outward_normal) < zero {
+        (outward_normal, refractive_index)
+    } else {
+        (-outward_normal, T::from_f64(1.0) / refractive_index)
+    };
+    let frame = shading_frame(normal);
+    let wo = frame.transpose() * -direction;
+    let mut rng = random::thread_rng();
+    let scattered_rays = Some(wo)
+        .filter(|wo| wo.z > zero)
+        .and_then(|wo| {
+            let wh = distribution.sample_visible_normal(
+                wo,
+                T::from_f64(rng.gen()),
+                T::from_f64(rng.gen()),
+            );
+            let reflectance = fresnel_dielectric(wo.dot(wh), eta);
+            let wi = if T::from_f64(rng.gen()) < reflectance {
+                Some(microfacet::reflect(wo, wh)).filter(|wi| wi.z > zero)
+            } else {
+                microfacet::refract(wo, wh, eta).filter(|wi| wi.z < zero)
+            };
+            wi.map(|wi| {
+                let weight = distribution.g(wo, wi) / distribution.g1(wo);
+                ScatteredRay::new(
+                    Ray::new(*hit_normal.origin(), frame * wi),
+                    Vec3::new(weight, weight, weight),
+                )
+            })
+        })
+        .into_iter()
+        .collect();
+    LightInteraction::new(Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)), scattered_rays)
+}
+
+fn interact_with_principled<T: Float>(
+    ray: Ray<T>,
+    hit_normal: &Ray<T>,
+    principled: &Principled<T>,
+) -> LightInteraction<T> {
+    let outward_normal = hit_normal.direction().unit();
+    let direction = ray.direction().unit();
+    let entering = direction.dot(outward_normal) < T::from_f64(0.0);
+    let frame = shading_frame(if entering {
+        outward_normal
+    } else {
+        -outward_normal
+    });
+    let mut rng = random::thread_rng();
+    let scattered_rays = principled
+        .sample(frame.transpose() * -direction, entering, &mut || {
+            T::from_f64(rng.gen())
+        })
+        .map(|(wi, weight)| ScatteredRay::new(Ray::new(*hit_normal.origin(), frame * wi), weight))
+        .into_iter()
+        .collect();
+    LightInteraction::new(Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)), scattered_rays)
+}
+
+#[inline]
+fn schlick<T: Float>(cosine: T, refractive_index: T) -> T {
+    let one = T::from_f64(1.0);
+    let r0 = (one - refractive_index) / (one + refractive_index);
+    let r0 = r0 * r0;
+    r0 + (one - r0) * (one - cosine).powi(5)
+}
+
+#[inline]
+fn refract<T: Float>(inbound: Vec3<T>, normal: &Vec3<T>, ni_over_nt: T) -> Option<Vec3<T>> {
+    let inbound_unit = inbound.unit();
+    let dt = inbound_unit.dot(*normal);
+    let one = T::from_f64(1.0);
+    let discriminant = one - ni_over_nt * ni_over_nt * (one - dt * dt);
+    if discriminant > T::from_f64(0.0) {
+        Some(ni_over_nt * (inbound_unit - *normal * dt) - *normal * discriminant.sqrt())
+    } else {
+        None
+    }
+}
+
+#[inline]
+fn random_in_unit_sphere<T: Float>() -> Vec3<T> {
+    let mut rng: ThreadRng = random::thread_rng();
+    let mut p: Vec3<f64>;
+    loop {
+        p = 2.0 * Vec3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>())
+            - Vec3::new(1f64, 1f64, 1f64);
+        if p.squared_length() < 1.0 {
+            break;
+        }
+    }
+    Vec3::from_f64(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use material::dispersion::Glass;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn hit_normal() -> Ray<f64> {
+        Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0))
+    }
+
+    #[test]
+    fn test_smooth_conductor_is_a_mirror() {
+        let silver = Material::conductor(Metal::Silver, 0.0, 0.0);
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let interaction = silver.interact(ray, &hit_normal(), None);
+        let scattered = interaction.scattered_rays()[0];
+        let direction = scattered.ray().direction().unit();
+        assert!((direction - Vec3::new(1.0, 1.0, 0.0).unit()).length() < 1e-3);
+        assert!(scattered.attenuation().x > 0.9);
+    }
+
+    #[test]
+    fn test_rough_materials_conserve_energy() {
+        let materials = [
+            Material::conductor(Metal::Aluminium, 0.8, 0.5),
+            Material::conductor(Metal::Gold, 0.3, 0.0),
+            Material::RoughDielectric {
+                refractive_index: 1.5,
+                roughness: 0.5,
+                anisotropy: 0.2,
+            },
+        ];
+        let ray = Ray::new(Vec3::new(-1.0, 0.2, 0.3), Vec3::new(1.0, -0.2, -0.3));
+        for material in materials.iter() {
+            for _ in 0..1000 {
+                for scattered in material.interact(ray, &hit_normal(), None).scattered_rays() {
+                    let attenuation = scattered.attenuation();
+                    assert!(attenuation.x <= 1.0 && attenuation.y <= 1.0 && attenuation.z <= 1.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_evaluate_matches_interact() {
+        // Integrating the light reflected from every direction must give the same albedo as
+        // averaging the weights of sampled reflections.
+        let mut rng = StdRng::seed_from_u64(5);
+        let materials = [
+            Material::Lambertian {
+                albedo: Vec3::new(0.8, 0.5, 0.2),
+            },
+            Material::conductor(Metal::Gold, 0.5, 0.0),
+            Material::conductor(Metal::Aluminium, 0.3, 0.6),
+            Material::Principled(Principled {
+                roughness: 0.4,
+                clearcoat: 1.0,
+                // Smooth lobes are too sharp to integrate by sampling the hemisphere.
+                clearcoat_roughness: 0.3,
+                sheen: 0.5,
+                ..Principled::default()
+            }),
+        ];
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.3), Vec3::new(1.0, -1.0, -0.3));
+        let samples = 50_000;
+        for material in materials.iter() {
+            let mut sampled = Vec3::new(0.0, 0.0, 0.0);
+            let mut integrated = Vec3::new(0.0, 0.0, 0.0);
+            for _ in 0..samples {
+                for scattered in material.interact(ray, &hit_normal(), None).scattered_rays() {
+                    sampled = sampled + scattered.attenuation();
+                }
+                // Uniformly over the hemisphere, a density of 1 / 2π.
+                let z: f64 = rng.gen();
+                let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+                let r = (1.0 - z * z).sqrt();
+                let direction = Vec3::new(r * phi.cos(), z, r * phi.sin());
+                let reflected = material.evaluate(ray, &hit_normal(), direction).unwrap();
+                integrated = integrated + reflected * (2.0 * std::f64::consts::PI);
+            }
+            let difference = (sampled - integrated) / samples as f64;
+            assert!(
+                difference
+                    .x
+                    .abs()
+                    .max(difference.y.abs())
+                    .max(difference.z.abs())
+                    < 0.03,
+                "{}: sampled {} but integrated {}",
+                material,
+                sampled / samples as f64,
+                integrated / samples as f64
+            );
+        }
+        assert_eq!(
+            Material::dielectric(1.5).evaluate(ray, &hit_normal(), Vec3::new(0.0, 1.0, 0.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pdf_matches_interact() {
+        // The share of sampled directions that `pdf` covers, and their mean height, against
+        // integrating `pdf` over the sphere.
+        let mut rng = StdRng::seed_from_u64(8);
+        let materials = [
+            Material::Lambertian {
+                albedo: Vec3::new(0.8, 0.5, 0.2),
+            },
+            Material::conductor(Metal::Gold, 0.8, 0.0),
+            Material::RoughDielectric {
+                refractive_index: 1.5,
+                roughness: 0.4,
+                anisotropy: 0.0,
+            },
+            Material::Volume {
+                albedo: Vec3::new(0.9, 0.9, 0.9),
+                phase: HenyeyGreenstein { g: 0.6 },
+            },
+        ];
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.3), Vec3::new(1.0, -1.0, -0.3));
+        let samples = 100_000;
+        for material in materials.iter() {
+            let mut sampled = (0.0, 0.0);
+            let mut integrated = (0.0, 0.0);
+            for _ in 0..samples {
+                for scattered in material.interact(ray, &hit_normal(), None).scattered_rays() {
+                    let direction = scattered.ray().direction().unit();
+                    if material.pdf(ray, &hit_normal(), direction).is_some() {
+                        sampled = (sampled.0 + 1.0, sampled.1 + direction.y);
+                    }
+                }
+                // Uniformly over the sphere, a density of 1 / 4π.
+                let y = 1.0 - 2.0 * rng.gen::<f64>();
+                let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+                let r = (1.0 - y * y).sqrt();
+                let direction = Vec3::new(r * phi.cos(), y, r * phi.sin());
+                let pdf = material.pdf(ray, &hit_normal(), direction).unwrap_or(0.0);
+                let weight = pdf * 4.0 * std::f64::consts::PI;
+                integrated = (integrated.0 + weight, integrated.1 + weight * y);
+            }
+            for &(sampled, integrated) in &[(sampled.0, integrated.0), (sampled.1, integrated.1)] {
+                assert!(
+                    (sampled - integrated).abs() / (samples as f64) < 0.02,
+                    "{}: sampled {} but integrated {}",
+                    material,
+                    sampled / samples as f64,
+                    integrated / samples as f64
+                );
+            }
+        }
+        assert_eq!(
+            Material::dielectric(1.5).pdf(ray, &hit_normal(), Vec3::new(0.0, 1.0, 0.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_absorption_depends_on_distance_inside() {
+        let glass = Material::Dieletric {
+            refractive_index: 1.5,
+            absorption: Vec3::new(0.5, 0.0, 0.0),
+            dispersion: None,
+        };
+        // Leaving the glass after travelling 2 units inside it.
+        let inside = Ray::new(Vec3::new(0.0, -2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let attenuation =
+            glass.interact(inside, &hit_normal(), None).scattered_rays()[0].attenuation();
+        assert!((attenuation.x - (-1.0f64).exp()).abs() < 1e-12);
+        assert_eq!((attenuation.y, attenuation.z), (1.0, 1.0));
+        // Entering it, nothing has been absorbed yet.
+        let outside = Ray::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let attenuation = glass
+            .interact(outside, &hit_normal(), None)
+            .scattered_rays()[0]
+            .attenuation();
+        assert_eq!(attenuation, Vec3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_dispersion_bends_blue_more_than_red() {
+        let diamond = Material::Dieletric {
+            refractive_index: 2.417,
+            absorption: Vec3::new(0.0, 0.0, 0.0),
+            dispersion: Some(Glass::Diamond.dispersion()),
+        };
+        assert!(diamond.is_dispersive());
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        // The sine of the refracted ray's angle to the normal.
+        let refracted_sine = |wavelength: f64| {
+            (0..100)
+                .flat_map(|_| {
+                    diamond
+                        .interact(ray, &hit_normal(), Some(wavelength))
+                        .scattered_rays
+                })
+                .map(|scattered| scattered.ray().direction().unit())
+                .find(|direction| direction.y < 0.0)
+                .unwrap()
+                .x
+        };
+        assert!(refracted_sine(450.0) < refracted_sine(650.0));
+    }
+
+    #[test]
+    fn test_rough_dielectric_transmits_from_both_sides() {
+        let glass = Material::RoughDielectric {
+            refractive_index: 1.5,
+            roughness: 0.1,
+            anisotropy: 0.0,
+        };
+        for &direction in &[Vec3::new(0.0, -1.0, 0.1), Vec3::new(0.0, 1.0, 0.1)] {
+            let ray = Ray::new(-direction, direction);
+            let transmitted = (0..1000)
+                .flat_map(|_| glass.interact(ray, &hit_normal(), None).scattered_rays)
+                .filter(|scattered| scattered.ray().direction().dot(direction) > 0.0)
+                .count();
+            // Around 4% is reflected at near normal incidence.
+            assert!(transmitted > 900, "{}", transmitted);
+        }
+    }
+}
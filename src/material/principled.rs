@@ -0,0 +1,371 @@
+use std::fmt;
+
+use material::microfacet::{self, fresnel_dielectric, reflect, refract, TrowbridgeReitz};
+use vec3::{Float, Vec3};
+
+/// A Disney style "principled" material, described the way artists and glTF assets describe
+/// surfaces: a base colour plus how metallic, rough, coated, velvety and transparent it is. All
+/// parameters but `ior` run from 0 to 1.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Principled<T> {
+    pub base_color: Vec3<T>,
+    pub metallic: T,
+    pub roughness: T,
+    pub anisotropy: T,
+    /// Strength of the specular reflection of non-metals; 0.5 is the usual 4%.
+    pub specular: T,
+    /// Tints that reflection towards the base colour.
+    pub specular_tint: T,
+    /// A soft grazing-angle sheen, as on cloth.
+    pub sheen: T,
+    pub sheen_tint: T,
+    /// A clear varnish layer on top of everything else.
+    pub clearcoat: T,
+    pub clearcoat_roughness: T,
+    /// How much of the non-metallic base lets light through rather than scattering it diffusely.
+    pub transmission: T,
+    pub ior: T,
+}
+
+impl<T: Float> Default for Principled<T> {
+    fn default() -> Principled<T> {
+        Principled {
+            base_color: Vec3::from_f64(Vec3::new(0.8, 0.8, 0.8)),
+            metallic: T::from_f64(0.0),
+            roughness: T::from_f64(0.5),
+            anisotropy: T::from_f64(0.0),
+            specular: T::from_f64(0.5),
+            specular_tint: T::from_f64(0.0),
+            sheen: T::from_f64(0.0),
+            sheen_tint: T::from_f64(0.5),
+            clearcoat: T::from_f64(0.0),
+            clearcoat_roughness: T::from_f64(0.03),
+            transmission: T::from_f64(0.0),
+            ior: T::from_f64(1.5),
+        }
+    }
+}
+
+impl<T: Float> Principled<T> {
+    /// Picks one lobe at random, in proportion to how much light it reflects, and samples a
+    /// direction from it. Directions are in the local shading frame with the normal along +z on
+    /// the side of `wo`; `entering` says whether that is the outside of the surface. Returns the
+    /// incoming direction and its weight, or `None` if the light is absorbed.
+    pub fn sample(
+        &self,
+        wo: Vec3<T>,
+        entering: bool,
+        random: &mut dyn FnMut() -> T,
+    ) -> Option<(Vec3<T>, Vec3<T>)> {
+        let zero = T::from_f64(0.0);
+        let one = T::from_f64(1.0);
+        let white = Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0));
+        let distribution = TrowbridgeReitz::from_roughness(self.roughness, self.anisotropy);
+        // Light already inside a transmissive object can only meet the glass lobe on its way out.
+        if !entering {
+            return self.sample_glass(wo, one / self.ior, distribution, random);
+        }
+
+        // The clear coat sits on top: it reflects a Fresnel fraction of the light, and the rest
+        // carries on down to the base.
+        let coat_reflectance = self.clearcoat * fresnel_dielectric(wo.z, T::from_f64(1.5)).min(one);
+        if random() < coat_reflectance {
+            let coat = TrowbridgeReitz::from_roughness(self.clearcoat_roughness, zero);
+            let wh = coat.sample_visible_normal(wo, random(), random());
+            let wi = reflect(wo, wh);
+            return Some(wi)
+                .filter(|wi| wi.z > zero)
+                .map(|wi| (wi, white * (coat.g(wo, wi) / coat.g1(wo))));
+        }
+
+        if random() < self.metallic {
+            // Metals reflect with Fresnel tinted by the base colour, in Schlick's approximation.
+            let wh = distribution.sample_visible_normal(wo, random(), random());
+            let wi = reflect(wo, wh);
+            return Some(wi).filter(|wi| wi.z > zero).map(|wi| {
+                let fresnel = schlick(self.base_color, wo.dot(wh));
+                (wi, fresnel * (distribution.g(wo, wi) / distribution.g1(wo)))
+            });
+        }
+
+        // Non-metals: a specular reflection over either glass or a diffuse base.
+        let tint = luminance_normalised(self.base_color);
+        let specular_color = self.specular_color();
+        let wh = distribution.sample_visible_normal(wo, random(), random());
+        let reflectance = schlick(specular_color, wo.dot(wh));
+        let reflect_probability = reflectance.x.max(reflectance.y).max(reflectance.z);
+        if random() < reflect_probability {
+            let wi = reflect(wo, wh);
+            return Some(wi).filter(|wi| wi.z > zero).map(|wi| {
+                let weight = distribution.g(wo, wi) / distribution.g1(wo) / reflect_probability;
+                (wi, reflectance * weight)
+            });
+        }
+        if random() < self.transmission {
+            return refract(wo, wh, self.ior)
+                .filter(|wi| wi.z < zero)
+                .map(|wi| {
+                    let weight = distribution.g(wo, wi) / distribution.g1(wo);
+                    (wi, self.base_color * weight)
+                });
+        }
+
+        // Cosine weighted diffuse, plus sheen towards grazing angles.
+        let (u1, u2) = (random(), random());
+        let r = u1.sqrt();
+        let phi = T::from_f64(2.0) * T::PI * u2;
+        let wi = Vec3::new(r * phi.cos(), r * phi.sin(), (one - u1).sqrt());
+        let sheen_color = (white * (one - self.sheen_tint) + tint * self.sheen_tint) * self.sheen;
+        let grazing = (one - wi.dot((wi + wo).unit())).powi(5);
+        let weight = self.base_color + sheen_color * grazing;
+        Some((wi, weight.map(|channel| channel.min(one))))
+    }
+
+    /// The light reflected towards `wo` of the light arriving from `wi`, per unit of irradiance
+    /// (the BSDF times the cosine of `wi`), for light sampling. Each lobe is weighted by how
+    /// often `sample` would pick it, except that the diffuse lobe's share is estimated at the
+    /// macroscopic normal rather than averaged over the microfacets. Light transmitted through
+    /// the surface is left to `sample`.
+    pub fn evaluate(&self, wo: Vec3<T>, wi: Vec3<T>) -> Vec3<T> {
+        let zero = T::from_f64(0.0);
+        let one = T::from_f64(1.0);
+        let white = Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0));
+        if wo.z <= zero || wi.z <= zero {
+            return white * zero;
+        }
+        let wh = (wo + wi).unit();
+        let distribution = TrowbridgeReitz::from_roughness(self.roughness, self.anisotropy);
+
+        let coat_reflectance = self.clearcoat * fresnel_dielectric(wo.z, T::from_f64(1.5)).min(one);
+        let coat = microfacet::reflection(
+            TrowbridgeReitz::from_roughness(self.clearcoat_roughness, zero),
+            white,
+            wo,
+            wi,
+            wh,
+        );
+        let metal = microfacet::reflection(
+            distribution,
+            schlick(self.base_color, wo.dot(wh)),
+            wo,
+            wi,
+            wh,
+        );
+
+        let tint = luminance_normalised(self.base_color);
+        let specular_color = self.specular_color();
+        let specular = microfacet::reflection(
+            distribution,
+            schlick(specular_color, wo.dot(wh)),
+            wo,
+            wi,
+            wh,
+        );
+        let normal_reflectance = schlick(specular_color, wo.z);
+        let diffuse_share = (one
+            - normal_reflectance
+                .x
+                .max(normal_reflectance.y)
+                .max(normal_reflectance.z))
+            * (one - self.transmission);
+        let sheen_color = (white * (one - self.sheen_tint) + tint * self.sheen_tint) * self.sheen;
+        let grazing = (one - wi.dot(wh)).powi(5);
+        let diffuse = (self.base_color + sheen_color * grazing).map(|channel| channel.min(one))
+            * (wi.z / T::PI);
+
+        coat * coat_reflectance
+            + (metal * self.metallic + (specular + diffuse * diffuse_share) * (one - self.metallic))
+                * (one - coat_reflectance)
+    }
+
+    /// The density per steradian with which `sample` picks `wi`, for the light `evaluate`
+    /// accounts for. Like `evaluate`, it estimates the diffuse lobe's share at the macroscopic
+    /// normal.
+    pub fn pdf(&self, wo: Vec3<T>, wi: Vec3<T>) -> T {
+        let zero = T::from_f64(0.0);
+        let one = T::from_f64(1.0);
+        if wo.z <= zero || wi.z <= zero {
+            return zero;
+        }
+        let wh = (wo + wi).unit();
+        let distribution = TrowbridgeReitz::from_roughness(self.roughness, self.anisotropy);
+        let coat_reflectance = self.clearcoat * fresnel_dielectric(wo.z, T::from_f64(1.5)).min(one);
+        let coat = microfacet::reflection_pdf(
+            TrowbridgeReitz::from_roughness(self.clearcoat_roughness, zero),
+            wo,
+            wh,
+        );
+        let specular = microfacet::reflection_pdf(distribution, wo, wh);
+        let max = |color: Vec3<T>| color.x.max(color.y).max(color.z);
+        let reflect_probability = max(schlick(self.specular_color(), wo.dot(wh)));
+        let diffuse_share =
+            (one - max(schlick(self.specular_color(), wo.z))) * (one - self.transmission);
+        coat * coat_reflectance
+            + (specular * self.metallic
+                + (specular * reflect_probability + wi.z / T::PI * diffuse_share)
+                    * (one - self.metallic))
+                * (one - coat_reflectance)
+    }
+
+    /// The reflectance of non-metals at normal incidence.
+    fn specular_color(&self) -> Vec3<T> {
+        let one = T::from_f64(1.0);
+        let white = Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0));
+        let tint = luminance_normalised(self.base_color);
+        (white * (one - self.specular_tint) + tint * self.specular_tint)
+            * (T::from_f64(0.08) * self.specular)
+    }
+
+    /// Rough glass, for light leaving a transmissive object. The base colour already tinted it on
+    /// the way in.
+    fn sample_glass(
+        &self,
+        wo: Vec3<T>,
+        eta: T,
+        distribution: TrowbridgeReitz<T>,
+        random: &mut dyn FnMut() -> T,
+    ) -> Option<(Vec3<T>, Vec3<T>)> {
+        let zero = T::from_f64(0.0);
+        let wh = distribution.sample_visible_normal(wo, random(), random());
+        let wi = if random() < fresnel_dielectric(wo.dot(wh), eta) {
+            Some(reflect(wo, wh)).filter(|wi| wi.z > zero)
+        } else {
+            refract(wo, wh, eta).filter(|wi| wi.z < zero)
+        };
+        wi.map(|wi| {
+            let weight = distribution.g(wo, wi) / distribution.g1(wo);
+            (wi, Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0)) * weight)
+        })
+    }
+}
+
+impl<T: Float> fmt::Display for Principled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Principled base {:.2}, metallic {:.2}, roughness {:.2}",
+            self.base_color, self.metallic, self.roughness
+        )?;
+        for &(name, value) in &[
+            ("clearcoat", self.clearcoat),
+            ("sheen", self.sheen),
+            ("transmission", self.transmission),
+        ] {
+            // Only mention the optional lobes that are switched on.
+            if value > T::from_f64(0.0) {
+                write!(f, ", {} {:.2}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Schlick's approximation to Fresnel reflectance with reflectance `f0` at normal incidence.
+fn schlick<T: Float>(f0: Vec3<T>, cos_i: T) -> Vec3<T> {
+    let one = T::from_f64(1.0);
+    let factor = (one - cos_i.max(T::from_f64(0.0))).powi(5);
+    f0 + (Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0)) - f0) * factor
+}
+
+/// The hue of `color` without its brightness, or white for black.
+fn luminance_normalised<T: Float>(color: Vec3<T>) -> Vec3<T> {
+    let luminance = T::from_f64(0.2126) * color.x
+        + T::from_f64(0.7152) * color.y
+        + T::from_f64(0.0722) * color.z;
+    if luminance > T::from_f64(0.0) {
+        color / luminance
+    } else {
+        Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// The average weight of samples leaving `wo`, i.e. the fraction of light reflected or
+    /// transmitted, per channel.
+    fn albedo(material: &Principled<f64>, wo: Vec3<f64>) -> Vec3<f64> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut random = || rng.gen::<f64>();
+        let samples = 20_000;
+        let mut total = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            if let Some((_, weight)) = material.sample(wo, true, &mut random) {
+                total = total + weight;
+            }
+        }
+        total / samples as f64
+    }
+
+    #[test]
+    fn test_white_materials_conserve_energy() {
+        let wo = Vec3::new(0.3, 0.1, 0.8).unit();
+        let white = Vec3::new(1.0, 1.0, 1.0);
+        let materials = [
+            Principled {
+                base_color: white,
+                ..Principled::default()
+            },
+            Principled {
+                base_color: white,
+                metallic: 1.0,
+                roughness: 0.2,
+                ..Principled::default()
+            },
+            Principled {
+                base_color: white,
+                clearcoat: 1.0,
+                sheen: 1.0,
+                specular: 1.0,
+                ..Principled::default()
+            },
+            Principled {
+                base_color: white,
+                transmission: 1.0,
+                roughness: 0.1,
+                ..Principled::default()
+            },
+        ];
+        for material in materials.iter() {
+            let albedo = albedo(material, wo);
+            assert!(
+                albedo.x <= 1.02 && albedo.x > 0.8,
+                "{}: {}",
+                material,
+                albedo
+            );
+        }
+    }
+
+    #[test]
+    fn test_transmission_passes_through() {
+        let glass = Principled {
+            transmission: 1.0,
+            roughness: 0.0,
+            ..Principled::default()
+        };
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut random = || rng.gen::<f64>();
+        let wo = Vec3::new(0.0, 0.0, 1.0);
+        let transmitted = (0..1000)
+            .filter_map(|_| glass.sample(wo, true, &mut random))
+            .filter(|(wi, _)| wi.z < 0.0)
+            .count();
+        assert!(transmitted > 900, "{}", transmitted);
+    }
+
+    #[test]
+    fn test_display() {
+        let material = Principled {
+            clearcoat: 1.0,
+            ..Principled::default()
+        };
+        assert_eq!(
+            material.to_string(),
+            "Principled base (0.80, 0.80, 0.80), metallic 0.00, roughness 0.50, clearcoat 1.00"
+        );
+    }
+}
@@ -0,0 +1,413 @@
+use rand::Rng;
+
+use hittable::{Hit, Hittable};
+use material::Material;
+use random;
+use ray::Ray;
+use vec3::aabb::Aabb;
+use vec3::{Float, Vec3};
+
+/// The Henyey-Greenstein phase function: how light scattering off particles in a medium is
+/// spread around its direction of travel. `g` runs from -1 (all back towards where the light
+/// came from) through 0 (evenly in all directions) to 1 (straight on).
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct HenyeyGreenstein<T> {
+    pub g: T,
+}
+
+impl<T: Float> HenyeyGreenstein<T> {
+    /// The density of scattering through an angle with cosine `cos_theta`, per steradian.
+    pub fn p(&self, cos_theta: T) -> T {
+        let one = T::from_f64(1.0);
+        let denominator = one + self.g * self.g - T::from_f64(2.0) * self.g * cos_theta;
+        (one - self.g * self.g) / (T::from_f64(4.0) * T::PI * denominator * denominator.sqrt())
+    }
+
+    /// Samples a new direction for light travelling along `direction` (a unit vector) from two
+    /// uniform random numbers, exactly in proportion to `p`.
+    pub fn sample(&self, direction: Vec3<T>, u1: T, u2: T) -> Vec3<T> {
+        let one = T::from_f64(1.0);
+        let two = T::from_f64(2.0);
+        let cos_theta = if self.g.abs() < T::from_f64(1e-3) {
+            one - two * u1
+        } else {
+            let square = (one - self.g * self.g) / (one - self.g + two * self.g * u1);
+            ((one + self.g * self.g - square * square) / (two * self.g))
+                .max(-one)
+                .min(one)
+        };
+        let sin_theta = (one - cos_theta * cos_theta).max(T::from_f64(0.0)).sqrt();
+        let phi = two * T::PI * u2;
+        let (tangent, bitangent) = direction.orthonormal_basis();
+        tangent * (sin_theta * phi.cos())
+            + bitangent * (sin_theta * phi.sin())
+            + direction * cos_theta
+    }
+}
+
+/// Densities on a regular grid over `bounds`, interpolated trilinearly between the samples and
+/// zero outside. `densities` run x fastest, then y, then z.
+pub struct Grid<T> {
+    pub bounds: Aabb<T>,
+    pub size: [usize; 3],
+    pub densities: Vec<T>,
+    max_density: T,
+}
+
+impl<T: Float> Grid<T> {
+    /// `None` unless there are `size[0] * size[1] * size[2]` densities, at least two a side.
+    pub fn new(bounds: Aabb<T>, size: [usize; 3], densities: Vec<T>) -> Option<Grid<T>> {
+        if size.iter().any(|&n| n < 2) || densities.len() != size[0] * size[1] * size[2] {
+            return None;
+        }
+        let max_density = densities
+            .iter()
+            .fold(T::from_f64(0.0), |max, &density| max.max(density));
+        Some(Grid {
+            bounds,
+            size,
+            densities,
+            max_density,
+        })
+    }
+
+    pub fn max_density(&self) -> T {
+        self.max_density
+    }
+
+    pub fn density(&self, p: Vec3<T>) -> T {
+        let zero = T::from_f64(0.0);
+        let extent = self.bounds.max - self.bounds.min;
+        let relative = p - self.bounds.min;
+        let coordinates = [
+            relative.x / extent.x,
+            relative.y / extent.y,
+            relative.z / extent.z,
+        ];
+        if coordinates
+            .iter()
+            .any(|&c| !(c >= zero && c <= T::from_f64(1.0)))
+        {
+            return zero;
+        }
+        // The lower corner of the cell p is in, and how far across the cell it is.
+        let mut corner = [0; 3];
+        let mut fraction = [zero; 3];
+        for axis in 0..3 {
+            let position = coordinates[axis] * T::from_f64((self.size[axis] - 1) as f64);
+            let cell = (position.floor().to_f64() as usize).min(self.size[axis] - 2);
+            corner[axis] = cell;
+            fraction[axis] = position - T::from_f64(cell as f64);
+        }
+        let mut density = zero;
+        for offset in 0..8 {
+            let mut weight = T::from_f64(1.0);
+            let mut index = 0;
+            let mut stride = 1;
+            for axis in 0..3 {
+                let step = (offset >> axis) & 1;
+                weight = weight
+                    * if step == 1 {
+                        fraction[axis]
+                    } else {
+                        T::from_f64(1.0) - fraction[axis]
+                    };
+                index += (corner[axis] + step) * stride;
+                stride *= self.size[axis];
+            }
+            density = density + weight * self.densities[index];
+        }
+        density
+    }
+}
+
+/// A participating medium, such as smoke, fog or the inside of a cloud, that light can scatter
+/// off or be absorbed by anywhere along its way rather than only at surfaces.
+///
+/// The medium fills `boundary`, which must be convex, or all of space if there is none. Its
+/// extinction coefficient (the chance per unit distance that light interacts with it) is
+/// `density`, scaled by the `grid` if there is one. Of the light that interacts, `albedo` is
+/// scattered according to `phase` and the rest absorbed.
+pub struct Volume<T> {
+    pub boundary: Option<Box<dyn Hittable<T>>>,
+    pub density: T,
+    pub grid: Option<Grid<T>>,
+    pub albedo: Vec3<T>,
+    pub phase: HenyeyGreenstein<T>,
+}
+
+impl<T: Float> Volume<T> {
+    /// A medium of the same density throughout.
+    pub fn homogeneous(
+        boundary: Option<Box<dyn Hittable<T>>>,
+        density: T,
+        albedo: Vec3<T>,
+        anisotropy: T,
+    ) -> Volume<T> {
+        Volume {
+            boundary,
+            density,
+            grid: None,
+            albedo,
+            phase: HenyeyGreenstein { g: anisotropy },
+        }
+    }
+
+    fn extinction(&self, p: Vec3<T>) -> T {
+        match self.grid {
+            Some(ref grid) => self.density * grid.density(p),
+            None => self.density,
+        }
+    }
+
+    /// An upper bound on the extinction anywhere in the medium.
+    fn majorant(&self) -> T {
+        match self.grid {
+            Some(ref grid) => self.density * grid.max_density(),
+            None => self.density,
+        }
+    }
+
+    /// The part of `min_t..max_t` for which `ray` is inside the medium.
+    fn interval(&self, ray: Ray<T>, min_t: T, max_t: T) -> Option<(T, T)> {
+        let (mut enter, mut exit) = match self.boundary {
+            Some(ref boundary) => {
+                // The boundary may be entered behind the ray's origin, when it starts inside.
+                let enter = boundary.hit(ray, -T::MAX, T::MAX)?.t;
+                let exit = boundary.hit(ray, enter, T::MAX)?.t;
+                (enter, exit)
+            }
+            None => (min_t, max_t),
+        };
+        if let Some(ref grid) = self.grid {
+            let (grid_enter, grid_exit) = grid.bounds.hit(&ray, enter, exit)?;
+            enter = grid_enter;
+            exit = grid_exit;
+        }
+        let (enter, exit) = (enter.max(min_t), exit.min(max_t));
+        if enter < exit {
+            Some((enter, exit))
+        } else {
+            None
+        }
+    }
+
+    /// Samples a distance to the next step of delta (Woodcock) tracking: a free flight through
+    /// a medium as dense everywhere as the majorant, in units of `t`.
+    fn free_flight(&self, majorant: T, ray_length: T, rng: &mut impl Rng) -> T {
+        -(T::from_f64(1.0) - T::from_f64(rng.gen())).ln() / (majorant * ray_length)
+    }
+}
+
+impl<T: Float> Hittable<T> for Volume<T> {
+    /// Finds where light travelling along `ray` first interacts with the medium, if it does
+    /// before `max_t`, by delta tracking. The hit scatters with the phase function, weighted
+    /// by the albedo.
+    fn hit(&self, ray: Ray<T>, min_t: T, max_t: T) -> Option<Hit<T>> {
+        let (enter, exit) = self.interval(ray, min_t, max_t)?;
+        let majorant = self.majorant();
+        if majorant <= T::from_f64(0.0) {
+            return None;
+        }
+        let ray_length = ray.direction().length();
+        let mut rng = random::thread_rng();
+        let mut t = enter;
+        loop {
+            t = t + self.free_flight(majorant, ray_length, &mut rng);
+            if t >= exit {
+                return None;
+            }
+            let p = ray.point_at_parameter(t);
+            // A real collision with probability extinction / majorant, otherwise a null one
+            // that carries on unchanged.
+            if T::from_f64(rng.gen()) * majorant < self.extinction(p) {
+                return Some(Hit::new(
+                    t,
+                    Ray::new(p, -ray.direction().unit()),
+                    Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)),
+                    Material::Volume {
+                        albedo: self.albedo,
+                        phase: self.phase,
+                    },
+                ));
+            }
+        }
+    }
+
+    /// The fraction of light that crosses the medium along `ray` between `min_t` and `max_t`,
+    /// estimated without bias by ratio tracking: steps are taken as in delta tracking, but
+    /// instead of stopping at a real collision the estimate is multiplied by the chance that
+    /// each step was a null one.
+    fn transmittance(&self, ray: Ray<T>, min_t: T, max_t: T) -> T {
+        let one = T::from_f64(1.0);
+        let (enter, exit) = match self.interval(ray, min_t, max_t) {
+            Some(interval) => interval,
+            None => return one,
+        };
+        let ray_length = ray.direction().length();
+        if self.grid.is_none() {
+            return (-self.density * (exit - enter) * ray_length).exp();
+        }
+        let majorant = self.majorant();
+        if majorant <= T::from_f64(0.0) {
+            return one;
+        }
+        let mut rng = random::thread_rng();
+        let mut transmittance = one;
+        let mut t = enter;
+        loop {
+            t = t + self.free_flight(majorant, ray_length, &mut rng);
+            if t >= exit {
+                return transmittance;
+            }
+            transmittance =
+                transmittance * (one - self.extinction(ray.point_at_parameter(t)) / majorant);
+        }
+    }
+
+    /// The boundary's bounds, or failing that the grid's, outside which there is no medium.
+    fn bounds(&self) -> Option<Aabb<T>> {
+        match self.boundary {
+            Some(ref boundary) => boundary.bounds(),
+            None => self.grid.as_ref().map(|grid| grid.bounds),
+        }
+    }
+
+    #[cfg(any(feature = "window", test))]
+    fn describe(&self) -> String {
+        let medium = match self.grid {
+            Some(_) => "Heterogeneous medium",
+            None => "Homogeneous medium",
+        };
+        let extent = match self.boundary {
+            Some(ref boundary) => boundary.describe(),
+            None => "everywhere".to_string(),
+        };
+        format!(
+            "{} of density {:.2}, albedo {:.2}, in {}",
+            medium, self.density, self.albedo, extent
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hittable::Sphere;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn smoke(grid: Option<Grid<f64>>) -> Volume<f64> {
+        Volume {
+            grid,
+            ..Volume::homogeneous(
+                Some(Box::new(Sphere::new(
+                    Vec3::new(0.0, 0.0, 0.0),
+                    1.0,
+                    Material::Normal {},
+                ))),
+                0.5,
+                Vec3::new(0.8, 0.8, 0.8),
+                0.0,
+            )
+        }
+    }
+
+    /// A grid over the unit sphere's bounding box, densest in the middle.
+    fn grid() -> Grid<f64> {
+        let densities = (0..27)
+            .map(|i| if i == 13 { 4.0 } else { (i % 3) as f64 * 0.5 })
+            .collect();
+        Grid::new(
+            Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)),
+            [3, 3, 3],
+            densities,
+        )
+        .unwrap()
+    }
+
+    /// The fraction of rays along `ray` that pass through the volume without a collision.
+    fn fraction_passing(volume: &Volume<f64>, ray: Ray<f64>) -> f64 {
+        let rays = 100_000;
+        let passed = (0..rays)
+            .filter(|_| volume.hit(ray, 0.0, f64::MAX).is_none())
+            .count();
+        passed as f64 / rays as f64
+    }
+
+    #[test]
+    fn test_homogeneous_transmittance_is_beer_lambert() {
+        let volume = smoke(None);
+        // Through the middle, 2 units of medium at density 0.5.
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 2.0));
+        let expected = (-1.0f64).exp();
+        assert!((volume.transmittance(ray, 0.0, f64::MAX) - expected).abs() < 1e-12);
+        assert!((fraction_passing(&volume, ray) - expected).abs() < 0.01);
+        // Starting inside, only the distance to the boundary counts.
+        let inside = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!((fraction_passing(&volume, inside) - (-0.5f64).exp()).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_delta_and_ratio_tracking_agree() {
+        let volume = smoke(Some(grid()));
+        let ray = Ray::new(Vec3::new(-5.0, 0.1, -0.3), Vec3::new(1.0, 0.05, 0.1));
+        let ratio_tracked = (0..20_000)
+            .map(|_| volume.transmittance(ray, 0.0, f64::MAX))
+            .sum::<f64>()
+            / 20_000.0;
+        let delta_tracked = fraction_passing(&volume, ray);
+        assert!(
+            ratio_tracked > 0.05 && ratio_tracked < 0.95,
+            "{}",
+            ratio_tracked
+        );
+        assert!(
+            (ratio_tracked - delta_tracked).abs() < 0.015,
+            "{} {}",
+            ratio_tracked,
+            delta_tracked
+        );
+    }
+
+    #[test]
+    fn test_grid_interpolates() {
+        let grid = grid();
+        assert_eq!(grid.max_density(), 4.0);
+        assert_eq!(grid.density(Vec3::new(0.0, 0.0, 0.0)), 4.0);
+        assert_eq!(grid.density(Vec3::new(-1.0, -1.0, -1.0)), 0.0);
+        assert_eq!(grid.density(Vec3::new(1.0, -1.0, -1.0)), 1.0);
+        assert_eq!(grid.density(Vec3::new(0.5, -1.0, -1.0)), 0.75);
+        assert_eq!(grid.density(Vec3::new(2.0, 0.0, 0.0)), 0.0);
+        assert!(Grid::new(grid.bounds, [3, 3, 2], vec![0.0; 27]).is_none());
+    }
+
+    #[test]
+    fn test_henyey_greenstein_mean_cosine_is_g() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let direction = Vec3::new(0.0, 0.6, 0.8);
+        for &g in &[-0.7, 0.0, 0.3, 0.9] {
+            let phase = HenyeyGreenstein { g };
+            let samples = 20_000;
+            let mean_cosine = (0..samples)
+                .map(|_| {
+                    let wi = phase.sample(direction, rng.gen(), rng.gen());
+                    assert!((wi.length() - 1.0).abs() < 1e-9);
+                    wi.dot(direction)
+                })
+                .sum::<f64>()
+                / samples as f64;
+            assert!((mean_cosine - g).abs() < 0.02, "{} {}", g, mean_cosine);
+        }
+        // The phase function integrates to one over the sphere.
+        let phase = HenyeyGreenstein { g: 0.5 };
+        let steps = 10_000;
+        let integral = (0..steps)
+            .map(|i| {
+                let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / steps as f64;
+                phase.p(cos_theta) * 2.0 * std::f64::consts::PI * 2.0 / steps as f64
+            })
+            .sum::<f64>();
+        assert!((integral - 1.0).abs() < 1e-3);
+    }
+}
@@ -1,18 +1,35 @@
-use vec3::{NumWithVectorOps, Vec3, VectorWithOps};
+use vec3::{Float, NumWithVectorOps, Vec3, VectorWithOps};
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Ray<T> {
     pub origin: Vec3<T>,
     pub direction: Vec3<T>,
+    /// When the ray was sent, within the camera's shutter interval. Moving objects are traced
+    /// where they were at that moment.
+    pub time: T,
 }
 
 impl<T> Ray<T>
 where
-    T: NumWithVectorOps,
+    T: NumWithVectorOps + Default,
     Vec3<T>: VectorWithOps<T>,
 {
+    /// A ray at time zero.
     pub fn new(origin: Vec3<T>, direction: Vec3<T>) -> Ray<T> {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: T::default(),
+        }
+    }
+
+    pub fn with_time(self, time: T) -> Ray<T> {
+        Ray { time, ..self }
+    }
+
+    #[inline]
+    pub fn time(&self) -> T {
+        self.time
     }
 
     #[inline]
@@ -32,6 +49,53 @@ where
     }
 }
 
+impl<T: Float> Ray<T> {
+    /// A ray leaving the surface point `p`, which is known to within `error` on each axis, in
+    /// `direction`. The origin is pushed along the geometric normal `normal` just far enough to
+    /// be on the correct side of the surface, so the ray cannot hit the surface it is leaving
+    /// however big or small the scene is.
+    pub fn spawn(p: Vec3<T>, error: Vec3<T>, normal: Vec3<T>, direction: Vec3<T>) -> Ray<T> {
+        Ray::new(offset_ray_origin(p, error, normal, direction), direction)
+    }
+}
+
+/// Bounds the relative error of `n` successive floating point operations; see Physically Based
+/// Rendering (3rd edition), section 3.9.
+pub fn gamma<T: Float>(n: i32) -> T {
+    let bound = T::EPSILON * T::from_f64(0.5 * f64::from(n));
+    bound / (T::from_f64(1.0) - bound)
+}
+
+fn offset_ray_origin<T: Float>(
+    p: Vec3<T>,
+    error: Vec3<T>,
+    normal: Vec3<T>,
+    direction: Vec3<T>,
+) -> Vec3<T> {
+    let distance = normal.map(Float::abs).dot(error);
+    let offset = if direction.dot(normal) < T::from_f64(0.0) {
+        -(normal * distance)
+    } else {
+        normal * distance
+    };
+    let origin = p + offset;
+    // Round away from p so rounding in the addition above cannot undo the offset.
+    let round_away = |value: T, offset: T| {
+        if offset > T::from_f64(0.0) {
+            value.next_up()
+        } else if offset < T::from_f64(0.0) {
+            value.next_down()
+        } else {
+            value
+        }
+    };
+    Vec3::new(
+        round_away(origin.x, offset.x),
+        round_away(origin.y, offset.y),
+        round_away(origin.z, offset.z),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,134 @@
+/// A piecewise constant probability distribution over 0..1, in proportion to `function`'s
+/// values on equal width intervals; see Physically Based Rendering (3rd edition), section
+/// 13.3.1.
+pub struct Distribution1D {
+    function: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(function: Vec<f64>) -> Distribution1D {
+        let n = function.len() as f64;
+        let mut cdf = vec![0.0; function.len() + 1];
+        for i in 0..function.len() {
+            cdf[i + 1] = cdf[i] + function[i].abs() / n;
+        }
+        let integral = cdf[function.len()];
+        for (i, value) in cdf.iter_mut().enumerate() {
+            // An all zero function is sampled uniformly.
+            *value = if integral > 0.0 {
+                *value / integral
+            } else {
+                i as f64 / n
+            };
+        }
+        Distribution1D {
+            function,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    /// Maps a uniform random number to a sample, returning it with its density and the index
+    /// of the interval it is in.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // The last interval whose cdf starts at or below u.
+        let index =
+            (self.cdf.partition_point(|&value| value <= u).max(1) - 1).min(self.function.len() - 1);
+        let width = self.cdf[index + 1] - self.cdf[index];
+        let offset = if width > 0.0 {
+            (u - self.cdf[index]) / width
+        } else {
+            0.0
+        };
+        let x = (index as f64 + offset) / self.function.len() as f64;
+        (x.min(1.0 - f64::EPSILON), self.pdf_at(index), index)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let index = ((x * self.function.len() as f64) as usize).min(self.function.len() - 1);
+        self.pdf_at(index)
+    }
+
+    fn pdf_at(&self, index: usize) -> f64 {
+        if self.integral > 0.0 {
+            self.function[index].abs() / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A piecewise constant distribution over the unit square, for sampling images: rows are
+/// picked by their total, then a column within the row.
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `function` has `width` values per row, rows running down v.
+    pub fn new(function: &[f64], width: usize) -> Distribution2D {
+        let rows: Vec<Distribution1D> = function
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(Distribution1D::integral).collect());
+        Distribution2D { rows, marginal }
+    }
+
+    /// Maps two uniform random numbers to a point `(u, v)` and its density.
+    pub fn sample(&self, u1: f64, u2: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u2);
+        let (u, pdf_u, _) = self.rows[row].sample(u1);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.rows.len() as f64) as usize).min(self.rows.len() - 1);
+        self.rows[row].pdf(u) * self.marginal.pdf(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distribution_1d() {
+        let distribution = Distribution1D::new(vec![1.0, 3.0, 0.0, 4.0]);
+        assert_eq!(distribution.integral(), 2.0);
+        assert_eq!(distribution.sample(0.0), (0.0, 0.5, 0));
+        // A quarter of the way through the second interval's share of the cdf.
+        let (x, pdf, index) = distribution.sample(0.125 + 0.375 / 4.0);
+        assert!((x - 0.3125).abs() < 1e-12);
+        assert_eq!((pdf, index), (1.5, 1));
+        // The empty interval is never picked.
+        assert_eq!(distribution.sample(0.5).2, 3);
+        assert_eq!(distribution.pdf(0.6), 0.0);
+        assert_eq!(distribution.pdf(0.99), 2.0);
+        assert!(distribution.sample(1.0).0 < 1.0);
+    }
+
+    #[test]
+    fn test_all_zero_is_uniform() {
+        let distribution = Distribution1D::new(vec![0.0; 4]);
+        assert_eq!(distribution.sample(0.6), (0.6, 1.0, 2));
+    }
+
+    #[test]
+    fn test_distribution_2d() {
+        // Only the bottom right cell has any weight.
+        let distribution = Distribution2D::new(&[0.0, 0.0, 0.0, 5.0], 2);
+        let ((u, v), pdf) = distribution.sample(0.3, 0.7);
+        assert!(u >= 0.5 && v >= 0.5);
+        assert_eq!(pdf, 4.0);
+        assert_eq!(distribution.pdf(0.75, 0.75), 4.0);
+        assert_eq!(distribution.pdf(0.25, 0.75), 0.0);
+    }
+}
@@ -0,0 +1,1457 @@
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use animation::{Interpolate, Interpolation, Track};
+use background::environment_map::EnvironmentMap;
+use background::sky::Sky;
+use background::Background;
+use camera::aperture::{Aperture, ApertureImage};
+use camera::{CubeMap, Equirectangular, Fisheye, Orthographic, Perspective, Projection, Shutter};
+use hittable::{Hittable, Sphere, World};
+use light::{Light, Lighting};
+use material::dispersion::{Dispersion, Glass, D_LINE};
+use material::microfacet::Metal;
+use material::principled::Principled;
+use material::{absorption_for_color, Material};
+use medium::{Grid, Volume};
+use transform::{Pose, Transform};
+use vec3::aabb::Aabb;
+use vec3::quaternion::Quaternion;
+use vec3::Vec3;
+
+use self::json::Json;
+
+pub mod json;
+
+/// Everything needed to render an image, as described by a JSON scene file. See
+/// `scenes/principled.json` for an example.
+pub struct Scene {
+    pub width: usize,
+    pub height: usize,
+    pub samples: i32,
+    /// Whether to render in spectral rather than RGB mode.
+    pub spectral: bool,
+    pub camera: Projection<f64>,
+    pub shutter: Shutter,
+    pub world: World<f64>,
+    pub lighting: Lighting<f64>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SceneError {
+    pub message: String,
+}
+
+impl SceneError {
+    fn new(message: String) -> SceneError {
+        SceneError { message }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<json::ParseError> for SceneError {
+    fn from(error: json::ParseError) -> SceneError {
+        SceneError::new(error.to_string())
+    }
+}
+
+pub const DEFAULT_WIDTH: usize = 1920;
+pub const DEFAULT_HEIGHT: usize = 1080;
+pub const DEFAULT_SAMPLES: i32 = 1024;
+
+/// The camera used when a scene does not say otherwise.
+pub fn default_camera(aspect: f64) -> Projection<f64> {
+    Projection::Perspective(default_perspective(aspect))
+}
+
+fn default_perspective(aspect: f64) -> Perspective<f64> {
+    Perspective::new(
+        Vec3::new(7.5, 1.5, -2.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        60.0,
+        aspect,
+        0.05,
+        4.00,
+    )
+}
+
+/// Loads the scene as it is at `frame` of its animation.
+pub fn load(path: &str, frame: f64) -> Result<Scene, SceneError> {
+    let text = fs::read_to_string(path)
+        .map_err(|error| SceneError::new(format!("Could not read {}: {}", path, error)))?;
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    parse(&text, directory, frame).map_err(|error| SceneError::new(format!("{}: {}", path, error)))
+}
+
+/// Parses a scene whose files (e.g. environment maps) are relative to `directory`, as it is
+/// at `frame`. Keyframed parameters take their values at that frame, and transforms move from
+/// where they are at its start to where they are at the next, so each frame is blurred by the
+/// motion during it while the shutter is open.
+pub fn parse(text: &str, directory: &Path, frame: f64) -> Result<Scene, SceneError> {
+    parse_with_files(
+        text,
+        Files {
+            directory,
+            confined: false,
+        },
+        frame,
+    )
+}
+
+/// Parses a scene as `parse` does, but refuses files outside `directory`: the scene may only
+/// name them by relative paths without `..`. For scenes from people who should not be able to
+/// read any other file.
+pub fn parse_confined(text: &str, directory: &Path, frame: f64) -> Result<Scene, SceneError> {
+    parse_with_files(
+        text,
+        Files {
+            directory,
+            confined: true,
+        },
+        frame,
+    )
+}
+
+/// Where the files a scene refers to are found.
+#[derive(Debug, Copy, Clone)]
+struct Files<'a> {
+    directory: &'a Path,
+    /// Whether the files must be inside `directory`.
+    confined: bool,
+}
+
+impl<'a> Files<'a> {
+    /// The file named by the string at `key`.
+    fn get(&self, json: &Json, key: &str, path: &str) -> Result<PathBuf, SceneError> {
+        let name = Path::new(string(json, key, path)?);
+        let inside = name
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if self.confined && !inside {
+            return Err(SceneError::new(format!(
+                "{}.{}: expected a relative path that stays inside the scene directory",
+                path, key
+            )));
+        }
+        Ok(self.directory.join(name))
+    }
+}
+
+fn parse_with_files(text: &str, files: Files, frame: f64) -> Result<Scene, SceneError> {
+    let json = json::parse(text)?;
+    let width = count_or(&json, "width", DEFAULT_WIDTH, u32::MAX as usize)?;
+    let height = count_or(&json, "height", DEFAULT_HEIGHT, u32::MAX as usize)?;
+    let samples = count_or(
+        &json,
+        "samples",
+        DEFAULT_SAMPLES as usize,
+        i32::MAX as usize,
+    )? as i32;
+    let spectral = match json.get("spectral") {
+        None => false,
+        Some(Json::Bool(spectral)) => *spectral,
+        Some(_) => {
+            return Err(SceneError::new(
+                "scene.spectral: expected true or false".to_string(),
+            ))
+        }
+    };
+    let aspect = width as f64 / height as f64;
+    let camera = match json.get("camera") {
+        Some(camera) => parse_camera(camera, aspect, frame, files)?,
+        None => default_camera(aspect),
+    };
+    let shutter = match json.get("camera") {
+        Some(camera) => Shutter {
+            open: number_or(camera, "shutter_open", Shutter::default().open, "camera")?,
+            close: number_or(camera, "shutter_close", Shutter::default().close, "camera")?,
+        },
+        None => Shutter::default(),
+    };
+    let background = match json.get("background") {
+        Some(background) => parse_background(background, files)?,
+        None => Background::default(),
+    };
+    let lights = match json.get("lights") {
+        Some(lights) => lights
+            .as_array()
+            .ok_or_else(|| SceneError::new("lights: expected an array".to_string()))?
+            .iter()
+            .enumerate()
+            .map(|(i, light)| parse_light(light, &format!("lights[{}]", i)))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![],
+    };
+
+    let mut named_materials = vec![];
+    if let Some(materials) = json.get("materials") {
+        let members = materials
+            .as_object()
+            .ok_or_else(|| SceneError::new("materials: expected an object".to_string()))?;
+        for (name, material) in members {
+            let path = format!("materials.{}", name);
+            named_materials.push((name.clone(), parse_material(material, &[], &path)?));
+        }
+    }
+
+    let objects = json
+        .get("objects")
+        .and_then(Json::as_array)
+        .ok_or_else(|| SceneError::new("objects: expected an array".to_string()))?;
+    let objects = objects
+        .iter()
+        .enumerate()
+        .map(|(i, object)| {
+            parse_object(object, &named_materials, &format!("objects[{}]", i), frame)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Scene {
+        width,
+        height,
+        samples,
+        spectral,
+        camera,
+        shutter,
+        world: World::new(objects),
+        lighting: Lighting { background, lights },
+    })
+}
+
+/// Every camera is placed by `look_from`, `look_at` and `up`; its `type` (perspective unless
+/// given) says how it projects the scene onto the image. Any of its numbers and vectors may be
+/// animated by `keyframes`.
+fn parse_camera(
+    json: &Json,
+    aspect: f64,
+    frame: f64,
+    files: Files,
+) -> Result<Projection<f64>, SceneError> {
+    let default = default_perspective(aspect);
+    let path = "camera";
+    let number_at = |key: &str, default: f64| {
+        let still = number_or(json, key, default, path)?;
+        keyframed(json, key, still, frame, path, &|keyframe, keyframe_path| {
+            number(keyframe, key, keyframe_path)
+        })
+    };
+    let vector_at = |key: &str, default: Vec3<f64>| {
+        let still = vector_or(json, key, default, path)?;
+        keyframed(json, key, still, frame, path, &|keyframe, keyframe_path| {
+            vector(keyframe, key, keyframe_path)
+        })
+    };
+    let look_from = vector_at("look_from", default.location())?;
+    let look_at = vector_at("look_at", Vec3::new(0.0, 1.0, 0.0))?;
+    let up = vector_at("up", Vec3::new(0.0, 1.0, 0.0))?;
+    let kind = match json.get("type") {
+        Some(_) => string(json, "type", path)?,
+        None => "perspective",
+    };
+    match kind {
+        "perspective" => Ok(Projection::Perspective(
+            Perspective::new(
+                look_from,
+                look_at,
+                up,
+                number_at("vfov", 60.0)?,
+                aspect,
+                number_at("aperture", 0.05)?,
+                number_at("focus_distance", default.focus_distance())?,
+            )
+            .with_aperture(parse_aperture(json, files)?)
+            .with_optical_vignetting(number_at("optical_vignetting", 0.0)?)
+            .with_tilt(number_at("tilt", 0.0)?, number_at("swing", 0.0)?)
+            .with_shift(number_at("shift_x", 0.0)?, number_at("shift_y", 0.0)?),
+        )),
+        "orthographic" => {
+            // There is no sensible default, so a still height is needed even if keyframed.
+            number(json, "height", path)?;
+            Ok(Projection::Orthographic(Orthographic::new(
+                look_from,
+                look_at,
+                up,
+                number_at("height", 0.0)?,
+                aspect,
+            )))
+        }
+        "fisheye" => Ok(Projection::Fisheye(Fisheye::new(
+            look_from,
+            look_at,
+            up,
+            number_at("fov", 180.0)?,
+            aspect,
+        ))),
+        "panorama" => Ok(Projection::Equirectangular(Equirectangular::new(
+            look_from, look_at, up,
+        ))),
+        "cube_map" => Ok(Projection::CubeMap(CubeMap::new(look_from, look_at, up))),
+        other => Err(SceneError::new(format!(
+            "{}.type: unknown camera type '{}'",
+            path, other
+        ))),
+    }
+}
+
+/// The shape of a perspective camera's lens opening: a polygon of `aperture_blades` sides
+/// turned `aperture_rotation` degrees, an `aperture_image` relative to the scene file, or a
+/// circle.
+fn parse_aperture(json: &Json, files: Files) -> Result<Aperture, SceneError> {
+    let path = "camera";
+    match (json.get("aperture_blades"), json.get("aperture_image")) {
+        (Some(_), Some(_)) => Err(SceneError::new(format!(
+            "{}: give either aperture_blades or aperture_image",
+            path
+        ))),
+        (Some(_), None) => {
+            let blades = number(json, "aperture_blades", path)?;
+            if blades < 3.0 || blades.fract() != 0.0 {
+                return Err(SceneError::new(format!(
+                    "{}.aperture_blades: expected a whole number from 3 up",
+                    path
+                )));
+            }
+            Ok(Aperture::Polygon {
+                blades: blades as u32,
+                rotation: number_or(json, "aperture_rotation", 0.0, path)?,
+            })
+        }
+        (None, Some(_)) => {
+            let file = files.get(json, "aperture_image", path)?;
+            ApertureImage::load(&file)
+                .map(|image| Aperture::Image(Arc::new(image)))
+                .map_err(|error| {
+                    SceneError::new(format!(
+                        "{}.aperture_image: {}: {}",
+                        path,
+                        file.display(),
+                        error
+                    ))
+                })
+        }
+        (None, None) => Ok(Aperture::Circle),
+    }
+}
+
+fn parse_background(json: &Json, files: Files) -> Result<Background<f64>, SceneError> {
+    let path = "background";
+    match string(json, "type", path)? {
+        "solid" => Ok(Background::Solid(vector(json, "color", path)?)),
+        "gradient" => Ok(Background::Gradient {
+            top: vector(json, "top", path)?,
+            bottom: vector(json, "bottom", path)?,
+        }),
+        "map" => {
+            let file = files.get(json, "path", path)?;
+            EnvironmentMap::load(
+                &file,
+                number_or(json, "rotation", 0.0, path)?,
+                number_or(json, "intensity", 1.0, path)?,
+            )
+            .map(Background::Map)
+            .map_err(|error| {
+                SceneError::new(format!("{}.path: {}: {}", path, file.display(), error))
+            })
+        }
+        "sky" => Ok(Background::Sky(Sky::new(
+            vector(json, "sun_direction", path)?,
+            number_or(json, "turbidity", 3.0, path)?,
+            number_or(json, "intensity", 1.0, path)?,
+        ))),
+        other => Err(SceneError::new(format!(
+            "{}.type: unknown background type '{}'",
+            path, other
+        ))),
+    }
+}
+
+fn parse_light(json: &Json, path: &str) -> Result<Light<f64>, SceneError> {
+    match string(json, "type", path)? {
+        "point" => Ok(Light::Point {
+            position: vector(json, "position", path)?,
+            intensity: vector(json, "intensity", path)?,
+        }),
+        "spot" => Ok(Light::Spot {
+            position: vector(json, "position", path)?,
+            direction: vector(json, "direction", path)?,
+            intensity: vector(json, "intensity", path)?,
+            cone_angle: number_or(json, "cone_angle", 30.0, path)?,
+            falloff_angle: number_or(json, "falloff_angle", 5.0, path)?,
+        }),
+        // The sun is about half a degree across.
+        "directional" => Ok(Light::Directional {
+            direction: vector(json, "direction", path)?,
+            irradiance: vector(json, "irradiance", path)?,
+            angular_diameter: number_or(json, "angular_diameter", 0.53, path)?,
+        }),
+        "sphere" => Ok(Light::Sphere {
+            center: vector(json, "center", path)?,
+            radius: positive(json, "radius", path)?,
+            radiance: vector(json, "radiance", path)?,
+        }),
+        other => Err(SceneError::new(format!(
+            "{}.type: unknown light type '{}'",
+            path, other
+        ))),
+    }
+}
+
+fn parse_object(
+    json: &Json,
+    named_materials: &[(String, Material<f64>)],
+    path: &str,
+    frame: f64,
+) -> Result<Box<dyn Hittable<f64>>, SceneError> {
+    match string(json, "type", path)? {
+        "volume" => return parse_volume(json, path),
+        "transform" => return parse_transform(json, named_materials, path, frame),
+        _ => {}
+    }
+    let material = json
+        .get("material")
+        .ok_or_else(|| SceneError::new(format!("{}.material: missing", path)))?;
+    let material = parse_material(material, named_materials, &format!("{}.material", path))?;
+    parse_shape(json, material, path)
+}
+
+fn parse_shape(
+    json: &Json,
+    material: Material<f64>,
+    path: &str,
+) -> Result<Box<dyn Hittable<f64>>, SceneError> {
+    match string(json, "type", path)? {
+        "sphere" => {
+            let center = vector(json, "center", path)?;
+            Ok(Box::new(Sphere::moving(
+                center,
+                vector_or(json, "end_center", center, path)?,
+                positive(json, "radius", path)?,
+                material,
+            )))
+        }
+        other => Err(SceneError::new(format!(
+            "{}.type: unknown object type '{}'",
+            path, other
+        ))),
+    }
+}
+
+/// An `object` placed by a `start` pose, moving to an `end` pose if it has one. Alternatively
+/// its pose is animated by `keyframes`, and within a frame it moves from its pose at that
+/// frame to its pose at the next.
+fn parse_transform(
+    json: &Json,
+    named_materials: &[(String, Material<f64>)],
+    path: &str,
+    frame: f64,
+) -> Result<Box<dyn Hittable<f64>>, SceneError> {
+    let object = json
+        .get("object")
+        .ok_or_else(|| SceneError::new(format!("{}.object: missing", path)))?;
+    let object = parse_object(object, named_materials, &format!("{}.object", path), frame)?;
+    let (start, end) = if json.get("keyframes").is_some() {
+        if json.get("start").is_some() || json.get("end").is_some() {
+            return Err(SceneError::new(format!(
+                "{}: give either keyframes or a start and end",
+                path
+            )));
+        }
+        (
+            keyframed_pose(json, frame, path)?,
+            keyframed_pose(json, frame + 1.0, path)?,
+        )
+    } else {
+        let start = match json.get("start") {
+            Some(start) => parse_pose(start, &format!("{}.start", path))?,
+            None => Pose::identity(),
+        };
+        let end = match json.get("end") {
+            Some(end) => parse_pose(end, &format!("{}.end", path))?,
+            None => start,
+        };
+        (start, end)
+    };
+    match Transform::new(object, start, end) {
+        Some(transform) => Ok(Box::new(transform)),
+        None => Err(SceneError::new(format!(
+            "{}: a scale must not be zero",
+            path
+        ))),
+    }
+}
+
+/// A `translation`, a `rotation` and a `scale`, each the identity if not given.
+fn parse_pose(json: &Json, path: &str) -> Result<Pose<f64>, SceneError> {
+    let identity = Pose::identity();
+    Ok(Pose {
+        translation: vector_or(json, "translation", identity.translation, path)?,
+        rotation: match json.get("rotation") {
+            Some(_) => rotation(json, path)?,
+            None => identity.rotation,
+        },
+        scale: match json.get("scale") {
+            Some(_) => scale(json, path)?,
+            None => identity.scale,
+        },
+    })
+}
+
+/// The pose at `frame`, each of its parts interpolated between the keyframes that give it.
+fn keyframed_pose(json: &Json, frame: f64, path: &str) -> Result<Pose<f64>, SceneError> {
+    let identity = Pose::identity();
+    Ok(Pose {
+        translation: keyframed(
+            json,
+            "translation",
+            identity.translation,
+            frame,
+            path,
+            &|keyframe, keyframe_path| vector(keyframe, "translation", keyframe_path),
+        )?,
+        rotation: keyframed(json, "rotation", identity.rotation, frame, path, &rotation)?,
+        scale: keyframed(json, "scale", identity.scale, frame, path, &scale)?,
+    })
+}
+
+/// A `rotation` of `angle` degrees about an `axis`.
+fn rotation(json: &Json, path: &str) -> Result<Quaternion<f64>, SceneError> {
+    let path = format!("{}.rotation", path);
+    let rotation = json
+        .get("rotation")
+        .ok_or_else(|| SceneError::new(format!("{}: missing", path)))?;
+    Ok(Quaternion::from_axis_angle(
+        vector(rotation, "axis", &path)?,
+        number(rotation, "angle", &path)?.to_radians(),
+    ))
+}
+
+/// A `scale` that is either one number or one for each axis.
+fn scale(json: &Json, path: &str) -> Result<Vec3<f64>, SceneError> {
+    match json.get("scale").and_then(Json::as_f64) {
+        Some(scale) => Ok(Vec3::new(scale, scale, scale)),
+        None => vector(json, "scale", path),
+    }
+}
+
+/// The value of `key` at `frame`, interpolated between those of the object's `keyframes` that
+/// give it, each of which also has a `frame`. `still` is used if none do. Keyframes move in
+/// straight lines between each other unless the object's `interpolation` is `smooth`.
+fn keyframed<V: Interpolate>(
+    json: &Json,
+    key: &str,
+    still: V,
+    frame: f64,
+    path: &str,
+    read: &dyn Fn(&Json, &str) -> Result<V, SceneError>,
+) -> Result<V, SceneError> {
+    let keyframes = match json.get("keyframes") {
+        Some(keyframes) => keyframes
+            .as_array()
+            .ok_or_else(|| SceneError::new(format!("{}.keyframes: expected an array", path)))?,
+        None => return Ok(still),
+    };
+    let interpolation = match json.get("interpolation") {
+        None => Interpolation::Linear,
+        Some(_) => match string(json, "interpolation", path)? {
+            "linear" => Interpolation::Linear,
+            "smooth" => Interpolation::Smooth,
+            other => {
+                return Err(SceneError::new(format!(
+                    "{}.interpolation: unknown interpolation '{}'",
+                    path, other
+                )))
+            }
+        },
+    };
+    let mut keys = vec![];
+    for (i, keyframe) in keyframes.iter().enumerate() {
+        let keyframe_path = format!("{}.keyframes[{}]", path, i);
+        let key_frame = number(keyframe, "frame", &keyframe_path)?;
+        if keyframe.get(key).is_some() {
+            keys.push((key_frame, read(keyframe, &keyframe_path)?));
+        }
+    }
+    if keys.is_empty() {
+        return Ok(still);
+    }
+    Track::new(keys, interpolation)
+        .map(|track| track.at(frame))
+        .ok_or_else(|| {
+            SceneError::new(format!(
+                "{}.keyframes: more than one keyframe gives {} at the same frame",
+                path, key
+            ))
+        })
+}
+
+/// A participating medium filling the shape given as its `boundary`, or all of space (fog) if
+/// it has none. Its `density` is scaled by the optional `grid` of densities, which covers the
+/// box from `min` to `max` with `size` samples a side.
+fn parse_volume(json: &Json, path: &str) -> Result<Box<dyn Hittable<f64>>, SceneError> {
+    let boundary = match json.get("boundary") {
+        // Only the shape of the boundary matters.
+        Some(boundary) => Some(parse_shape(
+            boundary,
+            Material::Normal {},
+            &format!("{}.boundary", path),
+        )?),
+        None => None,
+    };
+    let grid = match json.get("grid") {
+        Some(grid) => Some(parse_grid(grid, &format!("{}.grid", path))?),
+        None => None,
+    };
+    Ok(Box::new(Volume {
+        grid,
+        ..Volume::homogeneous(
+            boundary,
+            number(json, "density", path)?,
+            vector_or(json, "albedo", Vec3::new(1.0, 1.0, 1.0), path)?,
+            number_or(json, "anisotropy", 0.0, path)?,
+        )
+    }))
+}
+
+fn parse_grid(json: &Json, path: &str) -> Result<Grid<f64>, SceneError> {
+    let size = vector(json, "size", path)?;
+    let densities: Vec<f64> = json
+        .get("densities")
+        .and_then(Json::as_array)
+        .and_then(|values| values.iter().map(Json::as_f64).collect())
+        .ok_or_else(|| {
+            SceneError::new(format!("{}.densities: expected an array of numbers", path))
+        })?;
+    Grid::new(
+        Aabb::new(vector(json, "min", path)?, vector(json, "max", path)?),
+        [size.x as usize, size.y as usize, size.z as usize],
+        densities,
+    )
+    .ok_or_else(|| {
+        SceneError::new(format!(
+            "{}: expected at least 2 samples a side and size.x * size.y * size.z densities",
+            path
+        ))
+    })
+}
+
+/// A material is either the name of one in the scene's `materials`, or an object with a
+/// `type` and that type's parameters.
+fn parse_material(
+    json: &Json,
+    named_materials: &[(String, Material<f64>)],
+    path: &str,
+) -> Result<Material<f64>, SceneError> {
+    if let Some(name) = json.as_str() {
+        return named_materials
+            .iter()
+            .find(|(material_name, _)| material_name == name)
+            .map(|(_, material)| *material)
+            .ok_or_else(|| SceneError::new(format!("{}: unknown material '{}'", path, name)));
+    }
+    match string(json, "type", path)? {
+        "normal" => Ok(Material::Normal {}),
+        "lambertian" => Ok(Material::Lambertian {
+            albedo: vector(json, "albedo", path)?,
+        }),
+        "metal" => Ok(Material::Metal {
+            albedo: vector(json, "albedo", path)?,
+            fuzz: number_or(json, "fuzz", 0.0, path)?,
+        }),
+        "dielectric" => parse_dielectric(json, path),
+        "conductor" => {
+            let roughness = number_or(json, "roughness", 0.0, path)?;
+            let anisotropy = number_or(json, "anisotropy", 0.0, path)?;
+            match json.get("metal") {
+                Some(metal) => {
+                    let name = metal.as_str().unwrap_or("");
+                    let metal = Metal::from_name(name).ok_or_else(|| {
+                        SceneError::new(format!("{}.metal: unknown metal '{}'", path, name))
+                    })?;
+                    Ok(Material::conductor(metal, roughness, anisotropy))
+                }
+                None => Ok(Material::Conductor {
+                    eta: vector(json, "eta", path)?,
+                    k: vector(json, "k", path)?,
+                    roughness,
+                    anisotropy,
+                }),
+            }
+        }
+        "rough_dielectric" => Ok(Material::RoughDielectric {
+            refractive_index: number_or(json, "refractive_index", 1.5, path)?,
+            roughness: number_or(json, "roughness", 0.0, path)?,
+            anisotropy: number_or(json, "anisotropy", 0.0, path)?,
+        }),
+        "principled" => parse_principled(json, path).map(Material::Principled),
+        "gltf" => parse_gltf(json, path).map(Material::Principled),
+        other => Err(SceneError::new(format!(
+            "{}.type: unknown material type '{}'",
+            path, other
+        ))),
+    }
+}
+
+/// Glass is clear unless given an `absorption` coefficient, or the `attenuation_color` it
+/// leaves after `attenuation_distance`. It disperses if it names a `glass` or has an
+/// `abbe_number`.
+fn parse_dielectric(json: &Json, path: &str) -> Result<Material<f64>, SceneError> {
+    let dispersion = match json.get("glass") {
+        Some(glass) => {
+            let name = glass.as_str().unwrap_or("");
+            let glass = Glass::from_name(name).ok_or_else(|| {
+                SceneError::new(format!("{}.glass: unknown glass '{}'", path, name))
+            })?;
+            Some(glass.dispersion())
+        }
+        None => match json.get("abbe_number") {
+            Some(_) => Some(Dispersion::from_abbe_number(
+                number_or(json, "refractive_index", 1.5, path)?,
+                number(json, "abbe_number", path)?,
+            )),
+            None => None,
+        },
+    };
+    let default_index = dispersion.map_or(1.5, |dispersion| dispersion.refractive_index(D_LINE));
+    let absorption = match json.get("attenuation_color") {
+        Some(_) => absorption_for_color(
+            vector(json, "attenuation_color", path)?,
+            number_or(json, "attenuation_distance", 1.0, path)?,
+        ),
+        None => vector_or(json, "absorption", Vec3::new(0.0, 0.0, 0.0), path)?,
+    };
+    Ok(Material::Dieletric {
+        refractive_index: number_or(json, "refractive_index", default_index, path)?,
+        absorption,
+        dispersion,
+    })
+}
+
+fn parse_principled(json: &Json, path: &str) -> Result<Principled<f64>, SceneError> {
+    let default = Principled::default();
+    Ok(Principled {
+        base_color: vector_or(json, "base_color", default.base_color, path)?,
+        metallic: number_or(json, "metallic", default.metallic, path)?,
+        roughness: number_or(json, "roughness", default.roughness, path)?,
+        anisotropy: number_or(json, "anisotropy", default.anisotropy, path)?,
+        specular: number_or(json, "specular", default.specular, path)?,
+        specular_tint: number_or(json, "specular_tint", default.specular_tint, path)?,
+        sheen: number_or(json, "sheen", default.sheen, path)?,
+        sheen_tint: number_or(json, "sheen_tint", default.sheen_tint, path)?,
+        clearcoat: number_or(json, "clearcoat", default.clearcoat, path)?,
+        clearcoat_roughness: number_or(
+            json,
+            "clearcoat_roughness",
+            default.clearcoat_roughness,
+            path,
+        )?,
+        transmission: number_or(json, "transmission", default.transmission, path)?,
+        ior: number_or(json, "ior", default.ior, path)?,
+    })
+}
+
+/// Reads a material as written in a glTF 2.0 file: `pbrMetallicRoughness` and the
+/// `KHR_materials_*` extensions for transmission, clearcoat, sheen, specular and ior. Other
+/// properties (textures, emission, ...) are ignored.
+fn parse_gltf(json: &Json, path: &str) -> Result<Principled<f64>, SceneError> {
+    let pbr = json.get("pbrMetallicRoughness").unwrap_or(&Json::Null);
+    let pbr_path = format!("{}.pbrMetallicRoughness", path);
+    let extension = |name: &str| {
+        json.get("extensions")
+            .and_then(|extensions| extensions.get(name))
+            .unwrap_or(&Json::Null)
+    };
+    let extension_path = |name: &str| format!("{}.extensions.{}", path, name);
+    // glTF colours are RGBA; alpha is ignored.
+    let base_color = vector_or(pbr, "baseColorFactor", Vec3::new(1.0, 1.0, 1.0), &pbr_path)?;
+    let sheen_color = vector_or(
+        extension("KHR_materials_sheen"),
+        "sheenColorFactor",
+        Vec3::new(0.0, 0.0, 0.0),
+        &extension_path("KHR_materials_sheen"),
+    )?;
+    let clearcoat = extension("KHR_materials_clearcoat");
+    let clearcoat_path = extension_path("KHR_materials_clearcoat");
+    Ok(Principled {
+        base_color,
+        metallic: number_or(pbr, "metallicFactor", 1.0, &pbr_path)?,
+        roughness: number_or(pbr, "roughnessFactor", 1.0, &pbr_path)?,
+        // glTF's default specularFactor of 1 is the usual 4% reflectance.
+        specular: 0.5
+            * number_or(
+                extension("KHR_materials_specular"),
+                "specularFactor",
+                1.0,
+                &extension_path("KHR_materials_specular"),
+            )?,
+        sheen: sheen_color.x.max(sheen_color.y).max(sheen_color.z),
+        clearcoat: number_or(clearcoat, "clearcoatFactor", 0.0, &clearcoat_path)?,
+        clearcoat_roughness: number_or(
+            clearcoat,
+            "clearcoatRoughnessFactor",
+            0.0,
+            &clearcoat_path,
+        )?,
+        transmission: number_or(
+            extension("KHR_materials_transmission"),
+            "transmissionFactor",
+            0.0,
+            &extension_path("KHR_materials_transmission"),
+        )?,
+        ior: number_or(
+            extension("KHR_materials_ior"),
+            "ior",
+            1.5,
+            &extension_path("KHR_materials_ior"),
+        )?,
+        ..Principled::default()
+    })
+}
+
+fn string<'a>(json: &'a Json, key: &str, path: &str) -> Result<&'a str, SceneError> {
+    json.get(key)
+        .and_then(Json::as_str)
+        .ok_or_else(|| SceneError::new(format!("{}.{}: expected a string", path, key)))
+}
+
+fn number(json: &Json, key: &str, path: &str) -> Result<f64, SceneError> {
+    json.get(key)
+        .and_then(Json::as_f64)
+        .ok_or_else(|| SceneError::new(format!("{}.{}: expected a number", path, key)))
+}
+
+fn number_or(json: &Json, key: &str, default: f64, path: &str) -> Result<f64, SceneError> {
+    match json.get(key) {
+        Some(_) => number(json, key, path),
+        None => Ok(default),
+    }
+}
+
+/// Reads a number greater than zero, such as a radius.
+fn positive(json: &Json, key: &str, path: &str) -> Result<f64, SceneError> {
+    let value = number(json, key, path)?;
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(SceneError::new(format!(
+            "{}.{}: expected a positive number",
+            path, key
+        )))
+    }
+}
+
+/// Reads a whole number of the scene's, such as its width, from 1 to `max`.
+fn count_or(json: &Json, key: &str, default: usize, max: usize) -> Result<usize, SceneError> {
+    let value = number_or(json, key, default as f64, "scene")?;
+    if value >= 1.0 && value <= max as f64 && value.fract() == 0.0 {
+        Ok(value as usize)
+    } else {
+        Err(SceneError::new(format!(
+            "scene.{}: expected a whole number from 1 to {}",
+            key, max
+        )))
+    }
+}
+
+/// Reads `[x, y, z]` of finite numbers, allowing (and ignoring) a fourth component such as
+/// alpha.
+fn vector(json: &Json, key: &str, path: &str) -> Result<Vec3<f64>, SceneError> {
+    let components: Option<Vec<f64>> = json
+        .get(key)
+        .and_then(Json::as_array)
+        .map(|values| values.iter().map(Json::as_f64).collect())
+        .and_then(|components: Option<Vec<f64>>| components);
+    match components {
+        Some(ref c) if (c.len() == 3 || c.len() == 4) && c.iter().all(|c| c.is_finite()) => {
+            Ok(Vec3::new(c[0], c[1], c[2]))
+        }
+        Some(ref c) if c.len() == 3 || c.len() == 4 => Err(SceneError::new(format!(
+            "{}.{}: expected finite numbers",
+            path, key
+        ))),
+        _ => Err(SceneError::new(format!(
+            "{}.{}: expected an array of 3 numbers",
+            path, key
+        ))),
+    }
+}
+
+fn vector_or(
+    json: &Json,
+    key: &str,
+    default: Vec3<f64>,
+    path: &str,
+) -> Result<Vec3<f64>, SceneError> {
+    match json.get(key) {
+        Some(_) => vector(json, key, path),
+        None => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::hdr::HDREncoder;
+    use image::Rgb;
+    use ray::Ray;
+    use std::env;
+
+    const SCENE: &str = r#"{
+        "width": 320,
+        "height": 240,
+        "samples": 16,
+        "camera": {"look_from": [0, 1, 5], "look_at": [0, 1, 0], "vfov": 40},
+        "materials": {
+            "varnished": {"type": "principled", "base_color": [0.8, 0.1, 0.1], "clearcoat": 1}
+        },
+        "objects": [
+            {"type": "sphere", "center": [0, 1, 0], "radius": 1, "material": "varnished"},
+            {"type": "sphere", "center": [0, -1000, 0], "radius": 1000,
+             "material": {"type": "conductor", "metal": "gold", "roughness": 0.2}}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let scene = parse(SCENE, Path::new(""), 0.0).unwrap();
+        assert_eq!((scene.width, scene.height, scene.samples), (320, 240, 16));
+        match scene.camera {
+            Projection::Perspective(camera) => {
+                assert_eq!(camera.location(), Vec3::new(0.0, 1.0, 5.0))
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(scene.world.objects().len(), 2);
+        assert_eq!(
+            scene.world.objects()[0].describe(),
+            "Sphere at (0.00, 1.00, 0.00), radius 1.00"
+        );
+    }
+
+    #[test]
+    fn test_parse_principled() {
+        let json = json::parse(r#"{"type": "principled", "metallic": 1, "roughness": 0.3}"#);
+        assert_eq!(
+            parse_material(&json.unwrap(), &[], "material"),
+            Ok(Material::Principled(Principled {
+                metallic: 1.0,
+                roughness: 0.3,
+                ..Principled::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_gltf() {
+        let json = json::parse(
+            r#"{
+                "type": "gltf",
+                "pbrMetallicRoughness": {"baseColorFactor": [0.5, 0.5, 1.0, 1.0], "metallicFactor": 0},
+                "extensions": {"KHR_materials_transmission": {"transmissionFactor": 0.9}}
+            }"#,
+        )
+        .unwrap();
+        match parse_material(&json, &[], "material") {
+            Ok(Material::Principled(principled)) => {
+                assert_eq!(principled.base_color, Vec3::new(0.5, 0.5, 1.0));
+                assert_eq!(principled.metallic, 0.0);
+                assert_eq!(principled.roughness, 1.0);
+                assert_eq!(principled.transmission, 0.9);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_dielectric() {
+        let json = json::parse(
+            r#"{"type": "dielectric", "glass": "bk7", "attenuation_color": [0.5, 1, 1],
+                "attenuation_distance": 2}"#,
+        )
+        .unwrap();
+        match parse_material(&json, &[], "material") {
+            Ok(Material::Dieletric {
+                refractive_index,
+                absorption,
+                dispersion,
+            }) => {
+                assert!((refractive_index - 1.5168).abs() < 1e-3);
+                assert!((absorption.x - 0.5f64.ln() / -2.0).abs() < 1e-12);
+                assert_eq!((absorption.y, absorption.z), (0.0, 0.0));
+                assert_eq!(dispersion, Some(Glass::Bk7.dispersion()));
+            }
+            other => panic!("{:?}", other),
+        }
+        let json = json::parse(r#"{"type": "dielectric", "glass": "quartz"}"#).unwrap();
+        assert_eq!(
+            parse_material(&json, &[], "material")
+                .err()
+                .unwrap()
+                .message,
+            "material.glass: unknown glass 'quartz'"
+        );
+    }
+
+    #[test]
+    fn test_parse_volumes() {
+        let scene = parse(
+            r#"{"objects": [
+                {"type": "volume", "density": 0.01},
+                {"type": "volume", "density": 2, "albedo": [0.5, 0.5, 0.5], "anisotropy": 0.3,
+                 "boundary": {"type": "sphere", "center": [0, 1, 0], "radius": 1},
+                 "grid": {"min": [-1, 0, -1], "max": [1, 2, 1], "size": [2, 2, 2],
+                          "densities": [0, 1, 0, 1, 0, 1, 0, 1]}}
+            ]}"#,
+            Path::new(""),
+            0.0,
+        )
+        .unwrap();
+        let descriptions: Vec<String> = scene
+            .world
+            .objects()
+            .iter()
+            .map(|object| object.describe())
+            .collect();
+        assert_eq!(
+            descriptions,
+            vec![
+                "Homogeneous medium of density 0.01, albedo (1.00, 1.00, 1.00), in everywhere",
+                "Heterogeneous medium of density 2.00, albedo (0.50, 0.50, 0.50), in Sphere at \
+                 (0.00, 1.00, 0.00), radius 1.00",
+            ]
+        );
+        assert_eq!(
+            parse(
+                r#"{"objects": [{"type": "volume", "density": 1,
+                    "grid": {"min": [0, 0, 0], "max": [1, 1, 1], "size": [2, 2, 2],
+                             "densities": [1, 2, 3]}}]}"#,
+                Path::new(""),
+                0.0
+            )
+            .err()
+            .unwrap()
+            .message,
+            "objects[0].grid: expected at least 2 samples a side and size.x * size.y * size.z \
+             densities"
+        );
+    }
+
+    #[test]
+    fn test_parse_lights() {
+        let scene = parse(
+            r#"{"objects": [], "lights": [
+                {"type": "point", "position": [0, 5, 0], "intensity": [10, 10, 10]},
+                {"type": "spot", "position": [0, 5, 0], "direction": [0, -1, 0],
+                 "intensity": [10, 10, 10], "cone_angle": 20},
+                {"type": "directional", "direction": [1, 1, 0], "irradiance": [3, 3, 3]},
+                {"type": "sphere", "center": [0, 5, 0], "radius": 0.5, "radiance": [4, 4, 4]}
+            ]}"#,
+            Path::new(""),
+            0.0,
+        )
+        .unwrap();
+        let lights = scene.lighting.lights;
+        assert_eq!(lights.len(), 4);
+        assert_eq!(
+            lights[1],
+            Light::Spot {
+                position: Vec3::new(0.0, 5.0, 0.0),
+                direction: Vec3::new(0.0, -1.0, 0.0),
+                intensity: Vec3::new(10.0, 10.0, 10.0),
+                cone_angle: 20.0,
+                falloff_angle: 5.0,
+            }
+        );
+        assert_eq!(
+            lights[2],
+            Light::Directional {
+                direction: Vec3::new(1.0, 1.0, 0.0),
+                irradiance: Vec3::new(3.0, 3.0, 3.0),
+                angular_diameter: 0.53,
+            }
+        );
+        assert_eq!(
+            parse(
+                r#"{"objects": [], "lights": [{"type": "area", "position": [0, 1, 0]}]}"#,
+                Path::new(""),
+                0.0
+            )
+            .err()
+            .unwrap()
+            .message,
+            "lights[0].type: unknown light type 'area'"
+        );
+    }
+
+    #[test]
+    fn test_parse_motion() {
+        let scene = parse(
+            r#"{
+                "camera": {"shutter_open": 0.25, "shutter_close": 0.75},
+                "objects": [
+                    {"type": "sphere", "center": [0, 1, 0], "end_center": [1, 1, 0], "radius": 1,
+                     "material": {"type": "normal"}},
+                    {"type": "transform",
+                     "object": {"type": "sphere", "center": [0, 0, 0], "radius": 1,
+                                "material": {"type": "normal"}},
+                     "start": {"translation": [0, 0, -5], "scale": [2, 1, 1]},
+                     "end": {"translation": [0, 0, -5], "rotation": {"axis": [0, 1, 0], "angle": 90},
+                             "scale": 2}}
+                ]
+            }"#,
+            Path::new(""),
+            0.0,
+        )
+        .unwrap();
+        assert_eq!(
+            scene.shutter,
+            Shutter {
+                open: 0.25,
+                close: 0.75
+            }
+        );
+        let objects = scene.world.objects();
+        assert_eq!(
+            objects[0].describe(),
+            "Sphere moving from (0.00, 1.00, 0.00) to (1.00, 1.00, 0.00), radius 1.00"
+        );
+        assert_eq!(
+            objects[1].describe(),
+            "Sphere at (0.00, 0.00, 0.00), radius 1.00, moving"
+        );
+        let error = |json: &str| {
+            let text = format!(r#"{{"objects": [{}]}}"#, json);
+            parse(&text, Path::new(""), 0.0).err().unwrap().message
+        };
+        assert_eq!(
+            error(r#"{"type": "transform"}"#),
+            "objects[0].object: missing"
+        );
+        assert_eq!(
+            error(
+                r#"{"type": "transform", "start": {"rotation": {"axis": [0, 1, 0]}},
+                    "object": {"type": "sphere", "center": [0, 0, 0], "radius": 1,
+                               "material": {"type": "normal"}}}"#
+            ),
+            "objects[0].start.rotation.angle: expected a number"
+        );
+        assert_eq!(
+            error(
+                r#"{"type": "transform", "end": {"scale": 0},
+                    "object": {"type": "sphere", "center": [0, 0, 0], "radius": 1,
+                               "material": {"type": "normal"}}}"#
+            ),
+            "objects[0]: a scale must not be zero"
+        );
+    }
+
+    #[test]
+    fn test_parse_keyframes() {
+        let text = r#"{
+            "width": 200,
+            "height": 100,
+            "camera": {
+                "look_at": [0, 1, 0], "vfov": 40, "aperture": 0,
+                "keyframes": [
+                    {"frame": 0, "look_from": [0, 1, 10]},
+                    {"frame": 20, "look_from": [10, 1, 10], "vfov": 20}
+                ]
+            },
+            "objects": [
+                {"type": "transform",
+                 "object": {"type": "sphere", "center": [0, 0, 0], "radius": 1,
+                            "material": {"type": "normal"}},
+                 "interpolation": "smooth",
+                 "keyframes": [
+                     {"frame": 0, "translation": [0, 0, 0]},
+                     {"frame": 10, "translation": [0, 5, 0]},
+                     {"frame": 20, "translation": [0, 10, 0]}
+                 ]}
+            ]
+        }"#;
+        let at = |frame: f64| parse(text, Path::new(""), frame).unwrap();
+        // vfov is only keyframed once, so holds still.
+        let camera = |look_from: Vec3<f64>| {
+            Projection::Perspective(Perspective::new(
+                look_from,
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                20.0,
+                2.0,
+                0.0,
+                4.0,
+            ))
+        };
+        assert_eq!(at(5.0).camera, camera(Vec3::new(2.5, 1.0, 10.0)));
+        assert_eq!(at(30.0).camera, camera(Vec3::new(10.0, 1.0, 10.0)));
+        // Within a frame the transform moves on to where it is at the next.
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let scene = at(10.0);
+        let object = &scene.world.objects()[0];
+        assert!(object.hit(ray, 0.0, f64::MAX).is_none());
+        let ray = Ray::new(Vec3::new(0.0, 6.25, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(object.hit(ray, 0.0, f64::MAX).is_none());
+        assert!(object.hit(ray.with_time(1.0), 0.0, f64::MAX).is_some());
+
+        let error = |json: &str| {
+            let text = format!(r#"{{"objects": [], "camera": {}}}"#, json);
+            parse(&text, Path::new(""), 0.0).err().unwrap().message
+        };
+        assert_eq!(
+            error(r#"{"keyframes": [{"vfov": 30}]}"#),
+            "camera.keyframes[0].frame: expected a number"
+        );
+        assert_eq!(
+            error(r#"{"keyframes": [{"frame": 1, "vfov": 30}, {"frame": 1, "vfov": 40}]}"#),
+            "camera.keyframes: more than one keyframe gives vfov at the same frame"
+        );
+        assert_eq!(
+            error(r#"{"interpolation": "cubic", "keyframes": [{"frame": 1, "vfov": 30}]}"#),
+            "camera.interpolation: unknown interpolation 'cubic'"
+        );
+    }
+
+    #[test]
+    fn test_parse_cameras() {
+        let camera = |json: &str| {
+            let text = format!(
+                r#"{{"width": 400, "height": 200, "camera": {}, "objects": []}}"#,
+                json
+            );
+            parse(&text, Path::new(""), 0.0).map(|scene| scene.camera)
+        };
+        let (from, at, up) = (
+            Vec3::new(0.0, 1.0, 5.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let placed = r#""look_from": [0, 1, 5], "look_at": [0, 1, 0]"#;
+        assert_eq!(
+            camera(&format!(
+                r#"{{"type": "orthographic", {}, "height": 3}}"#,
+                placed
+            )),
+            Ok(Projection::Orthographic(Orthographic::new(
+                from, at, up, 3.0, 2.0
+            )))
+        );
+        assert_eq!(
+            camera(&format!(r#"{{"type": "fisheye", {}}}"#, placed)),
+            Ok(Projection::Fisheye(Fisheye::new(from, at, up, 180.0, 2.0)))
+        );
+        assert_eq!(
+            camera(&format!(r#"{{"type": "panorama", {}}}"#, placed)),
+            Ok(Projection::Equirectangular(Equirectangular::new(
+                from, at, up
+            )))
+        );
+        assert_eq!(
+            camera(&format!(r#"{{"type": "cube_map", {}}}"#, placed)),
+            Ok(Projection::CubeMap(CubeMap::new(from, at, up)))
+        );
+        assert_eq!(
+            camera(r#"{"type": "orthographic"}"#).err().unwrap().message,
+            "camera.height: expected a number"
+        );
+        assert_eq!(
+            camera(r#"{"type": "pinhole"}"#).err().unwrap().message,
+            "camera.type: unknown camera type 'pinhole'"
+        );
+    }
+
+    #[test]
+    fn test_parse_lens_effects() {
+        let file = env::temp_dir().join("rustray_test_aperture.png");
+        image::GrayImage::from_fn(4, 4, |x, _| image::Luma([if x < 2 { 0 } else { 255 }]))
+            .save(&file)
+            .unwrap();
+        let camera = |json: &str| {
+            let text = format!(r#"{{"camera": {}, "objects": []}}"#, json);
+            match parse(&text, &env::temp_dir(), 0.0) {
+                Ok(Scene {
+                    camera: Projection::Perspective(camera),
+                    ..
+                }) => Ok(camera),
+                Ok(_) => panic!("expected a perspective camera"),
+                Err(error) => Err(error.message),
+            }
+        };
+        let lens = camera(
+            r#"{"aperture_blades": 6, "aperture_rotation": 15, "optical_vignetting": 0.5,
+                "tilt": 10, "shift_y": 0.1}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            lens.aperture,
+            Aperture::Polygon {
+                blades: 6,
+                rotation: 15.0
+            }
+        );
+        assert_eq!(lens.optical_vignetting, 0.5);
+        let image = camera(r#"{"aperture_image": "rustray_test_aperture.png"}"#).unwrap();
+        match image.aperture {
+            Aperture::Image(_) => {}
+            other => panic!("expected an image, not {:?}", other),
+        }
+        assert_eq!(
+            camera(r#"{"aperture_blades": 2}"#).err().unwrap(),
+            "camera.aperture_blades: expected a whole number from 3 up"
+        );
+        assert_eq!(
+            camera(r#"{"aperture_blades": 5, "aperture_image": "x.png"}"#)
+                .err()
+                .unwrap(),
+            "camera: give either aperture_blades or aperture_image"
+        );
+        assert!(camera(r#"{"aperture_image": "missing.png"}"#)
+            .err()
+            .unwrap()
+            .starts_with("camera.aperture_image: "));
+    }
+
+    #[test]
+    fn test_parse_backgrounds() {
+        let background = |json: &str| {
+            let text = format!(r#"{{"background": {}, "objects": []}}"#, json);
+            parse(&text, &env::temp_dir(), 0.0).map(|scene| scene.lighting.background)
+        };
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        match background(r#"{"type": "solid", "color": [0.1, 0.2, 0.3]}"#) {
+            Ok(Background::Solid(color)) => assert_eq!(color, Vec3::new(0.1, 0.2, 0.3)),
+            _ => panic!("expected a solid background"),
+        }
+        let gradient =
+            background(r#"{"type": "gradient", "top": [0, 0, 1], "bottom": [1, 1, 1]}"#).unwrap();
+        assert_eq!(gradient.radiance(up), Vec3::new(0.0, 0.0, 1.0));
+        let sky = background(r#"{"type": "sky", "sun_direction": [0, 1, -1]}"#).unwrap();
+        assert!(sky.radiance(up).z > 0.0);
+
+        let file = env::temp_dir().join("rustray_test_background.hdr");
+        HDREncoder::new(fs::File::create(&file).unwrap())
+            .encode(&[Rgb([0.5, 1.0, 2.0]); 4], 2, 2)
+            .unwrap();
+        let map =
+            background(r#"{"type": "map", "path": "rustray_test_background.hdr", "intensity": 2}"#);
+        fs::remove_file(&file).unwrap();
+        assert_eq!(map.unwrap().radiance(up), Vec3::new(1.0, 2.0, 4.0));
+
+        assert_eq!(
+            background(r#"{"type": "map", "path": "sky.png"}"#)
+                .err()
+                .unwrap()
+                .message,
+            format!(
+                "background.path: {}: expected a .hdr or .exr file",
+                env::temp_dir().join("sky.png").display()
+            )
+        );
+        assert_eq!(
+            background(r#"{"type": "stars"}"#).err().unwrap().message,
+            "background.type: unknown background type 'stars'"
+        );
+    }
+
+    #[test]
+    fn test_confined_files_stay_in_the_directory() {
+        let outside =
+            "background.path: expected a relative path that stays inside the scene directory";
+        let error = |path: &str, confined: bool| {
+            let text = format!(
+                r#"{{"background": {{"type": "map", "path": "{}"}}, "objects": []}}"#,
+                path
+            );
+            let parse = if confined { parse_confined } else { parse };
+            parse(&text, Path::new("scenes"), 0.0)
+                .err()
+                .unwrap()
+                .message
+        };
+        for path in &["/etc/sky.hdr", "../sky.hdr", "maps/../../sky.hdr"] {
+            assert_eq!(error(path, true), outside);
+            assert_ne!(error(path, false), outside);
+        }
+        assert_ne!(error("./maps/missing.hdr", true), outside);
+        assert_eq!(
+            parse_confined(
+                r#"{"camera": {"aperture_image": "../bokeh.png"}, "objects": []}"#,
+                Path::new("scenes"),
+                0.0
+            )
+            .err()
+            .unwrap()
+            .message,
+            "camera.aperture_image: expected a relative path that stays inside the scene directory"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |text: &str| parse(text, Path::new(""), 0.0).err().unwrap().message;
+        assert_eq!(error("{}"), "objects: expected an array");
+        assert_eq!(
+            error(r#"{"objects": [{"type": "sphere", "material": "missing"}]}"#),
+            "objects[0].material: unknown material 'missing'"
+        );
+        assert_eq!(
+            error(r#"{"objects": [{"type": "cube", "material": {"type": "normal"}}]}"#),
+            "objects[0].type: unknown object type 'cube'"
+        );
+        assert_eq!(
+            error(
+                r#"{"objects": [{"type": "sphere", "radius": 1, "center": [0, 1],
+                "material": {"type": "normal"}}]}"#
+            ),
+            "objects[0].center: expected an array of 3 numbers"
+        );
+        assert_eq!(
+            error(
+                r#"{"objects": [{"type": "sphere", "radius": 1, "center": [0, 1e999, 0],
+                "material": {"type": "normal"}}]}"#
+            ),
+            "objects[0].center: expected finite numbers"
+        );
+        for radius in &["0", "-1", "1e999"] {
+            assert_eq!(
+                error(&format!(
+                    r#"{{"objects": [{{"type": "sphere", "radius": {}, "center": [0, 1, 0],
+                    "material": {{"type": "normal"}}}}]}}"#,
+                    radius
+                )),
+                "objects[0].radius: expected a positive number"
+            );
+            assert_eq!(
+                error(&format!(
+                    r#"{{"lights": [{{"type": "sphere", "radius": {}, "center": [0, 1, 0],
+                    "radiance": [1, 1, 1]}}], "objects": []}}"#,
+                    radius
+                )),
+                "lights[0].radius: expected a positive number"
+            );
+        }
+        assert_eq!(error("{"), "Expected '\"' at line 1, column 2");
+        for (size, value) in &[("width", "0"), ("height", "-2"), ("width", "1.5")] {
+            assert_eq!(
+                error(&format!(r#"{{"{}": {}, "objects": []}}"#, size, value)),
+                format!(
+                    "scene.{}: expected a whole number from 1 to 4294967295",
+                    size
+                )
+            );
+        }
+        assert_eq!(
+            error(r#"{"width": 1e999, "objects": []}"#),
+            "scene.width: expected a whole number from 1 to 4294967295"
+        );
+        assert_eq!(
+            error(r#"{"samples": -3, "objects": []}"#),
+            "scene.samples: expected a whole number from 1 to 2147483647"
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::io::{self, BufRead, Read, Write};
+
+use scene::json::Json;
+
+/// The largest request body accepted, which is plenty for a scene document.
+const MAX_BODY: usize = 16 << 20;
+
+/// The most header lines accepted in a request.
+const MAX_HEADERS: usize = 100;
+
+/// The longest request line or header line accepted, with its line ending.
+const MAX_LINE: usize = 8 << 10;
+
+/// An HTTP/1.1 request, as much of it as the render service needs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Reads a request. A malformed or oversized one gives the response to send back instead.
+    pub fn read_from(reader: &mut dyn BufRead) -> Result<Request, Response> {
+        let bad_request = |message: &str| Response::error(400, message);
+        let line = read_line(reader)
+            .map_err(|_| bad_request("could not read the request"))?
+            .ok_or_else(|| bad_request("the request line is too long"))?;
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+                (method.to_string(), target.to_string())
+            }
+            _ => return Err(bad_request("expected an HTTP/1.1 request line")),
+        };
+
+        let mut content_length = 0;
+        for _ in 0..=MAX_HEADERS {
+            let header = read_line(reader)
+                .map_err(|_| bad_request("could not read the headers"))?
+                .ok_or_else(|| Response::error(431, "a header is too long"))?;
+            let header = header.trim_end();
+            if header.is_empty() {
+                let mut body = vec![0; content_length];
+                reader
+                    .read_exact(&mut body)
+                    .map_err(|_| bad_request("the body is shorter than its Content-Length"))?;
+                let (path, query) = match target.find('?') {
+                    Some(mark) => (&target[..mark], parse_query(&target[mark + 1..])),
+                    None => (target.as_str(), vec![]),
+                };
+                return Ok(Request {
+                    method,
+                    path: path.to_string(),
+                    query,
+                    body,
+                });
+            }
+            let mut name_value = header.splitn(2, ':');
+            let name = name_value.next().unwrap_or("");
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = name_value
+                    .next()
+                    .and_then(|value| value.trim().parse().ok())
+                    .ok_or_else(|| bad_request("expected a number for Content-Length"))?;
+                if content_length > MAX_BODY {
+                    return Err(Response::error(413, "the body is too large"));
+                }
+            }
+        }
+        Err(bad_request("too many headers"))
+    }
+
+    /// The value of a query parameter, if it was given.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads a line, or as much as there is before the end of the stream, as long as it is no longer
+/// than `MAX_LINE`. A longer line gives `None`, having read no more of it than that.
+fn read_line(reader: &mut dyn BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    Read::take(reader, MAX_LINE as u64).read_line(&mut line)?;
+    if line.len() == MAX_LINE && !line.ends_with('\n') {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+/// `a=1&b=2`, without percent decoding, which the service's parameters never need.
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut key_value = pair.splitn(2, '=');
+            let key = key_value.next().unwrap_or("").to_string();
+            (key, key_value.next().unwrap_or("").to_string())
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: Vec<u8>) -> Response {
+        Response {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body,
+        }
+    }
+
+    pub fn json(status: u16, json: &Json) -> Response {
+        Response::new(status, "application/json", json.to_string().into_bytes())
+    }
+
+    /// `{"error": message}`.
+    pub fn error(status: u16, message: &str) -> Response {
+        Response::json(
+            status,
+            &Json::Object(vec![(
+                "error".to_string(),
+                Json::String(message.to_string()),
+            )]),
+        )
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Writes the response, after which the connection is closed.
+    pub fn write_to(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason(self.status));
+        for (name, value) in &self.headers {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+        head += &format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        );
+        writer.write_all(head.as_bytes())?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_request() {
+        let text = "POST /jobs?frame=12&draft HTTP/1.1\r\nHost: localhost\r\n\
+                    content-length: 4\r\n\r\n{}\r\n";
+        let request = Request::read_from(&mut Cursor::new(text)).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/jobs");
+        assert_eq!(request.query("frame"), Some("12"));
+        assert_eq!(request.query("draft"), Some(""));
+        assert_eq!(request.query("samples"), None);
+        assert_eq!(request.body, b"{}\r\n");
+
+        let error = |text: &str| {
+            Request::read_from(&mut Cursor::new(text))
+                .unwrap_err()
+                .status
+        };
+        assert_eq!(error("GET /\r\n\r\n"), 400);
+        assert_eq!(error("POST / HTTP/1.1\r\nContent-Length: 9\r\n\r\n{}"), 400);
+        assert_eq!(
+            error("POST / HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n"),
+            413
+        );
+        let long = "x".repeat(MAX_LINE);
+        assert_eq!(error(&format!("GET /{} HTTP/1.1\r\n\r\n", long)), 400);
+        assert_eq!(
+            error(&format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", long)),
+            431
+        );
+        let header = format!("X-Long: {}\r\n", &long[..MAX_LINE - 10]);
+        assert_eq!(header.len(), MAX_LINE);
+        let text = format!("GET / HTTP/1.1\r\n{}\r\n", header);
+        assert!(Request::read_from(&mut Cursor::new(text)).is_ok());
+    }
+
+    #[test]
+    fn test_write_response() {
+        let mut bytes = vec![];
+        Response::error(404, "no such job")
+            .with_header("X-Job", "3")
+            .write_to(&mut bytes)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nX-Job: 3\r\n\
+             Content-Length: 23\r\nConnection: close\r\n\r\n{\"error\":\"no such job\"}"
+        );
+    }
+}
@@ -0,0 +1,629 @@
+//! An HTTP service that renders scenes submitted to it, one at a time in the order they arrive.
+//!
+//! `POST /jobs` takes a scene document, as in a scene file, and queues it; `?frame=` picks the
+//! frame of an animated scene. `GET /jobs` lists the jobs, `GET /jobs/<id>` gives one's status
+//! and progress, `GET /jobs/<id>/image.png` the image as far as it has got, and
+//! `DELETE /jobs/<id>` cancels a job and forgets it.
+
+pub mod http;
+
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use control::RenderControl;
+use display::{encode_png, ToneMapping};
+use image_data::ImageData;
+use scene::json::Json;
+use scene::{self, Scene};
+use server::http::{Request, Response};
+use stats::RenderStats;
+use tracer::{draw_to_image_data, ColourMode, RenderSettings};
+use vec3::Vec3;
+
+/// Where the service listens unless told otherwise. Only this machine can reach it, as anyone
+/// who can could make it render whatever they like.
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many finished jobs are kept, with their images, unless told otherwise.
+const DEFAULT_KEPT_JOBS: usize = 16;
+
+/// The most pixels a submitted scene may have, enough for 8K. Each takes 40 bytes once the job
+/// starts rendering, so the largest image takes about 1.3 GB.
+const MAX_PIXELS: usize = 1 << 25;
+
+/// How many jobs may wait to be rendered. Scenes posted beyond that are turned away until the
+/// queue gets shorter.
+const MAX_QUEUED_JOBS: usize = 16;
+
+enum State {
+    Queued(Box<Scene>),
+    Rendering,
+    Done,
+    Cancelled,
+    /// The render panicked, with this message.
+    Failed(String),
+}
+
+impl State {
+    fn is_queued(&self) -> bool {
+        match self {
+            State::Queued(_) => true,
+            State::Rendering | State::Done | State::Cancelled | State::Failed(_) => false,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        match self {
+            State::Done | State::Cancelled | State::Failed(_) => true,
+            State::Queued(_) | State::Rendering => false,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            State::Queued(_) => "queued",
+            State::Rendering => "rendering",
+            State::Done => "done",
+            State::Cancelled => "cancelled",
+            State::Failed(_) => "failed",
+        }
+    }
+}
+
+struct Job {
+    id: usize,
+    samples: i32,
+    width: usize,
+    height: usize,
+    /// Made when the job starts rendering, so that queued jobs take little memory.
+    image_data: Mutex<Option<Arc<ImageData<Vec3<f64>>>>>,
+    stats: RenderStats,
+    control: RenderControl,
+    state: Mutex<State>,
+}
+
+impl Job {
+    fn image_data(&self) -> Option<Arc<ImageData<Vec3<f64>>>> {
+        self.image_data.lock().unwrap().clone()
+    }
+
+    fn to_json(&self) -> Json {
+        let state = self.state.lock().unwrap();
+        let complete = self
+            .image_data()
+            .map_or(0.0, |image_data| image_data.get_complete());
+        let remaining = match *state {
+            State::Rendering => self
+                .stats
+                .estimated_remaining(complete)
+                .map_or(Json::Null, |remaining| {
+                    Json::Number(remaining.as_secs_f64())
+                }),
+            _ => Json::Null,
+        };
+        let error = match *state {
+            State::Failed(ref message) => Json::String(message.clone()),
+            _ => Json::Null,
+        };
+        let number = |value: usize| Json::Number(value as f64);
+        Json::Object(vec![
+            ("id".to_string(), number(self.id)),
+            ("status".to_string(), Json::String(state.name().to_string())),
+            ("error".to_string(), error),
+            ("progress".to_string(), Json::Number(complete)),
+            ("remaining_seconds".to_string(), remaining),
+            ("samples".to_string(), number(self.samples.max(0) as usize)),
+            (
+                "samples_per_pixel".to_string(),
+                number(self.stats.samples_per_pixel()),
+            ),
+            ("width".to_string(), number(self.width)),
+            ("height".to_string(), number(self.height)),
+            (
+                "image".to_string(),
+                Json::String(format!("/jobs/{}/image.png", self.id)),
+            ),
+        ])
+    }
+}
+
+struct Jobs {
+    list: Vec<Arc<Job>>,
+    next_id: usize,
+}
+
+/// Queues the scenes posted to it and renders them one after another. Once more than a few
+/// jobs have finished, the oldest are forgotten.
+pub struct RenderService {
+    jobs: Mutex<Jobs>,
+    submitted: Condvar,
+    /// Where files the scenes refer to, such as environment maps, are looked for.
+    directory: PathBuf,
+    tone_mapping: ToneMapping,
+    kept_jobs: usize,
+}
+
+impl RenderService {
+    pub fn new(directory: PathBuf, tone_mapping: ToneMapping) -> RenderService {
+        RenderService {
+            jobs: Mutex::new(Jobs {
+                list: vec![],
+                next_id: 1,
+            }),
+            submitted: Condvar::new(),
+            directory,
+            tone_mapping,
+            kept_jobs: DEFAULT_KEPT_JOBS,
+        }
+    }
+
+    /// Keeps `kept_jobs` finished jobs, rather than the default.
+    pub fn with_kept_jobs(self, kept_jobs: usize) -> RenderService {
+        RenderService { kept_jobs, ..self }
+    }
+
+    /// Renders the jobs submitted from now on, and answers requests on `listener`, each
+    /// connection on a thread of its own.
+    pub fn serve(service: Arc<RenderService>, listener: TcpListener) -> JoinHandle<()> {
+        let renderer = Arc::clone(&service);
+        thread::spawn(move || renderer.render_jobs(render));
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let service = Arc::clone(&service);
+                thread::spawn(move || {
+                    if let Err(error) = service.serve_connection(stream) {
+                        eprintln!("Could not answer a request: {}", error);
+                    }
+                });
+            }
+        })
+    }
+
+    fn serve_connection(&self, stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let response = match Request::read_from(&mut reader) {
+            Ok(request) => self.handle(&request),
+            Err(response) => response,
+        };
+        response.write_to(&mut &stream)
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        let job = || {
+            segments[1]
+                .parse()
+                .ok()
+                .and_then(|id| self.find(id))
+                .ok_or_else(|| Response::error(404, "no such job"))
+        };
+        let result = match (request.method.as_str(), segments.as_slice()) {
+            ("GET", ["jobs"]) => Ok(self.list()),
+            ("POST", ["jobs"]) => self.submit(request),
+            ("GET", ["jobs", _]) => job().map(|job| Response::json(200, &job.to_json())),
+            ("DELETE", ["jobs", _]) => job().map(|job| self.remove(&job)),
+            ("GET", ["jobs", _, "image.png"]) => job().and_then(|job| self.image(&job)),
+            (_, ["jobs"]) | (_, ["jobs", _]) | (_, ["jobs", _, "image.png"]) => {
+                Err(Response::error(405, "method not allowed"))
+            }
+            _ => Err(Response::error(404, "not found")),
+        };
+        result.unwrap_or_else(|response| response)
+    }
+
+    fn find(&self, id: usize) -> Option<Arc<Job>> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.list.iter().find(|job| job.id == id).map(Arc::clone)
+    }
+
+    fn list(&self) -> Response {
+        let jobs = self.jobs.lock().unwrap();
+        Response::json(
+            200,
+            &Json::Array(jobs.list.iter().map(|job| job.to_json()).collect()),
+        )
+    }
+
+    /// Queues the scene in the request's body, unless the queue is full. It is parsed straight
+    /// away, so that a broken one is turned down rather than failing later.
+    fn submit(&self, request: &Request) -> Result<Response, Response> {
+        let text = std::str::from_utf8(&request.body)
+            .map_err(|_| Response::error(400, "the scene is not UTF-8"))?;
+        let frame = match request.query("frame") {
+            Some(frame) => frame.parse().map_err(|_| {
+                Response::error(400, &format!("frame: expected a number, not '{}'", frame))
+            })?,
+            None => 0.0,
+        };
+        let scene = scene::parse_confined(text, &self.directory, frame)
+            .map_err(|error| Response::error(400, &error.to_string()))?;
+        if scene.width.saturating_mul(scene.height) > MAX_PIXELS {
+            return Err(Response::error(
+                400,
+                &format!(
+                    "the image is {}x{}, more than the {} pixels allowed",
+                    scene.width, scene.height, MAX_PIXELS
+                ),
+            ));
+        }
+
+        let mut jobs = self.jobs.lock().unwrap();
+        let is_queued = |job: &Job| job.state.lock().unwrap().is_queued();
+        if jobs.list.iter().filter(|job| is_queued(job)).count() >= MAX_QUEUED_JOBS {
+            return Err(Response::error(
+                503,
+                &format!(
+                    "there are already {} jobs waiting to be rendered",
+                    MAX_QUEUED_JOBS
+                ),
+            ));
+        }
+        let job = Arc::new(Job {
+            id: jobs.next_id,
+            samples: scene.samples,
+            width: scene.width,
+            height: scene.height,
+            image_data: Mutex::new(None),
+            stats: RenderStats::new(),
+            control: RenderControl::new(),
+            state: Mutex::new(State::Queued(Box::new(scene))),
+        });
+        jobs.next_id += 1;
+        jobs.list.push(Arc::clone(&job));
+        self.submitted.notify_all();
+        Ok(Response::json(201, &job.to_json())
+            .with_header("Location", &format!("/jobs/{}", job.id)))
+    }
+
+    /// Cancels the job if it has not finished, and forgets it.
+    fn remove(&self, job: &Job) -> Response {
+        job.control.cancel();
+        {
+            let mut state = job.state.lock().unwrap();
+            if let State::Queued(_) = *state {
+                *state = State::Cancelled;
+            }
+        }
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.list.retain(|other| other.id != job.id);
+        Response::new(204, "application/json", vec![])
+    }
+
+    fn image(&self, job: &Job) -> Result<Response, Response> {
+        let image_data = job.image_data().ok_or_else(|| {
+            Response::error(
+                404,
+                "the job has no image, as it has not started or it failed",
+            )
+        })?;
+        encode_png(&image_data, self.tone_mapping)
+            .map(|png| Response::new(200, "image/png", png))
+            .map_err(|error| Response::error(500, &error.to_string()))
+    }
+
+    /// Renders queued jobs with `render`, oldest first, for ever. A job whose render panics
+    /// is marked failed and its image dropped, and the next job is rendered as usual.
+    fn render_jobs(&self, render: fn(&Job, &ImageData<Vec3<f64>>, Box<Scene>)) {
+        loop {
+            let (job, scene) = self.next_job();
+            let image_data = Arc::new(ImageData::new_blank(
+                scene.width,
+                scene.height,
+                Vec3::new(0.0, 0.0, 0.0),
+            ));
+            *job.image_data.lock().unwrap() = Some(Arc::clone(&image_data));
+            let rendered =
+                panic::catch_unwind(AssertUnwindSafe(|| render(&job, &image_data, scene)));
+            let failure = rendered.err().map(|payload| {
+                *job.image_data.lock().unwrap() = None;
+                match payload.downcast::<String>() {
+                    Ok(message) => *message,
+                    Err(payload) => match payload.downcast::<&str>() {
+                        Ok(message) => message.to_string(),
+                        Err(_) => "the render failed".to_string(),
+                    },
+                }
+            });
+            self.finish(&job, failure);
+        }
+    }
+
+    /// Marks a job finished, or failed with a message, and forgets the oldest finished jobs
+    /// beyond the number kept, all at once so no request sees more than that.
+    fn finish(&self, job: &Job, failure: Option<String>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        *job.state.lock().unwrap() = match failure {
+            Some(message) => State::Failed(message),
+            None if job.control.is_cancelled() => State::Cancelled,
+            None => State::Done,
+        };
+        let is_finished = |job: &Job| job.state.lock().unwrap().is_finished();
+        let finished = jobs.list.iter().filter(|job| is_finished(job)).count();
+        let mut excess = finished.saturating_sub(self.kept_jobs);
+        jobs.list.retain(|job| {
+            if excess > 0 && is_finished(job) {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Waits for a queued job, and takes its scene to render.
+    fn next_job(&self) -> (Arc<Job>, Box<Scene>) {
+        let mut jobs = self.jobs.lock().unwrap();
+        loop {
+            for job in &jobs.list {
+                let mut state = job.state.lock().unwrap();
+                if let State::Queued(_) = *state {
+                    if let State::Queued(scene) = std::mem::replace(&mut *state, State::Rendering) {
+                        return (Arc::clone(job), scene);
+                    }
+                }
+            }
+            jobs = self.submitted.wait(jobs).unwrap();
+        }
+    }
+}
+
+fn render(job: &Job, image_data: &ImageData<Vec3<f64>>, scene: Box<Scene>) {
+    let colour_mode = if scene.spectral {
+        ColourMode::Spectral
+    } else {
+        ColourMode::Rgb
+    };
+    draw_to_image_data(
+        image_data,
+        &RwLock::new(scene.camera),
+        &scene.world,
+        &scene.lighting,
+        RenderSettings {
+            samples: scene.samples,
+            colour_mode,
+            shutter: scene.shutter,
+            start_pass: 0,
+            seed: rand::random(),
+        },
+        &job.stats,
+        &job.control,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::time::Instant;
+
+    const SCENE: &str = r#"{"width": 5, "height": 3, "samples": 3,
+        "background": {"type": "solid", "color": [0.25, 0.5, 1]}, "objects": []}"#;
+
+    fn service() -> RenderService {
+        RenderService::new(PathBuf::from("."), ToneMapping::default())
+    }
+
+    fn start(service: RenderService) -> String {
+        let service = Arc::new(service);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        RenderService::serve(service, listener);
+        address
+    }
+
+    /// Sends a request as any HTTP client would, and returns the status and body.
+    fn request(address: &str, method: &str, path: &str, body: &str) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            address,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        let end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .unwrap();
+        let head = String::from_utf8(response[..end].to_vec()).unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, response[end + 4..].to_vec())
+    }
+
+    fn json(body: &[u8]) -> Json {
+        scene::json::parse(std::str::from_utf8(body).unwrap()).unwrap()
+    }
+
+    /// The status of job `id` once it is no longer queued or rendering.
+    fn wait_until_finished(address: &str, id: usize) -> Json {
+        let started = Instant::now();
+        loop {
+            let (status, body) = request(address, "GET", &format!("/jobs/{}", id), "");
+            assert_eq!(status, 200);
+            let job = json(&body);
+            match job.get("status").and_then(Json::as_str) {
+                Some("queued") | Some("rendering") => {}
+                _ => return job,
+            }
+            assert!(started.elapsed() < Duration::from_secs(30));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_render_a_submitted_scene() {
+        let address = start(service());
+        let (status, body) = request(&address, "POST", "/jobs", SCENE);
+        assert_eq!(status, 201);
+        let id = json(&body).get("id").and_then(Json::as_f64).unwrap();
+        assert_eq!(id, 1.0);
+
+        let job = wait_until_finished(&address, 1);
+        assert_eq!(job.get("status").and_then(Json::as_str), Some("done"));
+        assert_eq!(job.get("progress").and_then(Json::as_f64), Some(1.0));
+        assert_eq!(
+            job.get("samples_per_pixel").and_then(Json::as_f64),
+            Some(3.0)
+        );
+
+        let (status, png) = request(&address, "GET", "/jobs/1/image.png", "");
+        assert_eq!(status, 200);
+        let image = image::load_from_memory(&png).unwrap().to_rgba();
+        assert_eq!(image.dimensions(), (5, 3));
+        // The brightest channel is scaled to white.
+        assert_eq!(image.get_pixel(2, 1)[2], 255);
+        assert!(image.get_pixel(2, 1)[0] < image.get_pixel(2, 1)[1]);
+
+        let (status, body) = request(&address, "GET", "/jobs", "");
+        assert_eq!(status, 200);
+        assert_eq!(json(&body).as_array().map(<[Json]>::len), Some(1));
+        assert_eq!(request(&address, "DELETE", "/jobs/1", "").0, 204);
+        assert_eq!(request(&address, "GET", "/jobs/1", "").0, 404);
+    }
+
+    #[test]
+    fn test_bad_requests() {
+        let address = start(service());
+        let (status, body) = request(&address, "POST", "/jobs", "{\"width\": ");
+        assert_eq!(status, 400);
+        assert!(json(&body).get("error").and_then(Json::as_str).is_some());
+        assert_eq!(request(&address, "POST", "/jobs?frame=x", SCENE).0, 400);
+        assert_eq!(
+            request(&address, "POST", "/jobs", &"[".repeat(200_000)).0,
+            400
+        );
+        let outside = r#"{"background": {"type": "map", "path": "/etc/passwd"}, "objects": []}"#;
+        assert_eq!(request(&address, "POST", "/jobs", outside).0, 400);
+        for size in &[
+            r#""width": 1e12, "height": 1e12"#,
+            r#""width": 0, "height": 3"#,
+            r#""width": 5, "height": 3, "samples": -1"#,
+        ] {
+            let scene = format!(r#"{{{}, "objects": []}}"#, size);
+            let (status, body) = request(&address, "POST", "/jobs", &scene);
+            assert_eq!(status, 400, "{}", size);
+            assert!(json(&body).get("error").is_some());
+        }
+        let (_, body) = request(
+            &address,
+            "POST",
+            "/jobs",
+            r#"{"width": 8192, "height": 8192, "objects": []}"#,
+        );
+        assert_eq!(
+            json(&body).get("error").and_then(Json::as_str),
+            Some("the image is 8192x8192, more than the 33554432 pixels allowed")
+        );
+        assert_eq!(request(&address, "GET", "/jobs/7", "").0, 404);
+        assert_eq!(request(&address, "GET", "/jobs/seven", "").0, 404);
+        assert_eq!(request(&address, "GET", "/scenes", "").0, 404);
+        assert_eq!(request(&address, "PUT", "/jobs/1", "").0, 405);
+    }
+
+    #[test]
+    fn test_a_render_that_panics_fails_its_job_only() {
+        fn render_unless_two_samples(
+            job: &Job,
+            image_data: &ImageData<Vec3<f64>>,
+            scene: Box<Scene>,
+        ) {
+            assert!(scene.samples != 2, "two samples");
+            render(job, image_data, scene);
+        }
+        let service = Arc::new(service());
+        let renderer = Arc::clone(&service);
+        thread::spawn(move || renderer.render_jobs(render_unless_two_samples));
+        let post = |scene: &str| {
+            let response = service.handle(&Request {
+                method: "POST".to_string(),
+                path: "/jobs".to_string(),
+                query: vec![],
+                body: scene.as_bytes().to_vec(),
+            });
+            assert_eq!(response.status, 201);
+        };
+        let get = |path: &str| {
+            service.handle(&Request {
+                method: "GET".to_string(),
+                path: path.to_string(),
+                query: vec![],
+                body: vec![],
+            })
+        };
+        let status = |id: usize| json(&get(&format!("/jobs/{}", id)).body);
+        post(&SCENE.replace("\"samples\": 3", "\"samples\": 2"));
+        post(SCENE);
+        let started = Instant::now();
+        while status(2).get("status").and_then(Json::as_str) != Some("done") {
+            assert!(started.elapsed() < Duration::from_secs(30));
+            thread::sleep(Duration::from_millis(10));
+        }
+        let failed = status(1);
+        assert_eq!(failed.get("status").and_then(Json::as_str), Some("failed"));
+        assert_eq!(
+            failed.get("error").and_then(Json::as_str),
+            Some("two samples")
+        );
+        assert_eq!(get("/jobs/1/image.png").status, 404);
+        assert_eq!(get("/jobs/2/image.png").status, 200);
+    }
+
+    #[test]
+    fn test_queue_is_limited() {
+        let address = start(service());
+        let slow = r#"{"width": 64, "height": 64, "samples": 1000000, "objects": []}"#;
+        assert_eq!(request(&address, "POST", "/jobs", slow).0, 201);
+        let started = Instant::now();
+        while json(&request(&address, "GET", "/jobs/1", "").1).get("status")
+            != Some(&Json::String("rendering".to_string()))
+        {
+            assert!(started.elapsed() < Duration::from_secs(30));
+            thread::sleep(Duration::from_millis(10));
+        }
+        for id in 2..MAX_QUEUED_JOBS + 2 {
+            assert_eq!(request(&address, "POST", "/jobs", slow).0, 201);
+            assert_eq!(
+                request(&address, "GET", &format!("/jobs/{}/image.png", id), "").0,
+                404
+            );
+        }
+        assert_eq!(request(&address, "POST", "/jobs", slow).0, 503);
+        assert_eq!(request(&address, "DELETE", "/jobs/2", "").0, 204);
+        assert_eq!(request(&address, "POST", "/jobs", slow).0, 201);
+        for id in 1..MAX_QUEUED_JOBS + 3 {
+            request(&address, "DELETE", &format!("/jobs/{}", id), "");
+        }
+    }
+
+    #[test]
+    fn test_old_finished_jobs_are_forgotten() {
+        let address = start(service().with_kept_jobs(2));
+        for _ in 0..4 {
+            assert_eq!(request(&address, "POST", "/jobs", SCENE).0, 201);
+        }
+        wait_until_finished(&address, 4);
+        let (_, body) = request(&address, "GET", "/jobs", "");
+        let ids: Vec<f64> = json(&body)
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|job| job.get("id").and_then(Json::as_f64))
+            .collect();
+        assert_eq!(ids, vec![3.0, 4.0]);
+        assert_eq!(request(&address, "GET", "/jobs/1/image.png", "").0, 404);
+    }
+}
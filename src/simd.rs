@@ -0,0 +1,800 @@
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+use std::ops::{Add, Div, Mul, Sub};
+
+use ray::Ray;
+use vec3::aabb::Aabb;
+use vec3::Float;
+
+/// The most rays traced together by the packet kernels.
+pub const PACKET_WIDTH: usize = 8;
+
+/// Which packet kernel to run, chosen from the features of the CPU we are running on.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SimdLevel {
+    /// 8 lanes in one AVX register. On a CPU without AVX the SSE kernel runs instead.
+    Avx,
+    /// 4 lanes in an SSE register; always available on x86_64.
+    Sse,
+    /// 4 lanes in plain arrays, for other architectures.
+    #[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+    Portable,
+}
+
+impl SimdLevel {
+    pub fn detect() -> SimdLevel {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx") {
+                SimdLevel::Avx
+            } else {
+                SimdLevel::Sse
+            }
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        {
+            SimdLevel::Portable
+        }
+    }
+
+    /// The levels that can run on this CPU, fastest first.
+    #[cfg(test)]
+    pub fn available() -> Vec<SimdLevel> {
+        let mut levels = vec![];
+        if SimdLevel::detect() == SimdLevel::Avx {
+            levels.push(SimdLevel::Avx);
+        }
+        if cfg!(target_arch = "x86_64") {
+            levels.push(SimdLevel::Sse);
+        }
+        levels.push(SimdLevel::Portable);
+        levels
+    }
+}
+
+/// Up to `PACKET_WIDTH` rays stored structure-of-arrays in single precision, ready for loading
+/// into SIMD registers. Unused lanes have a zero direction and never hit anything.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct RayPacket {
+    pub origin: [[f32; PACKET_WIDTH]; 3],
+    pub direction: [[f32; PACKET_WIDTH]; 3],
+    pub len: usize,
+}
+
+impl RayPacket {
+    pub fn new<T: Float>(rays: &[Ray<T>]) -> RayPacket {
+        assert!(rays.len() <= PACKET_WIDTH);
+        let mut packet = RayPacket {
+            origin: [[0.0; PACKET_WIDTH]; 3],
+            direction: [[0.0; PACKET_WIDTH]; 3],
+            len: rays.len(),
+        };
+        for (lane, ray) in rays.iter().enumerate() {
+            let origin = ray.origin().to_f64();
+            let direction = ray.direction().to_f64();
+            packet.origin[0][lane] = origin.x as f32;
+            packet.origin[1][lane] = origin.y as f32;
+            packet.origin[2][lane] = origin.z as f32;
+            packet.direction[0][lane] = direction.x as f32;
+            packet.direction[1][lane] = direction.y as f32;
+            packet.direction[2][lane] = direction.z as f32;
+        }
+        packet
+    }
+}
+
+/// A sphere in single precision, as the packet kernels see it.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PackedSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+/// A box in single precision, grown a little so that rounding never makes a ray that hits the
+/// box at full precision miss it.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PackedBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl PackedBox {
+    pub fn new<T: Float>(aabb: &Aabb<T>) -> PackedBox {
+        let (min, max) = (aabb.min.to_f64(), aabb.max.to_f64());
+        let mut packed = PackedBox {
+            min: [0.0; 3],
+            max: [0.0; 3],
+        };
+        for (axis, &(low, high)) in [(min.x, max.x), (min.y, max.y), (min.z, max.z)]
+            .iter()
+            .enumerate()
+        {
+            let padding = low.abs().max(high.abs()) * 1e-5;
+            packed.min[axis] = (low - padding) as f32;
+            packed.max[axis] = (high + padding) as f32;
+        }
+        packed
+    }
+}
+
+/// The nearest hit each lane of a packet has found so far: how far along the ray it is, and the
+/// index of the object hit, or -1. Indices are carried as floats so they can be blended like
+/// any other lane value. Unused lanes are nearer than any hit, so they never find one.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct PacketHits {
+    pub t: [f32; PACKET_WIDTH],
+    pub index: [f32; PACKET_WIDTH],
+}
+
+impl PacketHits {
+    pub fn new(packet: &RayPacket, max_t: f32) -> PacketHits {
+        let mut hits = PacketHits {
+            t: [f32::NEG_INFINITY; PACKET_WIDTH],
+            index: [-1.0; PACKET_WIDTH],
+        };
+        for t in &mut hits.t[..packet.len] {
+            *t = max_t;
+        }
+        hits
+    }
+
+    pub fn set(&mut self, lane: usize, t: f32, index: usize) {
+        self.t[lane] = t;
+        self.index[lane] = index as f32;
+    }
+
+    pub fn index(&self, lane: usize) -> Option<usize> {
+        Some(self.index[lane])
+            .filter(|&index| index >= 0.0)
+            .map(|index| index as usize)
+    }
+}
+
+/// `L::WIDTH` lanes of a packet, starting at `first_lane`, loaded into registers.
+#[derive(Debug, Copy, Clone)]
+pub struct PacketLanes<L> {
+    pub first_lane: usize,
+    origin: Vec3x<L>,
+    direction: Vec3x<L>,
+    inverse_direction: Vec3x<L>,
+    /// The squared length of each direction.
+    a: L,
+    min_t: L,
+}
+
+impl<L: Lanes> PacketLanes<L> {
+    #[inline(always)]
+    pub fn load(packet: &RayPacket, first_lane: usize, min_t: f32) -> PacketLanes<L> {
+        let lanes = first_lane..first_lane + L::WIDTH;
+        let origin = Vec3x::<L>::load(&packet.origin, lanes.clone());
+        let direction = Vec3x::<L>::load(&packet.direction, lanes);
+        let one = L::splat(1.0);
+        PacketLanes {
+            first_lane,
+            origin,
+            direction,
+            inverse_direction: Vec3x {
+                x: one / direction.x,
+                y: one / direction.y,
+                z: one / direction.z,
+            },
+            a: direction.dot(direction),
+            min_t: L::splat(min_t),
+        }
+    }
+
+    #[inline(always)]
+    pub fn lanes(&self) -> ::std::ops::Range<usize> {
+        self.first_lane..self.first_lane + L::WIDTH
+    }
+
+    /// Whether any lane passes through `bounds` nearer than the hit it has found so far; the
+    /// slab test of `Aabb::hit` across lanes.
+    #[inline(always)]
+    pub fn hit_box(&self, bounds: &PackedBox, hits: &PacketHits) -> bool {
+        let mut near = self.min_t;
+        let mut far = L::load(&hits.t[self.lanes()]);
+        let axes = [
+            (self.origin.x, self.inverse_direction.x, 0),
+            (self.origin.y, self.inverse_direction.y, 1),
+            (self.origin.z, self.inverse_direction.z, 2),
+        ];
+        for &(origin, inverse, axis) in axes.iter() {
+            let t0 = (L::splat(bounds.min[axis]) - origin) * inverse;
+            let t1 = (L::splat(bounds.max[axis]) - origin) * inverse;
+            // `max` and `min` return their second argument if either is NaN, which a ray lying
+            // in a slab's plane gives, so that leaves the range unchanged.
+            near = t0.min(t1).max(near);
+            far = t0.max(t1).min(far);
+        }
+        near.le(far).any()
+    }
+
+    /// The sphere test from `Sphere::hit` across lanes, recording the hits nearer than those
+    /// found so far.
+    #[inline(always)]
+    pub fn hit_sphere(&self, sphere: &PackedSphere, index: usize, hits: &mut PacketHits) {
+        let zero = L::splat(0.0);
+        let closest = L::load(&hits.t[self.lanes()]);
+        let center = Vec3x {
+            x: L::splat(sphere.center[0]),
+            y: L::splat(sphere.center[1]),
+            z: L::splat(sphere.center[2]),
+        };
+        let oc = self.origin - center;
+        let b = oc.dot(self.direction);
+        let c = oc.dot(oc) - L::splat(sphere.radius * sphere.radius);
+        let discriminant = b * b - self.a * c;
+        let root = discriminant.max(zero).sqrt();
+        let near = (zero - b - root) / self.a;
+        let far = (zero - b + root) / self.a;
+        let near_valid = self.min_t.lt(near).and(near.lt(closest));
+        let far_valid = self.min_t.lt(far).and(far.lt(closest));
+        let t = L::select(near_valid, near, far);
+        let hit = zero.le(discriminant).and(near_valid.or(far_valid));
+        if hit.any() {
+            let closest_index = L::load(&hits.index[self.lanes()]);
+            L::select(hit, t, closest).store(&mut hits.t[self.lanes()]);
+            L::select(hit, L::splat(index as f32), closest_index)
+                .store(&mut hits.index[self.lanes()]);
+        }
+    }
+}
+
+/// A SIMD register of f32 lanes. Comparisons return masks with every bit of a lane set or clear,
+/// and `min` and `max` return `other` where either lane is NaN, as SSE does.
+pub trait Lanes:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self>
+{
+    const WIDTH: usize;
+
+    fn splat(value: f32) -> Self;
+    fn load(values: &[f32]) -> Self;
+    fn store(self, values: &mut [f32]);
+    fn sqrt(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn lt(self, other: Self) -> Self;
+    fn le(self, other: Self) -> Self;
+    fn and(self, other: Self) -> Self;
+    fn or(self, other: Self) -> Self;
+    /// Lanes of `if_true` where `mask` is set, otherwise lanes of `if_false`.
+    fn select(mask: Self, if_true: Self, if_false: Self) -> Self;
+    /// Whether any lane of the mask is set.
+    fn any(self) -> bool;
+}
+
+/// Three lane registers holding the x, y and z of `L::WIDTH` vectors.
+#[derive(Debug, Copy, Clone)]
+pub struct Vec3x<L> {
+    pub x: L,
+    pub y: L,
+    pub z: L,
+}
+
+impl<L: Lanes> Vec3x<L> {
+    #[inline(always)]
+    pub fn load(
+        components: &[[f32; PACKET_WIDTH]; 3],
+        lanes: ::std::ops::Range<usize>,
+    ) -> Vec3x<L> {
+        Vec3x {
+            x: L::load(&components[0][lanes.clone()]),
+            y: L::load(&components[1][lanes.clone()]),
+            z: L::load(&components[2][lanes]),
+        }
+    }
+
+    #[inline(always)]
+    pub fn dot(self, other: Vec3x<L>) -> L {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+}
+
+impl<L: Lanes> Sub for Vec3x<L> {
+    type Output = Vec3x<L>;
+
+    #[inline(always)]
+    fn sub(self, other: Vec3x<L>) -> Vec3x<L> {
+        Vec3x {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+}
+
+impl<L: Lanes> Add for Vec3x<L> {
+    type Output = Vec3x<L>;
+
+    #[inline(always)]
+    fn add(self, other: Vec3x<L>) -> Vec3x<L> {
+        Vec3x {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+}
+
+macro_rules! lane_ops {
+    ($(#[$attribute:meta])* $t:ident, $add:ident, $sub:ident, $mul:ident, $div:ident) => {
+        $(#[$attribute])*
+        impl Add for $t {
+            type Output = $t;
+
+            #[inline(always)]
+            fn add(self, other: $t) -> $t {
+                $t(unsafe { $add(self.0, other.0) })
+            }
+        }
+
+        $(#[$attribute])*
+        impl Sub for $t {
+            type Output = $t;
+
+            #[inline(always)]
+            fn sub(self, other: $t) -> $t {
+                $t(unsafe { $sub(self.0, other.0) })
+            }
+        }
+
+        $(#[$attribute])*
+        impl Mul for $t {
+            type Output = $t;
+
+            #[inline(always)]
+            fn mul(self, other: $t) -> $t {
+                $t(unsafe { $mul(self.0, other.0) })
+            }
+        }
+
+        $(#[$attribute])*
+        impl Div for $t {
+            type Output = $t;
+
+            #[inline(always)]
+            fn div(self, other: $t) -> $t {
+                $t(unsafe { $div(self.0, other.0) })
+            }
+        }
+    };
+}
+
+/// Four lanes in an SSE register.
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug, Copy, Clone)]
+pub struct F32x4(__m128);
+
+// SSE is always enabled on x86_64, so since Rust 1.87 its intrinsics are safe to call. The
+// `unsafe` blocks are for older compilers, which require them.
+#[cfg(target_arch = "x86_64")]
+lane_ops!(
+    #[allow(unused_unsafe)]
+    F32x4,
+    _mm_add_ps,
+    _mm_sub_ps,
+    _mm_mul_ps,
+    _mm_div_ps
+);
+
+#[cfg(target_arch = "x86_64")]
+#[allow(unused_unsafe)]
+impl Lanes for F32x4 {
+    const WIDTH: usize = 4;
+
+    #[inline(always)]
+    fn splat(value: f32) -> F32x4 {
+        F32x4(unsafe { _mm_set1_ps(value) })
+    }
+
+    #[inline(always)]
+    fn load(values: &[f32]) -> F32x4 {
+        assert!(values.len() >= 4);
+        F32x4(unsafe { _mm_loadu_ps(values.as_ptr()) })
+    }
+
+    #[inline(always)]
+    fn store(self, values: &mut [f32]) {
+        assert!(values.len() >= 4);
+        unsafe { _mm_storeu_ps(values.as_mut_ptr(), self.0) }
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> F32x4 {
+        F32x4(unsafe { _mm_sqrt_ps(self.0) })
+    }
+
+    #[inline(always)]
+    fn min(self, other: F32x4) -> F32x4 {
+        F32x4(unsafe { _mm_min_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn max(self, other: F32x4) -> F32x4 {
+        F32x4(unsafe { _mm_max_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn lt(self, other: F32x4) -> F32x4 {
+        F32x4(unsafe { _mm_cmplt_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn le(self, other: F32x4) -> F32x4 {
+        F32x4(unsafe { _mm_cmple_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn and(self, other: F32x4) -> F32x4 {
+        F32x4(unsafe { _mm_and_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn or(self, other: F32x4) -> F32x4 {
+        F32x4(unsafe { _mm_or_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn select(mask: F32x4, if_true: F32x4, if_false: F32x4) -> F32x4 {
+        F32x4(unsafe {
+            _mm_or_ps(
+                _mm_and_ps(mask.0, if_true.0),
+                _mm_andnot_ps(mask.0, if_false.0),
+            )
+        })
+    }
+
+    #[inline(always)]
+    fn any(self) -> bool {
+        unsafe { _mm_movemask_ps(self.0) != 0 }
+    }
+}
+
+/// Eight lanes in an AVX register. Only ever created inside functions compiled for AVX, after
+/// the CPU has been checked for AVX support.
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug, Copy, Clone)]
+pub struct F32x8(__m256);
+
+#[cfg(target_arch = "x86_64")]
+lane_ops!(
+    F32x8,
+    _mm256_add_ps,
+    _mm256_sub_ps,
+    _mm256_mul_ps,
+    _mm256_div_ps
+);
+
+#[cfg(target_arch = "x86_64")]
+impl Lanes for F32x8 {
+    const WIDTH: usize = 8;
+
+    #[inline(always)]
+    fn splat(value: f32) -> F32x8 {
+        F32x8(unsafe { _mm256_set1_ps(value) })
+    }
+
+    #[inline(always)]
+    fn load(values: &[f32]) -> F32x8 {
+        assert!(values.len() >= 8);
+        F32x8(unsafe { _mm256_loadu_ps(values.as_ptr()) })
+    }
+
+    #[inline(always)]
+    fn store(self, values: &mut [f32]) {
+        assert!(values.len() >= 8);
+        unsafe { _mm256_storeu_ps(values.as_mut_ptr(), self.0) }
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> F32x8 {
+        F32x8(unsafe { _mm256_sqrt_ps(self.0) })
+    }
+
+    #[inline(always)]
+    fn min(self, other: F32x8) -> F32x8 {
+        F32x8(unsafe { _mm256_min_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn max(self, other: F32x8) -> F32x8 {
+        F32x8(unsafe { _mm256_max_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn lt(self, other: F32x8) -> F32x8 {
+        F32x8(unsafe { _mm256_cmp_ps(self.0, other.0, _CMP_LT_OQ) })
+    }
+
+    #[inline(always)]
+    fn le(self, other: F32x8) -> F32x8 {
+        F32x8(unsafe { _mm256_cmp_ps(self.0, other.0, _CMP_LE_OQ) })
+    }
+
+    #[inline(always)]
+    fn and(self, other: F32x8) -> F32x8 {
+        F32x8(unsafe { _mm256_and_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn or(self, other: F32x8) -> F32x8 {
+        F32x8(unsafe { _mm256_or_ps(self.0, other.0) })
+    }
+
+    #[inline(always)]
+    fn select(mask: F32x8, if_true: F32x8, if_false: F32x8) -> F32x8 {
+        F32x8(unsafe { _mm256_blendv_ps(if_false.0, if_true.0, mask.0) })
+    }
+
+    #[inline(always)]
+    fn any(self) -> bool {
+        unsafe { _mm256_movemask_ps(self.0) != 0 }
+    }
+}
+
+/// Four lanes in a plain array, for CPUs without a kernel of their own.
+#[derive(Debug, Copy, Clone)]
+pub struct Portable4([f32; 4]);
+
+impl Portable4 {
+    #[inline(always)]
+    fn zip(self, other: Portable4, f: impl Fn(f32, f32) -> f32) -> Portable4 {
+        let mut lanes = [0.0; 4];
+        for (lane, value) in lanes.iter_mut().enumerate() {
+            *value = f(self.0[lane], other.0[lane]);
+        }
+        Portable4(lanes)
+    }
+
+    #[inline(always)]
+    fn mask(condition: bool) -> f32 {
+        f32::from_bits(if condition { !0 } else { 0 })
+    }
+}
+
+impl Add for Portable4 {
+    type Output = Portable4;
+
+    #[inline(always)]
+    fn add(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| a + b)
+    }
+}
+
+impl Sub for Portable4 {
+    type Output = Portable4;
+
+    #[inline(always)]
+    fn sub(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| a - b)
+    }
+}
+
+impl Mul for Portable4 {
+    type Output = Portable4;
+
+    #[inline(always)]
+    fn mul(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| a * b)
+    }
+}
+
+impl Div for Portable4 {
+    type Output = Portable4;
+
+    #[inline(always)]
+    fn div(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| a / b)
+    }
+}
+
+impl Lanes for Portable4 {
+    const WIDTH: usize = 4;
+
+    #[inline(always)]
+    fn splat(value: f32) -> Portable4 {
+        Portable4([value; 4])
+    }
+
+    #[inline(always)]
+    fn load(values: &[f32]) -> Portable4 {
+        Portable4([values[0], values[1], values[2], values[3]])
+    }
+
+    #[inline(always)]
+    fn store(self, values: &mut [f32]) {
+        values[..4].copy_from_slice(&self.0);
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> Portable4 {
+        self.zip(self, |a, _| a.sqrt())
+    }
+
+    #[inline(always)]
+    fn min(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| {
+            if a.is_nan() || b.is_nan() {
+                b
+            } else {
+                a.min(b)
+            }
+        })
+    }
+
+    #[inline(always)]
+    fn max(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| {
+            if a.is_nan() || b.is_nan() {
+                b
+            } else {
+                a.max(b)
+            }
+        })
+    }
+
+    #[inline(always)]
+    fn lt(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| Portable4::mask(a < b))
+    }
+
+    #[inline(always)]
+    fn le(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| Portable4::mask(a <= b))
+    }
+
+    #[inline(always)]
+    fn and(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| f32::from_bits(a.to_bits() & b.to_bits()))
+    }
+
+    #[inline(always)]
+    fn or(self, other: Portable4) -> Portable4 {
+        self.zip(other, |a, b| f32::from_bits(a.to_bits() | b.to_bits()))
+    }
+
+    #[inline(always)]
+    fn select(mask: Portable4, if_true: Portable4, if_false: Portable4) -> Portable4 {
+        let mut lanes = [0.0; 4];
+        for (lane, value) in lanes.iter_mut().enumerate() {
+            *value = if mask.0[lane].to_bits() != 0 {
+                if_true.0[lane]
+            } else {
+                if_false.0[lane]
+            };
+        }
+        Portable4(lanes)
+    }
+
+    #[inline(always)]
+    fn any(self) -> bool {
+        self.0.iter().any(|lane| lane.to_bits() != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bvh::Bvh;
+    use vec3::Vec3;
+
+    fn spheres() -> Vec<PackedSphere> {
+        vec![
+            PackedSphere {
+                center: [0.0, 0.0, -5.0],
+                radius: 1.0,
+            },
+            PackedSphere {
+                center: [0.0, 0.0, -3.0],
+                radius: 0.5,
+            },
+            PackedSphere {
+                center: [0.0, -1000.5, 0.0],
+                radius: 1000.0,
+            },
+        ]
+    }
+
+    fn rays() -> Vec<Ray<f64>> {
+        let origin = Vec3::new(0.0, 0.0, 0.0);
+        vec![
+            Ray::new(origin, Vec3::new(0.0, 0.0, -1.0)),
+            Ray::new(origin, Vec3::new(0.0, 0.19, -1.0)),
+            Ray::new(origin, Vec3::new(0.0, 1.0, 0.0)),
+            Ray::new(origin, Vec3::new(0.0, -1.0, -1.0)),
+            Ray::new(Vec3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 0.0, -1.0)),
+            Ray::new(origin, Vec3::new(0.0, 0.0, 1.0)),
+            Ray::new(origin, Vec3::new(0.18, 0.0, -1.0)),
+        ]
+    }
+
+    /// The index of the nearest sphere each ray hits, through a BVH over them.
+    fn nearest(
+        level: SimdLevel,
+        rays: &[Ray<f64>],
+        min_t: f32,
+        max_t: f32,
+    ) -> [Option<usize>; PACKET_WIDTH] {
+        let spheres = spheres();
+        let bvh = Bvh::new(
+            spheres
+                .iter()
+                .enumerate()
+                .map(|(index, sphere)| {
+                    let center = Vec3::new(
+                        f64::from(sphere.center[0]),
+                        f64::from(sphere.center[1]),
+                        f64::from(sphere.center[2]),
+                    );
+                    let radius = f64::from(sphere.radius);
+                    let half = Vec3::new(radius, radius, radius);
+                    (index, Aabb::new(center - half, center + half))
+                })
+                .collect(),
+        );
+        let packet = RayPacket::new(rays);
+        let mut hits = PacketHits::new(&packet, max_t);
+        let packed: Vec<Option<PackedSphere>> = spheres.into_iter().map(Some).collect();
+        bvh.traverse_packet(level, &packet, &packed, min_t, &mut hits, |_, _, _| {
+            panic!("every object is a sphere")
+        });
+        let mut result = [None; PACKET_WIDTH];
+        for (lane, index) in result.iter_mut().enumerate() {
+            *index = hits.index(lane);
+        }
+        result
+    }
+
+    #[test]
+    fn test_levels_agree() {
+        let expected = [
+            Some(1),
+            Some(0),
+            None,
+            Some(2),
+            Some(0),
+            None,
+            Some(0),
+            None,
+        ];
+        for level in SimdLevel::available() {
+            assert_eq!(
+                nearest(level, &rays(), 0.0001, f32::MAX),
+                expected,
+                "{:?}",
+                level
+            );
+        }
+    }
+
+    #[test]
+    fn test_max_t_limits_hits() {
+        for level in SimdLevel::available() {
+            assert_eq!(
+                nearest(level, &rays()[..1], 0.0001, 2.0),
+                [None; PACKET_WIDTH]
+            );
+        }
+    }
+
+    #[test]
+    fn test_packed_box_holds_the_box() {
+        let aabb = Aabb::new(
+            Vec3::new(-1.0e6, 0.1, 3.0),
+            Vec3::new(0.3, 0.2, 1.0e-9 + 3.0),
+        );
+        let packed = PackedBox::new(&aabb);
+        for (axis, &(min, max)) in [
+            (aabb.min.x, aabb.max.x),
+            (aabb.min.y, aabb.max.y),
+            (aabb.min.z, aabb.max.z),
+        ]
+        .iter()
+        .enumerate()
+        {
+            assert!(f64::from(packed.min[axis]) < min);
+            assert!(f64::from(packed.max[axis]) > max);
+        }
+    }
+}
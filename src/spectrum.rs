@@ -0,0 +1,241 @@
+use vec3::{Float, Vec3};
+
+/// The range of visible wavelengths sampled, in nanometres.
+pub const MIN_WAVELENGTH: f64 = 380.0;
+pub const MAX_WAVELENGTH: f64 = 730.0;
+
+/// The average of `wavelength_to_rgb` before normalisation, over wavelengths sampled uniformly
+/// from the visible range.
+const RGB_NORMALISATION: [f64; 3] = [0.503_379, 0.329_663, 0.312_275];
+
+/// The same average without clipping, i.e. the colour of a constant spectrum, which spectral
+/// rendering balances to white.
+const WHITE_BALANCE: [f64; 3] = [0.366_753, 0.290_127, 0.277_286];
+
+/// Smits' basis spectra for turning RGB reflectances into smooth spectra, in ten bins from 380
+/// to 720nm; see Smits, "An RGB to Spectrum Conversion for Reflectances" (1999).
+const SMITS_WHITE: [f64; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f64; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f64; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f64; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f64; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f64; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f64; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/// The wavelengths a path is traced at in spectral mode. Colours along the path are the values
+/// of their spectra at these three wavelengths, carried in a `Vec3`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct SampledWavelengths<T> {
+    pub lambda: Vec3<T>,
+}
+
+impl<T: Float> SampledWavelengths<T> {
+    /// Hero wavelength sampling: `u` picks the hero wavelength, and the other two follow it at
+    /// even spacings across the visible range, wrapping around. See Wilkie et al., "Hero
+    /// Wavelength Spectral Sampling" (2014).
+    pub fn sample(u: T) -> SampledWavelengths<T> {
+        let hero = sample_wavelength(u).to_f64();
+        let range = MAX_WAVELENGTH - MIN_WAVELENGTH;
+        let rotate = |i: f64| {
+            let wavelength = hero + i * range / 3.0;
+            if wavelength > MAX_WAVELENGTH {
+                wavelength - range
+            } else {
+                wavelength
+            }
+        };
+        SampledWavelengths {
+            lambda: Vec3::from_f64(Vec3::new(hero, rotate(1.0), rotate(2.0))),
+        }
+    }
+
+    pub fn hero(&self) -> T {
+        self.lambda.x
+    }
+
+    /// The smooth spectrum Smits' method gives an RGB colour, at each of the wavelengths.
+    pub fn uplift(&self, rgb: Vec3<T>) -> Vec3<T> {
+        let rgb = rgb.to_f64();
+        self.lambda
+            .map(|wavelength| T::from_f64(uplift(rgb, wavelength.to_f64())))
+    }
+
+    /// Converts samples of a spectrum at these wavelengths to linear sRGB, by way of XYZ. Over
+    /// many sets of wavelengths this averages to the spectrum's colour, white balanced so a
+    /// constant spectrum is grey.
+    pub fn to_rgb(self, spectrum: Vec3<T>) -> Vec3<T> {
+        let samples = [
+            (self.lambda.x, spectrum.x),
+            (self.lambda.y, spectrum.y),
+            (self.lambda.z, spectrum.z),
+        ];
+        let mut rgb = Vec3::new(0.0, 0.0, 0.0);
+        for &(wavelength, value) in samples.iter() {
+            let colour = xyz_to_rgb(wavelength_to_xyz(wavelength.to_f64()));
+            rgb = rgb + Vec3::new(colour[0], colour[1], colour[2]) * (value.to_f64() / 3.0);
+        }
+        Vec3::from_f64(Vec3::new(
+            rgb.x / WHITE_BALANCE[0],
+            rgb.y / WHITE_BALANCE[1],
+            rgb.z / WHITE_BALANCE[2],
+        ))
+    }
+}
+
+/// Maps a uniform random number in 0..1 to a wavelength in nanometres.
+pub fn sample_wavelength<T: Float>(u: T) -> T {
+    T::from_f64(MIN_WAVELENGTH) + u * T::from_f64(MAX_WAVELENGTH - MIN_WAVELENGTH)
+}
+
+/// The weight with which light of a single, uniformly sampled wavelength contributes to each
+/// linear sRGB channel. Out of gamut colours are clipped, and the weights are scaled so that
+/// their average over all wavelengths is white, so a path that picks a wavelength at random
+/// still renders grey objects grey on average.
+pub fn wavelength_to_rgb<T: Float>(wavelength: T) -> Vec3<T> {
+    let rgb = xyz_to_rgb(wavelength_to_xyz(wavelength.to_f64()));
+    Vec3::from_f64(Vec3::new(
+        rgb[0].max(0.0) / RGB_NORMALISATION[0],
+        rgb[1].max(0.0) / RGB_NORMALISATION[1],
+        rgb[2].max(0.0) / RGB_NORMALISATION[2],
+    ))
+}
+
+/// The CIE 1931 colour matching functions, in the multi-lobe Gaussian fit of Wyman, Sloan and
+/// Shirley, "Simple Analytic Approximations to the CIE XYZ Color Matching Functions" (2013).
+fn wavelength_to_xyz(wavelength: f64) -> (f64, f64, f64) {
+    let lobe = |mean: f64, below: f64, above: f64| {
+        let sigma = if wavelength < mean { below } else { above };
+        (-0.5 * ((wavelength - mean) / sigma).powi(2)).exp()
+    };
+    let x = 1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+        - 0.065 * lobe(501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+/// Linear sRGB from CIE XYZ.
+pub fn xyz_to_rgb((x, y, z): (f64, f64, f64)) -> [f64; 3] {
+    [
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    ]
+}
+
+/// Smits' spectrum for `rgb` at `wavelength`: as much white as the smallest component, then
+/// the secondary and primary colours that make up the rest.
+fn uplift(rgb: Vec3<f64>, wavelength: f64) -> f64 {
+    let basis = |spectrum: &[f64; 10]| {
+        // Interpolate between bin centres.
+        let x = ((wavelength - 380.0) / 34.0 - 0.5).clamp(0.0, 9.0);
+        let i = (x as usize).min(8);
+        let fraction = x - i as f64;
+        spectrum[i] * (1.0 - fraction) + spectrum[i + 1] * fraction
+    };
+    let (r, g, b) = (rgb.x, rgb.y, rgb.z);
+    if r <= g && r <= b {
+        r * basis(&SMITS_WHITE)
+            + if g <= b {
+                (g - r) * basis(&SMITS_CYAN) + (b - g) * basis(&SMITS_BLUE)
+            } else {
+                (b - r) * basis(&SMITS_CYAN) + (g - b) * basis(&SMITS_GREEN)
+            }
+    } else if g <= r && g <= b {
+        g * basis(&SMITS_WHITE)
+            + if r <= b {
+                (r - g) * basis(&SMITS_MAGENTA) + (b - r) * basis(&SMITS_BLUE)
+            } else {
+                (b - g) * basis(&SMITS_MAGENTA) + (r - b) * basis(&SMITS_RED)
+            }
+    } else {
+        b * basis(&SMITS_WHITE)
+            + if r <= g {
+                (r - b) * basis(&SMITS_YELLOW) + (g - r) * basis(&SMITS_GREEN)
+            } else {
+                (g - b) * basis(&SMITS_YELLOW) + (r - g) * basis(&SMITS_RED)
+            }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wavelengths_average_to_white() {
+        let samples = 10_000;
+        let total = (0..samples)
+            .map(|i| wavelength_to_rgb(sample_wavelength((i as f64 + 0.5) / samples as f64)))
+            .fold(Vec3::new(0.0, 0.0, 0.0), |a, b| a + b);
+        let average: Vec3<f64> = total / samples as f64;
+        for &channel in &[average.x, average.y, average.z] {
+            assert!((channel - 1.0).abs() < 1e-3, "{}", average);
+        }
+    }
+
+    #[test]
+    fn test_uplifted_colours_convert_back() {
+        let samples = 3000;
+        for &rgb in &[
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(0.8, 0.6, 0.2),
+            Vec3::new(0.1, 0.2, 0.5),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(2.0, 0.5, 0.5),
+        ] {
+            // Stratified hero wavelengths, so the average converges quickly.
+            let total = (0..samples)
+                .map(|i| {
+                    let wavelengths = SampledWavelengths::sample((i as f64 + 0.5) / samples as f64);
+                    wavelengths.to_rgb(wavelengths.uplift(rgb))
+                })
+                .fold(Vec3::new(0.0, 0.0, 0.0), |a, b| a + b);
+            let difference = total / samples as f64 - rgb;
+            let brightest = rgb.x.max(rgb.y).max(rgb.z);
+            assert!(
+                difference
+                    .x
+                    .abs()
+                    .max(difference.y.abs())
+                    .max(difference.z.abs())
+                    < 0.03 * brightest,
+                "{} came back as {}",
+                rgb,
+                total / samples as f64
+            );
+        }
+    }
+
+    #[test]
+    fn test_hero_wavelengths_are_spread_out() {
+        let wavelengths = SampledWavelengths::sample(0.9);
+        assert_eq!(wavelengths.hero(), 695.0);
+        assert!((wavelengths.lambda.y - 461.666_666).abs() < 1e-3);
+        assert!((wavelengths.lambda.z - 578.333_333).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_wavelength_colours() {
+        let red = wavelength_to_rgb(650.0);
+        let green = wavelength_to_rgb(540.0);
+        let blue = wavelength_to_rgb(450.0);
+        assert!(red.x > red.y && red.x > red.z);
+        assert!(green.y > green.x && green.y > green.z);
+        assert!(blue.z > blue.x && blue.z > blue.y);
+    }
+}
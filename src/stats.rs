@@ -0,0 +1,157 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "window")]
+use rayon;
+
+/// Counters updated by the tracer while it renders, readable from any thread.
+pub struct RenderStats {
+    pub rays: AtomicU64,
+    pub passes: AtomicUsize,
+    pub started: RwLock<Instant>,
+}
+
+impl RenderStats {
+    pub fn new() -> RenderStats {
+        RenderStats {
+            rays: AtomicU64::new(0),
+            passes: AtomicUsize::new(0),
+            started: RwLock::new(Instant::now()),
+        }
+    }
+
+    #[inline]
+    pub fn add_rays(&self, rays: u64) {
+        self.rays.fetch_add(rays, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn pass_complete(&self) {
+        self.passes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Called when accumulation starts over, e.g. after the camera is refocused.
+    pub fn restart(&self) {
+        self.rays.store(0, Ordering::Relaxed);
+        self.passes.store(0, Ordering::Relaxed);
+        *self.started.write().unwrap() = Instant::now();
+    }
+
+    /// Called when a render carries on from `passes` taken earlier, e.g. from a checkpoint.
+    pub fn resume(&self, passes: usize) {
+        self.restart();
+        self.passes.store(passes, Ordering::Relaxed);
+    }
+
+    pub fn rays(&self) -> u64 {
+        self.rays.load(Ordering::Relaxed)
+    }
+
+    /// Completed passes, i.e. samples taken for every pixel so far.
+    pub fn samples_per_pixel(&self) -> usize {
+        self.passes.load(Ordering::Relaxed)
+    }
+
+    /// The threads rendering in parallel.
+    #[cfg(feature = "window")]
+    pub fn threads(&self) -> usize {
+        rayon::current_num_threads()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.read().unwrap().elapsed()
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.elapsed().as_secs_f64();
+        if seconds > 0.0 {
+            self.rays() as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Extrapolates the time left from the elapsed time and the fraction of work `complete`.
+    pub fn estimated_remaining(&self, complete: f64) -> Option<Duration> {
+        estimate_remaining(self.elapsed(), complete)
+    }
+}
+
+impl Default for RenderStats {
+    fn default() -> RenderStats {
+        RenderStats::new()
+    }
+}
+
+fn estimate_remaining(elapsed: Duration, complete: f64) -> Option<Duration> {
+    if complete <= 0.0 {
+        None
+    } else if complete >= 1.0 {
+        Some(Duration::from_secs(0))
+    } else {
+        Some(elapsed.mul_f64((1.0 - complete) / complete))
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
+}
+
+/// Formats a rate with an SI suffix, e.g. `1.50M`.
+pub fn format_rate(rate: f64) -> String {
+    if rate >= 1e9 {
+        format!("{:.2}G", rate / 1e9)
+    } else if rate >= 1e6 {
+        format!("{:.2}M", rate / 1e6)
+    } else if rate >= 1e3 {
+        format!("{:.2}k", rate / 1e3)
+    } else {
+        format!("{:.0}", rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters() {
+        let stats = RenderStats::new();
+        stats.add_rays(10);
+        stats.add_rays(5);
+        stats.pass_complete();
+        assert_eq!(stats.rays(), 15);
+        assert_eq!(stats.samples_per_pixel(), 1);
+        stats.restart();
+        assert_eq!(stats.rays(), 0);
+        assert_eq!(stats.samples_per_pixel(), 0);
+    }
+
+    #[test]
+    fn test_estimate_remaining() {
+        let elapsed = Duration::from_secs(30);
+        assert_eq!(estimate_remaining(elapsed, 0.0), None);
+        assert_eq!(
+            estimate_remaining(elapsed, 0.25),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            estimate_remaining(elapsed, 1.0),
+            Some(Duration::from_secs(0))
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1:02:03");
+        assert_eq!(format_rate(1_500_000.0), "1.50M");
+        assert_eq!(format_rate(12.0), "12");
+    }
+}
@@ -1,136 +1,881 @@
 extern crate rayon;
 
-use std::sync::Arc;
+use std::sync::RwLock;
 
-use rand::prelude::ThreadRng;
 use rand::Rng;
 use rayon::prelude::*;
 
-use camera::Camera;
-use hittable::Hittable;
+use camera::{Camera, Projection, Shutter};
+use control::RenderControl;
+use hittable::{Hit, Hittable};
 use image_data::ImageData;
+use light::Lighting;
 use material::LightInteraction;
+use random::{self, ThreadRng};
 use ray::Ray;
-use vec3::Vec3;
+use spectrum::{self, SampledWavelengths};
+use stats::RenderStats;
+use vec3::{Float, Vec3};
+
+/// How colour is carried along paths.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum ColourMode {
+    /// Red, green and blue. Fast, and what colours in scenes are written in.
+    Rgb,
+    /// Samples of the spectrum at a few wavelengths per path, converted back to RGB once the
+    /// path is complete. Noisier, but dispersion is resolved with every path rather than a few.
+    Spectral,
+}
+
+/// The colour space a single path is traced in.
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum PathColour<T> {
+    /// RGB, narrowed to a single wavelength once the path meets a dispersive material.
+    Rgb(Option<T>),
+    /// Spectral; `dispersed` once a dispersive material has left only the hero wavelength.
+    Spectral {
+        wavelengths: SampledWavelengths<T>,
+        dispersed: bool,
+    },
+}
+
+impl<T: Float> PathColour<T> {
+    fn new(mode: ColourMode) -> PathColour<T> {
+        match mode {
+            ColourMode::Rgb => PathColour::Rgb(None),
+            ColourMode::Spectral => PathColour::Spectral {
+                wavelengths: SampledWavelengths::sample(T::from_f64(random::thread_rng().gen())),
+                dispersed: false,
+            },
+        }
+    }
+
+    /// The single wavelength the path is traced at, if it has been narrowed to one.
+    fn wavelength(&self) -> Option<T> {
+        match self {
+            PathColour::Rgb(wavelength) => *wavelength,
+            PathColour::Spectral {
+                wavelengths,
+                dispersed: true,
+            } => Some(wavelengths.hero()),
+            PathColour::Spectral { .. } => None,
+        }
+    }
+
+    /// A dispersive material sends each wavelength its own way, so the path continues at a
+    /// single wavelength. Returns the narrowed path and the weight that keeps it unbiased.
+    fn disperse(self) -> (PathColour<T>, Vec3<T>) {
+        match self {
+            PathColour::Rgb(None) => {
+                let wavelength =
+                    spectrum::sample_wavelength(T::from_f64(random::thread_rng().gen()));
+                (
+                    PathColour::Rgb(Some(wavelength)),
+                    spectrum::wavelength_to_rgb(wavelength),
+                )
+            }
+            // Terminate the secondary wavelengths, carrying their share on the hero.
+            PathColour::Spectral {
+                wavelengths,
+                dispersed: false,
+            } => (
+                PathColour::Spectral {
+                    wavelengths,
+                    dispersed: true,
+                },
+                Vec3::from_f64(Vec3::new(3.0, 0.0, 0.0)),
+            ),
+            _ => (self, Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0))),
+        }
+    }
+
+    /// An RGB colour from the scene (an albedo, an attenuation, the sky) as the path sees it.
+    fn convert(&self, rgb: Vec3<T>) -> Vec3<T> {
+        match self {
+            PathColour::Rgb(_) => rgb,
+            PathColour::Spectral { wavelengths, .. } => wavelengths.uplift(rgb),
+        }
+    }
+
+    /// The path's final colour in RGB.
+    fn to_rgb(self, colour: Vec3<T>) -> Vec3<T> {
+        match self {
+            PathColour::Rgb(_) => colour,
+            PathColour::Spectral { wavelengths, .. } => wavelengths.to_rgb(colour),
+        }
+    }
+}
+
+/// How an image is rendered, as opposed to what of.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct RenderSettings {
+    pub samples: i32,
+    pub colour_mode: ColourMode,
+    /// When each path's time is picked from, for motion blur.
+    pub shutter: Shutter,
+    /// Passes already in the image, e.g. from a checkpoint. Rendering carries on after them.
+    pub start_pass: i32,
+    /// Decides every random number the render draws: each row of each pass draws from its own
+    /// stream of it, so rendering with the same seed gives the same image.
+    pub seed: u64,
+}
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 struct WorkItem {
     pub j: usize,
-    pub x_size: usize,
-    pub y_size: usize,
     pub sample: i32,
+    /// The columns of the row to render.
+    pub columns: (usize, usize),
 }
 
-pub fn draw_to_image_data(
-    image_data: &Arc<ImageData<Vec3<f64>>>,
-    camera: &Camera<f64>,
-    world: &Arc<Box<dyn Hittable<f64>>>,
-    samples: i32,
+/// Renders `settings.samples` passes over the image, one pass at a time: over the whole image,
+/// or only `control`'s region if it has one, leaving the other pixels as they are. If the
+/// camera or the region is changed (e.g. from the viewer) while a pass is running,
+/// accumulation restarts from scratch. `control` is told whenever a pass is complete. Returns
+/// early, leaving the image partially sampled, if `control` is cancelled.
+pub fn draw_to_image_data<T: Float>(
+    image_data: &ImageData<Vec3<T>>,
+    camera: &RwLock<Projection<T>>,
+    world: &dyn Hittable<T>,
+    lighting: &Lighting<T>,
+    settings: RenderSettings,
+    stats: &RenderStats,
+    control: &RenderControl,
 ) {
-    let work_items: Vec<WorkItem> = (0..samples)
-        .flat_map(|sample| {
-            (0..image_data.y_size())
-                .rev()
-                .map(|j| WorkItem {
-                    j,
-                    x_size: image_data.x_size,
-                    y_size: image_data.y_size,
-                    sample,
-                })
-                .collect::<Vec<WorkItem>>()
-        })
-        .collect();
-    let work_item_fraction_of_total: f64 = 1.0 / work_items.len() as f64;
-    work_items.par_iter().for_each(|work_item: &WorkItem| {
-        for i in 0..image_data.x_size() {
-            draw_to_image_data_for_pixel(
+    let samples = settings.samples;
+    let mut sample = settings.start_pass;
+    stats.resume(sample as usize);
+    image_data.update_complete(|_| f64::from(sample.min(samples)) / f64::from(samples));
+    while sample < samples {
+        let pass_camera: Projection<T> = camera.read().unwrap().clone();
+        let pass_region = control.region();
+        let region = pass_region.unwrap_or_else(|| image_data.region());
+        let work_item_fraction_of_total: f64 = 1.0 / (region.height as f64 * f64::from(samples));
+        let work_items: Vec<WorkItem> = (region.y..region.y + region.height)
+            .rev()
+            .map(|j| WorkItem {
+                j,
+                sample,
+                columns: (region.x, region.x + region.width),
+            })
+            .collect();
+        work_items.par_iter().for_each(|work_item: &WorkItem| {
+            control.wait_while_paused();
+            if control.is_cancelled() {
+                return;
+            }
+            random::reseed(settings.seed, work_item.sample, work_item.j);
+            let rays = draw_row_to_image_data(
                 image_data,
-                camera,
+                &pass_camera,
                 world,
-                i,
-                work_item.j,
-                work_item.sample,
+                lighting,
+                *work_item,
+                settings,
             );
+            stats.add_rays(rays);
+            image_data.update_complete(|prev| prev + work_item_fraction_of_total);
+        });
+        if control.is_cancelled() {
+            break;
         }
-        image_data.update_complete(|prev| prev + work_item_fraction_of_total);
-    });
+        if *camera.read().unwrap() == pass_camera && control.region() == pass_region {
+            sample += 1;
+            stats.pass_complete();
+            image_data.update_complete(|_| f64::from(sample) / f64::from(samples));
+            control.pass_complete(sample as u32, pass_region);
+        } else {
+            sample = 0;
+            stats.restart();
+            image_data.update_complete(|_| 0.0);
+        }
+    }
 }
 
-fn draw_to_image_data_for_pixel(
-    image_data: &ImageData<Vec3<f64>>,
-    camera: &Camera<f64>,
-    world: &Box<dyn Hittable<f64>>,
-    i: usize,
-    j: usize,
-    sample: i32,
-) {
-    let ray = camera.get_ray(
-        get_pixel_with_randomness(i, image_data.x_size()),
-        get_pixel_with_randomness(j, image_data.y_size()),
-    );
-    let pixel_colour = color(ray, &world, 0.0001, std::f64::MAX, 50);
-    match sample {
-        0 => image_data.update_pixel(i, j, |_| pixel_colour),
-        _ => image_data.update_pixel(i, j, |old_avg| {
-            let old_sum = old_avg * f64::from(sample - 1);
-            let new_sum = old_sum + pixel_colour;
-            new_sum / f64::from(sample)
-        }),
-    };
+/// Traces one sample for every pixel of a row, sending the primary rays through the world as
+/// a packet. Returns the number of rays traced.
+fn draw_row_to_image_data<T: Float>(
+    image_data: &ImageData<Vec3<T>>,
+    camera: &dyn Camera<T>,
+    world: &dyn Hittable<T>,
+    lighting: &Lighting<T>,
+    work_item: WorkItem,
+    settings: RenderSettings,
+) -> u64 {
+    let WorkItem { j, sample, columns } = work_item;
+    let mut rng = random::thread_rng();
+    let (primary_rays, weights): (Vec<Ray<T>>, Vec<T>) = (columns.0..columns.1)
+        .map(|i| {
+            let (ray, weight) = camera.get_weighted_ray(
+                get_pixel_with_randomness(i, image_data.x_size()),
+                get_pixel_with_randomness(j, image_data.y_size()),
+            );
+            (ray.with_time(settings.shutter.time(rng.gen())), weight)
+        })
+        .unzip();
+    let hits = world.hit_packet(&primary_rays, T::from_f64(0.0), T::MAX);
+    let mut rays = 0;
+    let pixels = primary_rays.into_iter().zip(hits).zip(weights);
+    for (i, ((ray, hit), weight)) in (columns.0..columns.1).zip(pixels) {
+        let path_colour = PathColour::new(settings.colour_mode);
+        let pixel_colour = if weight > T::from_f64(0.0) {
+            let colour = match light_hit(ray, &hit, lighting) {
+                Some((radiance, _)) => {
+                    rays += 1;
+                    path_colour.convert(radiance)
+                }
+                None => shade(ray, hit, world, lighting, 50, path_colour, &mut rays),
+            };
+            path_colour.to_rgb(colour) * weight
+        } else {
+            // Blocked by the lens, so not worth tracing.
+            Vec3::new(T::from_f64(0.0), T::from_f64(0.0), T::from_f64(0.0))
+        };
+        match sample {
+            0 => image_data.update_pixel(i, j, |_| pixel_colour),
+            _ => image_data.update_pixel(i, j, |old_avg| {
+                let old_sum = old_avg * T::from_f64(f64::from(sample));
+                let new_sum = old_sum + pixel_colour;
+                new_sum / T::from_f64(f64::from(sample + 1))
+            }),
+        };
+    }
+    rays
 }
 
-fn color(
-    ray: Ray<f64>,
-    hittable: &Box<dyn Hittable<f64>>,
-    min_t: f64,
-    max_t: f64,
+/// The colour seen along `ray`, which was scattered off a surface that picks it with density
+/// `scatter_pdf`, if the surface also had the lights and the background sampled directly.
+fn color<T: Float>(
+    ray: Ray<T>,
+    scatter_pdf: Option<T>,
+    hittable: &dyn Hittable<T>,
+    lighting: &Lighting<T>,
     max_depth: u32,
-) -> Vec3<f64> {
-    hittable.hit(ray, min_t, max_t).map_or_else(
-        || sky_color(ray),
+    path_colour: PathColour<T>,
+    rays: &mut u64,
+) -> Vec3<T> {
+    let hit = hittable.hit(ray, T::from_f64(0.0), T::MAX);
+    if let Some((radiance, light_pdf)) = light_hit(ray, &hit, lighting) {
+        *rays += 1;
+        let weight = scatter_pdf.map_or(T::from_f64(1.0), |pdf| power_heuristic(pdf, light_pdf));
+        return path_colour.convert(radiance) * weight;
+    }
+    match hit {
+        None => {
+            *rays += 1;
+            let direction = *ray.direction();
+            let weight = scatter_pdf.map_or(T::from_f64(1.0), |pdf| {
+                power_heuristic(pdf, lighting.background.pdf(direction))
+            });
+            path_colour.convert(lighting.background.radiance(direction)) * weight
+        }
+        hit => shade(ray, hit, hittable, lighting, max_depth, path_colour, rays),
+    }
+}
+
+/// The light given off by the nearest light `ray` hits, if it hits one before `hit`, and the
+/// density with which sampling that light from where the ray starts picks its direction.
+fn light_hit<T: Float>(
+    ray: Ray<T>,
+    hit: &Option<Hit<T>>,
+    lighting: &Lighting<T>,
+) -> Option<(Vec3<T>, T)> {
+    let max_t = hit.as_ref().map_or(T::MAX, |hit| hit.t);
+    lighting.hit(ray, max_t).map(|(light, radiance)| {
+        let pdf = light.pdf(*ray.origin(), *ray.direction());
+        (radiance, pdf.unwrap_or(T::from_f64(0.0)))
+    })
+}
+
+/// The colour seen along `ray`, given what (if anything) it hit, in the path's colour space.
+fn shade<T: Float>(
+    ray: Ray<T>,
+    hit: Option<Hit<T>>,
+    hittable: &dyn Hittable<T>,
+    lighting: &Lighting<T>,
+    max_depth: u32,
+    path_colour: PathColour<T>,
+    rays: &mut u64,
+) -> Vec3<T> {
+    *rays += 1;
+    hit.map_or_else(
+        || path_colour.convert(lighting.background.radiance(*ray.direction())),
         |hit| {
-            let interaction: LightInteraction<f64> = if max_depth <= 0 {
-                LightInteraction::new(Vec3::new(0.0, 0.0, 0.0), vec![])
+            let (path_colour, weight) = if hit.material().is_dispersive() {
+                path_colour.disperse()
             } else {
-                hit.material().interact(ray, &hit.normal())
+                (path_colour, Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0)))
             };
-            interaction
-                .scattered_rays()
-                .iter()
-                .map(|scattered_ray| {
-                    scattered_ray.attenuation()
-                        * color(
-                            scattered_ray.ray(),
-                            hittable,
-                            0.0001,
-                            std::f64::MAX,
-                            max_depth - 1,
-                        )
-                })
-                .fold(interaction.directly_emitted(), |a, b| a + b)
+            let interaction: LightInteraction<T> = if max_depth == 0 {
+                LightInteraction::new(Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)), vec![])
+            } else {
+                hit.material()
+                    .interact(ray, hit.normal(), path_colour.wavelength())
+            };
+            let direct = if max_depth == 0 {
+                Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0))
+            } else {
+                direct_light(ray, &hit, hittable, lighting, path_colour, rays)
+            };
+            weight
+                * interaction
+                    .scattered_rays()
+                    .iter()
+                    .map(|scattered_ray| {
+                        let direction = *scattered_ray.ray().direction();
+                        path_colour.convert(scattered_ray.attenuation())
+                            * color(
+                                hit.spawn_ray(direction).with_time(ray.time()),
+                                hit.material().pdf(ray, hit.normal(), direction.unit()),
+                                hittable,
+                                lighting,
+                                max_depth - 1,
+                                path_colour,
+                                rays,
+                            )
+                    })
+                    .fold(
+                        path_colour.convert(interaction.directly_emitted()) + direct,
+                        |a, b| a + b,
+                    )
         },
     )
 }
 
-fn sky_color(ray: Ray<f64>) -> Vec3<f64> {
-    let unit_direction = ray.direction().unit();
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    interpolate(Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.5, 0.7, 1.0), t)
+/// The light reaching the surface at `hit` straight from the lights and the background and sent
+/// back along `ray`, with one shadow ray per light. Light from sphere lights and the background
+/// can also be found by scattering, so the two are weighted against each other.
+fn direct_light<T: Float>(
+    ray: Ray<T>,
+    hit: &Hit<T>,
+    hittable: &dyn Hittable<T>,
+    lighting: &Lighting<T>,
+    path_colour: PathColour<T>,
+    rays: &mut u64,
+) -> Vec3<T> {
+    let mut total = Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0));
+    let mut rng = random::thread_rng();
+    for light in &lighting.lights {
+        let sample = light.sample(
+            *hit.normal().origin(),
+            T::from_f64(rng.gen()),
+            T::from_f64(rng.gen()),
+        );
+        let (direction, distance, radiance) = match sample {
+            Some(sample) => sample,
+            None => continue,
+        };
+        let reflected = match hit.material().evaluate(ray, hit.normal(), direction) {
+            Some(reflected) => reflected,
+            // Mirrors and smooth glass reflect no sampled direction towards the viewer.
+            None => return total,
+        };
+        if reflected == Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)) {
+            continue;
+        }
+        *rays += 1;
+        let shadow_ray = hit.spawn_ray(direction).with_time(ray.time());
+        let transmittance = hittable.transmittance(shadow_ray, T::from_f64(0.0), distance);
+        let weight = match (
+            light.pdf(*hit.normal().origin(), direction),
+            hit.material().pdf(ray, hit.normal(), direction),
+        ) {
+            (Some(light_pdf), Some(scatter_pdf)) => power_heuristic(light_pdf, scatter_pdf),
+            _ => T::from_f64(1.0),
+        };
+        if transmittance > T::from_f64(0.0) {
+            total = total
+                + path_colour.convert(reflected)
+                    * path_colour.convert(radiance)
+                    * (transmittance * weight);
+        }
+    }
+    let sample = lighting
+        .background
+        .sample(T::from_f64(rng.gen()), T::from_f64(rng.gen()));
+    if let Some((direction, radiance, pdf)) = sample {
+        let reflected = match hit.material().evaluate(ray, hit.normal(), direction) {
+            Some(reflected) => reflected,
+            None => return total,
+        };
+        if reflected == Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)) || pdf <= T::from_f64(0.0) {
+            return total;
+        }
+        *rays += 1;
+        let shadow_ray = hit.spawn_ray(direction).with_time(ray.time());
+        let transmittance = hittable.transmittance(shadow_ray, T::from_f64(0.0), T::MAX);
+        let weight = hit
+            .material()
+            .pdf(ray, hit.normal(), direction)
+            .map_or(T::from_f64(1.0), |scatter_pdf| {
+                power_heuristic(pdf, scatter_pdf)
+            });
+        if transmittance > T::from_f64(0.0) {
+            total = total
+                + path_colour.convert(reflected)
+                    * path_colour.convert(radiance)
+                    * (transmittance * weight / pdf);
+        }
+    }
+    total
 }
 
-#[inline]
-fn interpolate(first: Vec3<f64>, second: Vec3<f64>, factor: f64) -> Vec3<f64> {
-    first * factor + second * (1.0 - factor)
+/// The weight for a sample picked with density `pdf` by one of two strategies, the other of
+/// which would pick it with density `other_pdf`; see Physically Based Rendering (3rd edition),
+/// section 13.10.1.
+fn power_heuristic<T: Float>(pdf: T, other_pdf: T) -> T {
+    if pdf <= T::from_f64(0.0) {
+        return T::from_f64(0.0);
+    }
+    pdf * pdf / (pdf * pdf + other_pdf * other_pdf)
 }
 
 #[inline]
-fn get_pixel_with_randomness(i: usize, nx: usize) -> f64 {
-    let mut rng: ThreadRng = rand::thread_rng();
-    (i as f64 + rng.gen::<f64>()) / nx as f64
+fn get_pixel_with_randomness<T: Float>(i: usize, nx: usize) -> T {
+    let mut rng: ThreadRng = random::thread_rng();
+    T::from_f64((i as f64 + rng.gen::<f64>()) / nx as f64)
 }
 
-#[inline]
-#[allow(dead_code)]
-fn get_pixel(i: usize, nx: usize) -> f64 {
-    i as f64 / nx as f64
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use background::environment_map::EnvironmentMap;
+    use background::Background;
+    use camera::Perspective;
+    use checkpoint::Checkpoint;
+    use hittable::{Sphere, World};
+    use image_data::Region;
+    use light::Light;
+    use material::Material;
+    use std::f64::consts::PI;
+
+    fn vec3<T: Float>(x: f64, y: f64, z: f64) -> Vec3<T> {
+        Vec3::from_f64(Vec3::new(x, y, z))
+    }
+
+    fn render<T: Float>(
+        x_size: usize,
+        y_size: usize,
+        samples: i32,
+        colour_mode: ColourMode,
+    ) -> Vec<Vec3<f64>> {
+        let image_data = ImageData::new_blank(x_size, y_size, vec3(0.0, 0.0, 0.0));
+        render_spheres(
+            &image_data,
+            RenderSettings {
+                samples,
+                colour_mode,
+                shutter: Shutter::default(),
+                start_pass: 0,
+                seed: 1,
+            },
+        );
+        let pixels: Vec<Vec3<T>> = (&image_data).into();
+        pixels.iter().map(|pixel| pixel.to_f64()).collect()
+    }
+
+    /// Matte, metal and glass balls on a matte floor, under the default sky.
+    fn render_spheres<T: Float>(image_data: &ImageData<Vec3<T>>, settings: RenderSettings) {
+        let world: World<T> = World::new(vec![
+            Box::new(Sphere::new(
+                vec3(0.0, -100.5, -1.0),
+                T::from_f64(100.0),
+                Material::Lambertian {
+                    albedo: vec3(0.8, 0.8, 0.0),
+                },
+            )),
+            Box::new(Sphere::new(
+                vec3(0.0, 0.0, -1.0),
+                T::from_f64(0.5),
+                Material::Lambertian {
+                    albedo: vec3(0.1, 0.2, 0.5),
+                },
+            )),
+            Box::new(Sphere::new(
+                vec3(1.0, 0.0, -1.0),
+                T::from_f64(0.5),
+                Material::Metal {
+                    albedo: vec3(0.8, 0.6, 0.2),
+                    fuzz: T::from_f64(0.0),
+                },
+            )),
+            Box::new(Sphere::new(
+                vec3(-1.0, 0.0, -1.0),
+                T::from_f64(0.5),
+                Material::dielectric(T::from_f64(1.5)),
+            )),
+        ]);
+        let camera = RwLock::new(Projection::Perspective(Perspective::new(
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 0.0, -1.0),
+            vec3(0.0, 1.0, 0.0),
+            T::from_f64(90.0),
+            T::from_f64(image_data.x_size() as f64 / image_data.y_size() as f64),
+            T::from_f64(0.0),
+            T::from_f64(1.0),
+        )));
+        draw_to_image_data(
+            image_data,
+            &camera,
+            &world,
+            &Lighting::default(),
+            settings,
+            &RenderStats::new(),
+            &RenderControl::new(),
+        );
+    }
+
+    /// Averages square blocks of pixels, so per pixel sampling noise does not swamp the
+    /// comparison.
+    fn block_averages(pixels: &[Vec3<f64>], x_size: usize, block: usize) -> Vec<Vec3<f64>> {
+        let y_size = pixels.len() / x_size;
+        (0..y_size / block)
+            .flat_map(|by| (0..x_size / block).map(move |bx| (bx, by)))
+            .map(|(bx, by)| {
+                let mut sum = Vec3::new(0.0, 0.0, 0.0);
+                for y in by * block..(by + 1) * block {
+                    for x in bx * block..(bx + 1) * block {
+                        sum = sum + pixels[y * x_size + x];
+                    }
+                }
+                sum / (block * block) as f64
+            })
+            .collect()
+    }
+
+    fn assert_blocks_match(a: &[Vec3<f64>], b: &[Vec3<f64>], tolerance: f64) {
+        for (a, b) in a.iter().zip(b.iter()) {
+            let difference = *a - *b;
+            assert!(
+                difference
+                    .x
+                    .abs()
+                    .max(difference.y.abs())
+                    .max(difference.z.abs())
+                    < tolerance,
+                "block {:?} differs from block {:?}",
+                a,
+                b
+            );
+        }
+    }
+
+    /// Looking straight down at a grey floor 4 units under a point light, against black. The
+    /// floor only reflects light back up to itself, so all there is is the direct light:
+    /// albedo / π times the irradiance I / d². Renders one pass after `start_pass`.
+    fn render_floor(
+        image_data: &ImageData<Vec3<f64>>,
+        control: &RenderControl,
+        start_pass: i32,
+    ) -> f64 {
+        let world: World<f64> = World::new(vec![Box::new(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Material::Lambertian {
+                albedo: Vec3::new(0.5, 0.5, 0.5),
+            },
+        ))]);
+        let lighting = Lighting {
+            background: Background::Solid(Vec3::new(0.0, 0.0, 0.0)),
+            lights: vec![Light::Point {
+                position: Vec3::new(0.0, 4.0, 0.0),
+                intensity: Vec3::new(16.0, 16.0, 16.0),
+            }],
+        };
+        let camera = RwLock::new(Projection::Perspective(Perspective::new(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        )));
+        draw_to_image_data(
+            image_data,
+            &camera,
+            &world,
+            &lighting,
+            RenderSettings {
+                samples: start_pass + 1,
+                colour_mode: ColourMode::Rgb,
+                shutter: Shutter::default(),
+                start_pass,
+                seed: 1,
+            },
+            &RenderStats::new(),
+            control,
+        );
+        0.5 / std::f64::consts::PI
+    }
+
+    #[test]
+    fn test_point_light_on_matte_floor() {
+        let image_data = ImageData::new_blank(4, 4, Vec3::new(0.0, 0.0, 0.0));
+        let expected = render_floor(&image_data, &RenderControl::new(), 0);
+        let pixels: Vec<Vec3<f64>> = (&image_data).into();
+        for pixel in pixels {
+            assert!((pixel.x - expected).abs() < 1e-3, "{}", pixel);
+        }
+    }
+
+    #[test]
+    fn test_region_leaves_other_pixels_alone() {
+        let untouched = Vec3::new(-1.0, -1.0, -1.0);
+        let image_data = ImageData::new_blank(4, 4, untouched);
+        let control = RenderControl::new();
+        let region = Region {
+            x: 1,
+            y: 2,
+            width: 2,
+            height: 1,
+        };
+        control.set_region(Some(region));
+        let expected = render_floor(&image_data, &control, 0);
+        for y in 0..4 {
+            for x in 0..4 {
+                let pixel = image_data.get_pixel(x, y);
+                if region.contains(x, y) {
+                    assert!((pixel.x - expected).abs() < 1e-3, "{}", pixel);
+                } else {
+                    assert_eq!(pixel, untouched);
+                }
+            }
+        }
+        assert_eq!(image_data.get_complete(), 1.0);
+    }
+
+    #[test]
+    fn test_resume_carries_on_after_start_pass() {
+        // As if three passes had left every pixel at 1.
+        let image_data = ImageData::new_blank(4, 4, Vec3::new(1.0, 1.0, 1.0));
+        let control = RenderControl::new();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        control.set_after_pass(Box::new(move |passes, _| sender.send(passes).unwrap()));
+        let expected = (3.0 + render_floor(&image_data, &control, 3)) / 4.0;
+        let pixels: Vec<Vec3<f64>> = (&image_data).into();
+        for pixel in pixels {
+            assert!((pixel.x - expected).abs() < 1e-3, "{}", pixel);
+        }
+        assert_eq!(receiver.try_iter().collect::<Vec<u32>>(), vec![4]);
+    }
+
+    /// The average pixel of a floor of `material` seen from 1 unit above, looking `down` or up,
+    /// under a sphere light of radiance 8 and radius 1 centred 4 units above the floor, against
+    /// black.
+    fn render_under_sphere_light(material: Material<f64>, down: bool) -> Vec3<f64> {
+        let world: World<f64> = World::new(vec![Box::new(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            material,
+        ))]);
+        let lighting = Lighting {
+            background: Background::Solid(Vec3::new(0.0, 0.0, 0.0)),
+            lights: vec![Light::Sphere {
+                center: Vec3::new(0.0, 4.0, 0.0),
+                radius: 1.0,
+                radiance: Vec3::new(8.0, 8.0, 8.0),
+            }],
+        };
+        let camera = RwLock::new(Projection::Perspective(Perspective::new(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, if down { 0.0 } else { 2.0 }, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+        )));
+        let image_data = ImageData::new_blank(4, 4, Vec3::new(0.0, 0.0, 0.0));
+        draw_to_image_data(
+            &image_data,
+            &camera,
+            &world,
+            &lighting,
+            RenderSettings {
+                samples: 64,
+                colour_mode: ColourMode::Rgb,
+                shutter: Shutter::default(),
+                start_pass: 0,
+                seed: 1,
+            },
+            &RenderStats::new(),
+            &RenderControl::new(),
+        );
+        let pixels: Vec<Vec3<f64>> = (&image_data).into();
+        pixels.iter().fold(Vec3::new(0.0, 0.0, 0.0), |a, b| a + *b) / pixels.len() as f64
+    }
+
+    #[test]
+    fn test_sphere_lights_are_seen() {
+        let matte = Material::Lambertian {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        };
+        assert_eq!(
+            render_under_sphere_light(matte, false),
+            Vec3::new(8.0, 8.0, 8.0)
+        );
+        let mirror = Material::Metal {
+            albedo: Vec3::new(0.9, 0.9, 0.9),
+            fuzz: 0.0,
+        };
+        let reflected = render_under_sphere_light(mirror, true);
+        assert!((reflected.x - 7.2).abs() < 1e-9, "{}", reflected);
+    }
+
+    #[test]
+    fn test_sphere_light_is_not_counted_twice() {
+        // Sampled directly and found by scattered rays, but weighted so that it adds up to the
+        // irradiance π L sin²θ of a ball subtending half angle θ, times albedo / π.
+        let matte = Material::Lambertian {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        };
+        let floor = render_under_sphere_light(matte, true);
+        let expected = 0.5 * 8.0 / 16.0;
+        assert!((floor.x - expected).abs() < 0.02 * expected, "{}", floor);
+    }
+
+    #[test]
+    fn test_sun_in_environment_map_is_sampled() {
+        // A matte floor under a black sky with one bright pixel for a sun, 45 to 50.6 degrees
+        // from the zenith. Scattering alone rarely finds the sun; sampling the map finds it
+        // every time.
+        let (width, height, row) = (64, 32, 8);
+        let sun = 1000.0;
+        let mut pixels = vec![Vec3::new(0.0, 0.0, 0.0); width * height];
+        pixels[row * width + 16] = Vec3::new(sun, sun, sun);
+        let lighting = Lighting {
+            background: Background::Map(EnvironmentMap::new(width, height, pixels, 0.0, 1.0)),
+            lights: vec![],
+        };
+        let albedo = 0.5;
+        let world: World<f64> = World::new(vec![Box::new(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Material::Lambertian {
+                albedo: Vec3::new(albedo, albedo, albedo),
+            },
+        ))]);
+        // Albedo / π times the integral of the sun's radiance times the cosine over its pixel.
+        let (theta0, theta1) = (
+            PI * row as f64 / height as f64,
+            PI * (row + 1) as f64 / height as f64,
+        );
+        let expected = albedo / PI
+            * sun
+            * (2.0 * PI / width as f64)
+            * (theta1.sin().powi(2) - theta0.sin().powi(2))
+            / 2.0;
+
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = world.hit(ray, 0.0, f64::MAX).unwrap();
+        let samples = 20_000;
+        let mean_and_variance = |estimate: &mut dyn FnMut() -> f64| {
+            let values: Vec<f64> = (0..samples).map(|_| estimate()).collect();
+            let mean = values.iter().sum::<f64>() / samples as f64;
+            let variance = values
+                .iter()
+                .map(|value| (value - mean) * (value - mean))
+                .sum::<f64>()
+                / samples as f64;
+            (mean, variance)
+        };
+        let mut rays = 0;
+        let (mean, variance) = mean_and_variance(&mut || {
+            color(
+                ray,
+                None,
+                &world,
+                &lighting,
+                1,
+                PathColour::Rgb(None),
+                &mut rays,
+            )
+            .x
+        });
+        // As the tracer did before sampling the map: only the light scattered rays find.
+        let (_, scattering_variance) = mean_and_variance(&mut || {
+            let interaction = hit.material().interact(ray, hit.normal(), None);
+            let scattered = interaction.scattered_rays()[0];
+            (scattered.attenuation() * lighting.background.radiance(*scattered.ray().direction())).x
+        });
+        assert!(
+            (mean - expected).abs() < 0.02 * expected,
+            "{} but expected {}",
+            mean,
+            expected
+        );
+        assert!(
+            variance * 100.0 < scattering_variance,
+            "variance {} against {} from scattering alone",
+            variance,
+            scattering_variance
+        );
+    }
+
+    #[test]
+    fn test_resumed_render_matches_uninterrupted_one() {
+        let settings = RenderSettings {
+            samples: 3,
+            colour_mode: ColourMode::Rgb,
+            shutter: Shutter::default(),
+            start_pass: 0,
+            seed: 5,
+        };
+        let blank = || ImageData::new_blank(16, 8, Vec3::new(0.0, 0.0, 0.0));
+        let uninterrupted = blank();
+        render_spheres(&uninterrupted, settings);
+
+        let stopped = blank();
+        render_spheres(
+            &stopped,
+            RenderSettings {
+                samples: 2,
+                ..settings
+            },
+        );
+        let checkpoint = Checkpoint::capture(&stopped, None, 2, settings.seed, 0, 0);
+        let resumed = blank();
+        checkpoint.restore(&resumed);
+        render_spheres(
+            &resumed,
+            RenderSettings {
+                start_pass: checkpoint.passes as i32,
+                seed: checkpoint.seed,
+                ..settings
+            },
+        );
+        let uninterrupted: Vec<Vec3<f64>> = (&uninterrupted).into();
+        let resumed: Vec<Vec3<f64>> = (&resumed).into();
+        for (a, b) in uninterrupted.iter().zip(&resumed) {
+            assert!((*a - *b).length() < 1e-12, "{} != {}", a, b);
+        }
+
+        let reseeded = blank();
+        render_spheres(
+            &reseeded,
+            RenderSettings {
+                seed: 6,
+                ..settings
+            },
+        );
+        let reseeded: Vec<Vec3<f64>> = (&reseeded).into();
+        assert_ne!(reseeded, uninterrupted);
+    }
+
+    #[test]
+    fn test_f32_render_matches_f64() {
+        let single = block_averages(&render::<f32>(32, 16, 64, ColourMode::Rgb), 32, 8);
+        let double = block_averages(&render::<f64>(32, 16, 64, ColourMode::Rgb), 32, 8);
+        assert_blocks_match(&single, &double, 0.02);
+    }
+
+    #[test]
+    fn test_spectral_render_matches_rgb() {
+        let rgb = block_averages(&render::<f64>(32, 16, 64, ColourMode::Rgb), 32, 8);
+        let spectral = block_averages(&render::<f64>(32, 16, 64, ColourMode::Spectral), 32, 8);
+        // Products of uplifted spectra are not quite the spectra of products, so colours that
+        // bounce between the matte spheres come out slightly differently.
+        assert_blocks_match(&rgb, &spectral, 0.05);
+    }
 }
@@ -0,0 +1,282 @@
+use hittable::{Hit, Hittable};
+use ray::{gamma, Ray};
+use vec3::aabb::Aabb;
+use vec3::matrix::Mat3;
+use vec3::quaternion::Quaternion;
+use vec3::{Float, Vec3};
+
+/// Poses sampled across the motion when bounding a moving object.
+const BOUNDS_STEPS: usize = 16;
+
+/// Where an object is put: scaled along its own axes, then rotated, then moved.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Pose<T> {
+    pub translation: Vec3<T>,
+    pub rotation: Quaternion<T>,
+    pub scale: Vec3<T>,
+}
+
+impl<T: Float> Pose<T> {
+    pub fn identity() -> Pose<T> {
+        Pose {
+            translation: Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)),
+            rotation: Quaternion::identity(),
+            scale: Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0)),
+        }
+    }
+
+    /// The pose a fraction `t` of the way to `other`: moving and scaling in straight lines,
+    /// and turning at a steady rate.
+    pub fn interpolate(self, other: Pose<T>, t: T) -> Pose<T> {
+        let one = T::from_f64(1.0);
+        Pose {
+            translation: self.translation * (one - t) + other.translation * t,
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale * (one - t) + other.scale * t,
+        }
+    }
+
+    pub fn point_to_world(self, p: Vec3<T>) -> Vec3<T> {
+        self.rotation.rotate(p * self.scale) + self.translation
+    }
+
+    fn point_to_local(self, p: Vec3<T>) -> Vec3<T> {
+        self.vector_to_local(p - self.translation)
+    }
+
+    fn vector_to_local(self, v: Vec3<T>) -> Vec3<T> {
+        self.rotation.conjugate().rotate(v) / self.scale
+    }
+
+    /// Normals take the inverse transpose, which for a rotation and scale is the rotation and
+    /// the inverse scale. Not normalised.
+    fn normal_to_world(self, n: Vec3<T>) -> Vec3<T> {
+        self.rotation.rotate(n / self.scale)
+    }
+
+    /// The rotation and scale as a matrix.
+    fn linear(self) -> Mat3<T> {
+        self.rotation.to_mat3() * Mat3::scale(self.scale)
+    }
+}
+
+/// An object placed in the world by a pose that may change over time: at `start` at time 0,
+/// at `end` at time 1, and in between part way from one to the other.
+pub struct Transform<T> {
+    pub object: Box<dyn Hittable<T>>,
+    pub start: Pose<T>,
+    pub end: Pose<T>,
+}
+
+impl<T: Float> Transform<T> {
+    /// Fails if a pose scales an axis to nothing, so cannot be undone.
+    pub fn new(object: Box<dyn Hittable<T>>, start: Pose<T>, end: Pose<T>) -> Option<Transform<T>> {
+        let zero = T::from_f64(0.0);
+        let invertible =
+            |pose: &Pose<T>| pose.scale.x != zero && pose.scale.y != zero && pose.scale.z != zero;
+        Some(Transform { object, start, end }).filter(|_| invertible(&start) && invertible(&end))
+    }
+
+    pub fn pose_at(&self, time: T) -> Pose<T> {
+        if self.start == self.end {
+            return self.start;
+        }
+        let time = time.max(T::from_f64(0.0)).min(T::from_f64(1.0));
+        self.start.interpolate(self.end, time)
+    }
+
+    /// `ray` in the object's own space. The direction is not normalised, so distances along
+    /// the ray are the same in both.
+    fn to_local(&self, ray: Ray<T>) -> (Pose<T>, Ray<T>) {
+        let pose = self.pose_at(ray.time());
+        let local = Ray::new(
+            pose.point_to_local(*ray.origin()),
+            pose.vector_to_local(*ray.direction()),
+        );
+        (pose, local.with_time(ray.time()))
+    }
+}
+
+impl<T: Float> Hittable<T> for Transform<T> {
+    fn hit(&self, ray: Ray<T>, min_t: T, max_t: T) -> Option<Hit<T>> {
+        let (pose, local) = self.to_local(ray);
+        let hit = self.object.hit(local, min_t, max_t)?;
+        let p = *hit.normal().origin();
+        // The error of the hit point, carried through the transform and increased by the
+        // rounding in it; see Physically Based Rendering (3rd edition), section 3.9.3.
+        let linear = abs(pose.linear());
+        let error = linear * hit.error * (T::from_f64(1.0) + gamma::<T>(3))
+            + (linear * p.map(Float::abs) + pose.translation.map(Float::abs)) * gamma::<T>(3);
+        Some(Hit::new(
+            hit.t,
+            Ray::new(
+                pose.point_to_world(p),
+                pose.normal_to_world(*hit.normal().direction()).unit(),
+            ),
+            error,
+            hit.material,
+        ))
+    }
+
+    fn transmittance(&self, ray: Ray<T>, min_t: T, max_t: T) -> T {
+        let (_, local) = self.to_local(ray);
+        self.object.transmittance(local, min_t, max_t)
+    }
+
+    #[cfg(any(feature = "window", test))]
+    fn describe(&self) -> String {
+        let motion = if self.start == self.end {
+            "transformed"
+        } else {
+            "moving"
+        };
+        format!("{}, {}", self.object.describe(), motion)
+    }
+
+    /// The object's box placed at poses all through the motion, each grown by as far as any
+    /// point of the object can travel in half the time between them.
+    fn bounds(&self) -> Option<Aabb<T>> {
+        let local = self.object.bounds()?;
+        let steps = if self.start == self.end {
+            1
+        } else {
+            BOUNDS_STEPS
+        };
+        let reach = local
+            .corners()
+            .iter()
+            .map(|corner| corner.length())
+            .fold(T::from_f64(0.0), T::max);
+        let cos_half_angle = self.start.rotation.dot(self.end.rotation).abs();
+        let angle = T::from_f64(2.0) * cos_half_angle.min(T::from_f64(1.0)).acos();
+        let largest = |scale: Vec3<T>| scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+        let largest_scale = largest(self.start.scale).max(largest(self.end.scale));
+        let speed = (self.end.translation - self.start.translation).length()
+            + angle * largest_scale * reach
+            + (self.end.scale - self.start.scale).length() * reach;
+        let padding = speed / T::from_f64(2.0 * steps as f64);
+        (0..=steps)
+            .map(|step| {
+                let pose = self.pose_at(T::from_f64(step as f64 / steps as f64));
+                let corners: Vec<Vec3<T>> = local
+                    .corners()
+                    .iter()
+                    .map(|corner| pose.point_to_world(*corner))
+                    .collect();
+                Aabb::from_points(&corners).unwrap().padded(padding)
+            })
+            .fold(None, |bounds: Option<Aabb<T>>, aabb| {
+                Some(bounds.map_or(aabb, |bounds| bounds.union(aabb)))
+            })
+    }
+}
+
+fn abs<T: Float>(matrix: Mat3<T>) -> Mat3<T> {
+    let mut m = matrix.m;
+    for value in m.iter_mut().flat_map(|row| row.iter_mut()) {
+        *value = value.abs();
+    }
+    Mat3::new(m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hittable::Sphere;
+    use material::Material;
+
+    fn sphere() -> Box<dyn Hittable<f64>> {
+        Box::new(Sphere::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::Normal {},
+        ))
+    }
+
+    fn pose(translation: Vec3<f64>, angle: f64, scale: Vec3<f64>) -> Pose<f64> {
+        Pose {
+            translation,
+            rotation: Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), angle),
+            scale,
+        }
+    }
+
+    #[test]
+    fn test_scaled_and_moved() {
+        // A unit sphere stretched to 2 along x, then moved to x = 5.
+        let start = pose(Vec3::new(5.0, 0.0, 0.0), 0.0, Vec3::new(2.0, 1.0, 1.0));
+        let transform = Transform::new(sphere(), start, start).unwrap();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = transform.hit(ray, 0.0, f64::MAX).unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-12);
+        assert!((*hit.normal().direction() - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-12);
+        let bounds = transform.bounds().unwrap();
+        assert!((bounds.min - Vec3::new(3.0, -1.0, -1.0)).length() < 1e-12);
+        assert!((bounds.max - Vec3::new(7.0, 1.0, 1.0)).length() < 1e-12);
+        // Scaled normals stay perpendicular to the surface.
+        let ray = Ray::new(
+            Vec3::new(5.0 + 2.0_f64.sqrt(), 5.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+        );
+        let hit = transform.hit(ray, 0.0, f64::MAX).unwrap();
+        let local = *hit.normal().origin() - Vec3::new(5.0, 0.0, 0.0);
+        let gradient = Vec3::new(local.x / 4.0, local.y, local.z).unit();
+        assert!((*hit.normal().direction() - gradient).length() < 1e-9);
+        assert!(Transform::new(
+            sphere(),
+            pose(start.translation, 0.0, Vec3::new(0.0, 1.0, 1.0)),
+            start
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_moving() {
+        let identity = Pose::identity();
+        let moved = Pose {
+            translation: Vec3::new(0.0, 0.0, 4.0),
+            ..identity
+        };
+        let transform = Transform::new(sphere(), identity, moved).unwrap();
+        let ray = Ray::new(Vec3::new(0.0, 5.0, 2.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(transform.hit(ray, 0.0, f64::MAX).is_none());
+        let hit = transform.hit(ray.with_time(0.5), 0.0, f64::MAX).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-12);
+        assert!(transform.describe().ends_with("moving"));
+    }
+
+    #[test]
+    fn test_bounds_contain_the_object_throughout_its_motion() {
+        // Swinging an off-centre ball half way round: the straight line between the start and
+        // end boxes would miss the middle of the swing.
+        let ball: Box<dyn Hittable<f64>> = Box::new(Sphere::new(
+            Vec3::new(3.0, 0.0, 0.0),
+            0.5,
+            Material::Normal {},
+        ));
+        let transform = Transform::new(
+            ball,
+            pose(Vec3::new(0.0, 0.0, 0.0), 0.0, Vec3::new(1.0, 1.0, 1.0)),
+            pose(Vec3::new(1.0, 0.0, 0.0), 3.0, Vec3::new(1.5, 1.5, 1.5)),
+        )
+        .unwrap();
+        let bounds = transform.bounds().unwrap();
+        let inside = |p: Vec3<f64>| {
+            bounds.min.x <= p.x
+                && p.x <= bounds.max.x
+                && bounds.min.y <= p.y
+                && p.y <= bounds.max.y
+                && bounds.min.z <= p.z
+                && p.z <= bounds.max.z
+        };
+        for step in 0..=1000 {
+            let pose = transform.pose_at(step as f64 / 1000.0);
+            for corner in Aabb::new(Vec3::new(2.5, -0.5, -0.5), Vec3::new(3.5, 0.5, 0.5))
+                .corners()
+                .iter()
+            {
+                assert!(inside(pose.point_to_world(*corner)));
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use ray::Ray;
+use vec3::{Float, Vec3};
+
+/// An axis aligned bounding box.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Aabb<T> {
+    pub min: Vec3<T>,
+    pub max: Vec3<T>,
+}
+
+impl<T: Float> Aabb<T> {
+    pub fn new(min: Vec3<T>, max: Vec3<T>) -> Aabb<T> {
+        Aabb { min, max }
+    }
+
+    /// The smallest box containing every point.
+    pub fn from_points(points: &[Vec3<T>]) -> Option<Aabb<T>> {
+        let (first, rest) = points.split_first()?;
+        Some(rest.iter().fold(Aabb::new(*first, *first), |aabb, point| {
+            aabb.union(Aabb::new(*point, *point))
+        }))
+    }
+
+    pub fn union(self, other: Aabb<T>) -> Aabb<T> {
+        Aabb::new(
+            Vec3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            Vec3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        )
+    }
+
+    pub fn corners(&self) -> [Vec3<T>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// The box grown by `amount` on every side.
+    pub fn padded(self, amount: T) -> Aabb<T> {
+        let amount = Vec3::new(amount, amount, amount);
+        Aabb::new(self.min - amount, self.max + amount)
+    }
+
+    pub fn centroid(&self) -> Vec3<T> {
+        (self.min + self.max) / T::from_f64(2.0)
+    }
+
+    pub fn surface_area(&self) -> T {
+        let size = self.max - self.min;
+        T::from_f64(2.0) * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// 0, 1 or 2 for whichever of x, y and z the box is longest in.
+    pub fn longest_axis(&self) -> usize {
+        let size = self.max - self.min;
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// The slab test: the range of t over which `ray` is inside the box, clipped to
+    /// `min_t..max_t`, or `None` if it misses. Axis parallel rays are handled by the infinities
+    /// that dividing by a zero direction component produces.
+    pub fn hit(&self, ray: &Ray<T>, min_t: T, max_t: T) -> Option<(T, T)> {
+        let one = T::from_f64(1.0);
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let axes = [
+            (origin.x, direction.x, self.min.x, self.max.x),
+            (origin.y, direction.y, self.min.y, self.max.y),
+            (origin.z, direction.z, self.min.z, self.max.z),
+        ];
+        let mut near = min_t;
+        let mut far = max_t;
+        for &(origin, direction, min, max) in axes.iter() {
+            let inverse = one / direction;
+            let t0 = (min - origin) * inverse;
+            let t1 = (max - origin) * inverse;
+            let (t0, t1) = if inverse < T::from_f64(0.0) {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            };
+            // Written so a NaN (a ray lying in a slab's plane) leaves the range unchanged.
+            if t0 > near {
+                near = t0;
+            }
+            if t1 < far {
+                far = t1;
+            }
+            if far < near {
+                return None;
+            }
+        }
+        Some((near, far))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb<f64> {
+        Aabb::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_from_points() {
+        let aabb = Aabb::from_points(&[
+            Vec3::new(1.0, -2.0, 0.0),
+            Vec3::new(-1.0, 3.0, 0.5),
+            Vec3::new(0.0, 0.0, -4.0),
+        ])
+        .unwrap();
+        assert_eq!(
+            aabb,
+            Aabb::new(Vec3::new(-1.0, -2.0, -4.0), Vec3::new(1.0, 3.0, 0.5))
+        );
+        assert_eq!(aabb.longest_axis(), 1);
+        assert_eq!(aabb.centroid(), Vec3::new(0.0, 0.5, -1.75));
+        assert_eq!(unit_box().surface_area(), 24.0);
+        assert_eq!(Aabb::<f64>::from_points(&[]), None);
+    }
+
+    #[test]
+    fn test_hit() {
+        let aabb = unit_box();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(aabb.hit(&ray, 0.0, f64::MAX), Some((4.0, 6.0)));
+        assert_eq!(aabb.hit(&ray, 0.0, 3.0), None);
+        // Starting inside the box the range begins at min_t.
+        let inside = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(aabb.hit(&inside, 0.0, f64::MAX), Some((0.0, 1.0)));
+        let miss = Ray::new(Vec3::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(aabb.hit(&miss, 0.0, f64::MAX), None);
+        let diagonal = Ray::new(Vec3::new(-3.0, -3.0, 0.0), Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(aabb.hit(&diagonal, 0.0, f64::MAX), Some((2.0, 4.0)));
+    }
+
+    #[test]
+    fn test_hit_along_a_face() {
+        // Lying exactly in the plane of a face gives 0 * inf = NaN for that axis.
+        let aabb = unit_box();
+        let ray = Ray::new(Vec3::new(-5.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(aabb.hit(&ray, 0.0, f64::MAX), Some((4.0, 6.0)));
+    }
+}
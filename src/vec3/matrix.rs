@@ -0,0 +1,337 @@
+use std::ops::Mul;
+
+use vec3::{Float, Vec3};
+
+/// A 3x3 matrix stored row by row, acting on column vectors.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Mat3<T> {
+    pub m: [[T; 3]; 3],
+}
+
+impl<T: Float> Mat3<T> {
+    pub fn new(m: [[T; 3]; 3]) -> Mat3<T> {
+        Mat3 { m }
+    }
+
+    #[cfg(test)]
+    pub fn identity() -> Mat3<T> {
+        Mat3::scale(Vec3::from_f64(Vec3::new(1.0, 1.0, 1.0)))
+    }
+
+    pub fn scale(factors: Vec3<T>) -> Mat3<T> {
+        let zero = T::from_f64(0.0);
+        Mat3::new([
+            [factors.x, zero, zero],
+            [zero, factors.y, zero],
+            [zero, zero, factors.z],
+        ])
+    }
+
+    /// The matrix whose columns are `x`, `y` and `z`, i.e. which maps the standard axes onto
+    /// them. With an orthonormal basis this takes local coordinates to world coordinates.
+    pub fn from_columns(x: Vec3<T>, y: Vec3<T>, z: Vec3<T>) -> Mat3<T> {
+        Mat3::new([[x.x, y.x, z.x], [x.y, y.y, z.y], [x.z, y.z, z.z]])
+    }
+
+    /// Rotation by `angle` radians counterclockwise about `axis` (looking down the axis).
+    #[cfg(test)]
+    pub fn rotation(axis: Vec3<T>, angle: T) -> Mat3<T> {
+        let Vec3 { x, y, z } = axis.unit();
+        let (sin, cos) = (angle.sin(), angle.cos());
+        let one_minus_cos = T::from_f64(1.0) - cos;
+        Mat3::new([
+            [
+                cos + x * x * one_minus_cos,
+                x * y * one_minus_cos - z * sin,
+                x * z * one_minus_cos + y * sin,
+            ],
+            [
+                y * x * one_minus_cos + z * sin,
+                cos + y * y * one_minus_cos,
+                y * z * one_minus_cos - x * sin,
+            ],
+            [
+                z * x * one_minus_cos - y * sin,
+                z * y * one_minus_cos + x * sin,
+                cos + z * z * one_minus_cos,
+            ],
+        ])
+    }
+
+    pub fn row(&self, i: usize) -> Vec3<T> {
+        Vec3::new(self.m[i][0], self.m[i][1], self.m[i][2])
+    }
+
+    pub fn column(&self, j: usize) -> Vec3<T> {
+        Vec3::new(self.m[0][j], self.m[1][j], self.m[2][j])
+    }
+
+    pub fn transpose(&self) -> Mat3<T> {
+        Mat3::from_columns(self.row(0), self.row(1), self.row(2))
+    }
+
+    #[cfg(test)]
+    pub fn determinant(&self) -> T {
+        self.row(0).dot(self.row(1).cross(self.row(2)))
+    }
+
+    /// `None` if the matrix is singular.
+    #[cfg(test)]
+    pub fn inverse(&self) -> Option<Mat3<T>> {
+        let determinant = self.determinant();
+        if determinant.abs() <= T::EPSILON * T::EPSILON {
+            return None;
+        }
+        // The rows of the inverse are the cross products of the columns, over the determinant.
+        let (a, b, c) = (self.column(0), self.column(1), self.column(2));
+        let inverse_transpose = Mat3::from_columns(b.cross(c), c.cross(a), a.cross(b));
+        Some(inverse_transpose.transpose() * (T::from_f64(1.0) / determinant))
+    }
+
+    /// The matrix that carries surface normals through this transform so they stay
+    /// perpendicular to the transformed surface: the inverse transpose.
+    #[cfg(test)]
+    pub fn normal_matrix(&self) -> Option<Mat3<T>> {
+        self.inverse().map(|inverse| inverse.transpose())
+    }
+}
+
+impl<T: Float> Mul<Vec3<T>> for Mat3<T> {
+    type Output = Vec3<T>;
+
+    fn mul(self, v: Vec3<T>) -> Vec3<T> {
+        Vec3::new(self.row(0).dot(v), self.row(1).dot(v), self.row(2).dot(v))
+    }
+}
+
+impl<T: Float> Mul for Mat3<T> {
+    type Output = Mat3<T>;
+
+    fn mul(self, other: Mat3<T>) -> Mat3<T> {
+        Mat3::from_columns(
+            self * other.column(0),
+            self * other.column(1),
+            self * other.column(2),
+        )
+    }
+}
+
+impl<T: Float> Mul<T> for Mat3<T> {
+    type Output = Mat3<T>;
+
+    fn mul(self, s: T) -> Mat3<T> {
+        Mat3::from_columns(self.column(0) * s, self.column(1) * s, self.column(2) * s)
+    }
+}
+
+/// A 4x4 affine or projective transform stored row by row, acting on column vectors. Nothing
+/// in the renderer needs one yet, so it is only built for the tests.
+#[cfg(test)]
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Mat4<T> {
+    pub m: [[T; 4]; 4],
+}
+
+#[cfg(test)]
+impl<T: Float> Mat4<T> {
+    pub fn new(m: [[T; 4]; 4]) -> Mat4<T> {
+        Mat4 { m }
+    }
+
+    pub fn identity() -> Mat4<T> {
+        Mat4::from_mat3(Mat3::identity(), Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)))
+    }
+
+    /// The affine transform `linear * p + translation`.
+    pub fn from_mat3(linear: Mat3<T>, translation: Vec3<T>) -> Mat4<T> {
+        let (zero, one) = (T::from_f64(0.0), T::from_f64(1.0));
+        let l = linear.m;
+        Mat4::new([
+            [l[0][0], l[0][1], l[0][2], translation.x],
+            [l[1][0], l[1][1], l[1][2], translation.y],
+            [l[2][0], l[2][1], l[2][2], translation.z],
+            [zero, zero, zero, one],
+        ])
+    }
+
+    pub fn translation(offset: Vec3<T>) -> Mat4<T> {
+        Mat4::from_mat3(Mat3::identity(), offset)
+    }
+
+    pub fn scale(factors: Vec3<T>) -> Mat4<T> {
+        Mat4::from_mat3(
+            Mat3::scale(factors),
+            Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)),
+        )
+    }
+
+    pub fn rotation(axis: Vec3<T>, angle: T) -> Mat4<T> {
+        Mat4::from_mat3(
+            Mat3::rotation(axis, angle),
+            Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)),
+        )
+    }
+
+    /// The upper left 3x3 block, i.e. the transform without its translation.
+    pub fn linear(&self) -> Mat3<T> {
+        let m = self.m;
+        Mat3::new([
+            [m[0][0], m[0][1], m[0][2]],
+            [m[1][0], m[1][1], m[1][2]],
+            [m[2][0], m[2][1], m[2][2]],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4<T> {
+        let mut m = self.m;
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4::new(m)
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting. `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat4<T>> {
+        let mut a = self.m;
+        let mut inverse = Mat4::identity().m;
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().partial_cmp(&a[j][column].abs()).unwrap())
+                .unwrap();
+            if a[pivot][column].abs() <= T::EPSILON * T::EPSILON {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+            let scale = T::from_f64(1.0) / a[column][column];
+            for j in 0..4 {
+                a[column][j] = a[column][j] * scale;
+                inverse[column][j] = inverse[column][j] * scale;
+            }
+            for row in 0..4 {
+                if row != column {
+                    let factor = a[row][column];
+                    for j in 0..4 {
+                        a[row][j] = a[row][j] - factor * a[column][j];
+                        inverse[row][j] = inverse[row][j] - factor * inverse[column][j];
+                    }
+                }
+            }
+        }
+        Some(Mat4::new(inverse))
+    }
+
+    /// Transforms a position, dividing through by w for projective transforms.
+    pub fn transform_point(&self, p: Vec3<T>) -> Vec3<T> {
+        let m = self.m;
+        let transformed = self.linear() * p + Vec3::new(m[0][3], m[1][3], m[2][3]);
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        transformed / w
+    }
+
+    /// Transforms a direction, which is unaffected by translation.
+    pub fn transform_vector(&self, v: Vec3<T>) -> Vec3<T> {
+        self.linear() * v
+    }
+
+    /// Transforms a surface normal with the inverse transpose, so it stays perpendicular to the
+    /// surface under non-uniform scaling. The result is not normalised.
+    pub fn transform_normal(&self, n: Vec3<T>) -> Option<Vec3<T>> {
+        self.linear().normal_matrix().map(|matrix| matrix * n)
+    }
+}
+
+#[cfg(test)]
+impl<T: Float> Mul for Mat4<T> {
+    type Output = Mat4<T>;
+
+    fn mul(self, other: Mat4<T>) -> Mat4<T> {
+        let mut m = self.m;
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).fold(T::from_f64(0.0), |sum, k| {
+                    sum + self.m[i][k] * other.m[k][j]
+                });
+            }
+        }
+        Mat4::new(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn assert_close(a: Vec3<f64>, b: Vec3<f64>) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    fn assert_mat4_close(a: Mat4<f64>, b: Mat4<f64>) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < 1e-9, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mat3_rotation() {
+        let quarter_turn = Mat3::rotation(Vec3::new(0.0, 0.0, 1.0), PI / 2.0);
+        assert_close(
+            quarter_turn * Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        assert!((quarter_turn.determinant() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mat3_inverse() {
+        let m = Mat3::new([[2.0, 0.0, 1.0], [1.0, 3.0, 0.0], [0.0, 1.0, 4.0]]);
+        let v = Vec3::new(1.0, -2.0, 0.5);
+        assert_close(m.inverse().unwrap() * (m * v), v);
+        assert_eq!(m.transpose().transpose(), m);
+        assert_eq!(Mat3::scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+    }
+
+    #[test]
+    fn test_mat4_inverse() {
+        let m = Mat4::translation(Vec3::new(1.0, 2.0, 3.0))
+            * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 0.7)
+            * Mat4::scale(Vec3::new(2.0, 0.5, 3.0));
+        assert_mat4_close(m * m.inverse().unwrap(), Mat4::identity());
+        let p = Vec3::new(-1.0, 0.5, 2.0);
+        assert_close(
+            m.inverse().unwrap().transform_point(m.transform_point(p)),
+            p,
+        );
+        assert_eq!(Mat4::scale(Vec3::new(0.0, 1.0, 1.0)).inverse(), None);
+        let rotation = Mat4::rotation(Vec3::new(0.0, 1.0, 1.0), 0.3);
+        assert_mat4_close(rotation.transpose(), rotation.inverse().unwrap());
+    }
+
+    #[test]
+    fn test_mat4_transforms() {
+        let m = Mat4::translation(Vec3::new(1.0, 2.0, 3.0));
+        assert_close(
+            m.transform_point(Vec3::new(1.0, 1.0, 1.0)),
+            Vec3::new(2.0, 3.0, 4.0),
+        );
+        assert_close(
+            m.transform_vector(Vec3::new(1.0, 1.0, 1.0)),
+            Vec3::new(1.0, 1.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn test_transform_normal_stays_perpendicular() {
+        // Squash a 45 degree plane; its normal must tilt the other way to stay perpendicular.
+        let m = Mat4::scale(Vec3::new(1.0, 0.5, 1.0));
+        let tangent = m.transform_vector(Vec3::new(1.0, 1.0, 0.0));
+        let normal = m.transform_normal(Vec3::new(1.0, -1.0, 0.0)).unwrap();
+        assert!(tangent.dot(normal).abs() < 1e-12);
+        assert_close(normal, Vec3::new(1.0, -2.0, 0.0));
+    }
+}
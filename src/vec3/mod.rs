@@ -0,0 +1,669 @@
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+pub mod aabb;
+pub mod matrix;
+pub mod quaternion;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Vec3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+pub trait Number<Rhs = Self, Output = Self>:
+    Copy
+    + Add<Rhs, Output = Output>
+    + Sub<Rhs, Output = Output>
+    + Mul<Rhs, Output = Output>
+    + Div<Rhs, Output = Output>
+    + Neg<Output = Output>
+{
+}
+
+impl<T, Rhs, Output> Number<Rhs, Output> for T where
+    T: Copy
+        + Add<Rhs, Output = Output>
+        + Sub<Rhs, Output = Output>
+        + Mul<Rhs, Output = Output>
+        + Div<Rhs, Output = Output>
+        + Neg<Output = Output>
+{
+}
+
+pub trait NumWithVectorOps<T = Self>:
+    Number<T, T> + Div<Vec3<T>, Output = Vec3<T>> + Mul<Vec3<T>, Output = Vec3<T>>
+{
+}
+
+impl<T> NumWithVectorOps for T where
+    T: Number + Div<Vec3<T>, Output = Vec3<T>> + Mul<Vec3<T>, Output = Vec3<T>>
+{
+}
+
+pub trait VectorWithOps<T>:
+    Add<Vec3<T>, Output = Vec3<T>>
+    + Sub<Vec3<T>, Output = Vec3<T>>
+    + Div<Vec3<T>, Output = Vec3<T>>
+    + Mul<Vec3<T>, Output = Vec3<T>>
+    + Div<T, Output = Vec3<T>>
+    + Mul<T, Output = Vec3<T>>
+{
+}
+
+impl<T> VectorWithOps<T> for Vec3<T>
+where
+    T: NumWithVectorOps<T>,
+    Vec3<T>: Add<Vec3<T>, Output = Vec3<T>>
+        + Sub<Vec3<T>, Output = Vec3<T>>
+        + Div<Vec3<T>, Output = Vec3<T>>
+        + Mul<Vec3<T>, Output = Vec3<T>>
+        + Div<T, Output = Vec3<T>>
+        + Mul<T, Output = Vec3<T>>,
+{
+}
+
+/// The floating point types the renderer can work in, so a scene can be traced in `f32` for
+/// speed and memory or in `f64` for precision.
+pub trait Float:
+    NumWithVectorOps + PartialOrd + Default + fmt::Debug + fmt::Display + Send + Sync + 'static
+{
+    const PI: Self;
+    const MAX: Self;
+    const EPSILON: Self;
+
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn powi(self, n: i32) -> Self;
+    fn powf(self, n: Self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn acos(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+    fn floor(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    /// The next representable value towards positive infinity.
+    fn next_up(self) -> Self;
+    /// The next representable value towards negative infinity.
+    fn next_down(self) -> Self;
+}
+
+macro_rules! float_impl {
+    ($($t:ident)*) => ($(
+        impl Float for $t {
+            const PI: $t = std::$t::consts::PI;
+            const MAX: $t = $t::MAX;
+            const EPSILON: $t = $t::EPSILON;
+
+            #[inline]
+            fn from_f64(value: f64) -> $t {
+                value as $t
+            }
+
+            #[inline]
+            fn to_f64(self) -> f64 {
+                f64::from(self)
+            }
+
+            #[inline]
+            fn sqrt(self) -> $t {
+                $t::sqrt(self)
+            }
+
+            #[inline]
+            fn abs(self) -> $t {
+                $t::abs(self)
+            }
+
+            #[inline]
+            fn powi(self, n: i32) -> $t {
+                $t::powi(self, n)
+            }
+
+            #[inline]
+            fn powf(self, n: $t) -> $t {
+                $t::powf(self, n)
+            }
+
+            #[inline]
+            fn exp(self) -> $t {
+                $t::exp(self)
+            }
+
+            #[inline]
+            fn ln(self) -> $t {
+                $t::ln(self)
+            }
+
+            #[inline]
+            fn sin(self) -> $t {
+                $t::sin(self)
+            }
+
+            #[inline]
+            fn cos(self) -> $t {
+                $t::cos(self)
+            }
+
+            #[inline]
+            fn tan(self) -> $t {
+                $t::tan(self)
+            }
+
+            #[inline]
+            fn acos(self) -> $t {
+                $t::acos(self)
+            }
+
+            #[inline]
+            fn atan2(self, other: $t) -> $t {
+                $t::atan2(self, other)
+            }
+
+            #[inline]
+            fn floor(self) -> $t {
+                $t::floor(self)
+            }
+
+            #[inline]
+            fn min(self, other: $t) -> $t {
+                $t::min(self, other)
+            }
+
+            #[inline]
+            fn max(self, other: $t) -> $t {
+                $t::max(self, other)
+            }
+
+            fn next_up(self) -> $t {
+                if self.is_nan() || self == $t::INFINITY {
+                    return self;
+                }
+                // Treat -0 as +0 so both step up to the smallest positive value.
+                let value = if self == 0.0 { 0.0 } else { self };
+                let bits = value.to_bits();
+                $t::from_bits(if value >= 0.0 { bits + 1 } else { bits - 1 })
+            }
+
+            fn next_down(self) -> $t {
+                -(-self).next_up()
+            }
+        }
+    )*)
+}
+
+float_impl! { f32 f64 }
+
+impl<T> Vec3<T>
+where
+    T: NumWithVectorOps,
+    Vec3<T>: VectorWithOps<T>,
+{
+    pub fn new(x: T, y: T, z: T) -> Vec3<T> {
+        Vec3 { x, y, z }
+    }
+
+    #[inline]
+    pub fn x(self) -> T {
+        self.x
+    }
+
+    #[inline]
+    pub fn y(self) -> T {
+        self.y
+    }
+
+    #[inline]
+    pub fn z(self) -> T {
+        self.z
+    }
+
+    #[inline]
+    pub fn r(self) -> T {
+        self.x
+    }
+
+    #[inline]
+    pub fn g(self) -> T {
+        self.y
+    }
+
+    #[inline]
+    pub fn b(self) -> T {
+        self.z
+    }
+
+    #[inline]
+    pub fn map<B, F>(self, f: F) -> Vec3<B>
+    where
+        F: Fn(T) -> B,
+        B: NumWithVectorOps,
+        Vec3<B>: VectorWithOps<B>,
+    {
+        Vec3::new(f(self.x), f(self.y), f(self.z))
+    }
+
+    #[inline]
+    pub fn dot(self, other: Vec3<T>) -> T {
+        (self.x * other.x) + (self.y * other.y) + (self.z * other.z)
+    }
+
+    #[inline]
+    pub fn cross(self, other: Vec3<T>) -> Vec3<T> {
+        Self {
+            x: (self.y * other.z) - (self.z * other.y),
+            y: -((self.x * other.z) - (self.z * other.x)),
+            z: (self.x * other.y) - (self.y * other.x),
+        }
+    }
+
+    #[inline]
+    pub fn squared_length(self) -> T {
+        (self.x * self.x) + (self.y * self.y) + (self.z * self.z)
+    }
+}
+
+impl<T: Float> Vec3<T> {
+    #[inline]
+    pub fn unit(self) -> Vec3<T> {
+        self / self.length()
+    }
+
+    #[inline]
+    pub fn length(self) -> T {
+        self.squared_length().sqrt()
+    }
+
+    /// Widens (or narrows) every component to `f64`, e.g. for display.
+    #[inline]
+    pub fn to_f64(self) -> Vec3<f64> {
+        Vec3::new(self.x.to_f64(), self.y.to_f64(), self.z.to_f64())
+    }
+
+    /// Two unit vectors that together with this one (which must be a unit vector, e.g. a
+    /// normal) form a right handed orthonormal basis. Uses the branchless construction of Duff
+    /// et al., "Building an Orthonormal Basis, Revisited" (2017).
+    pub fn orthonormal_basis(self) -> (Vec3<T>, Vec3<T>) {
+        let one = T::from_f64(1.0);
+        let sign = if self.z < T::from_f64(0.0) { -one } else { one };
+        let a = -one / (sign + self.z);
+        let b = self.x * self.y * a;
+        (
+            Vec3::new(one + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Vec3::new(b, sign + self.y * self.y * a, -self.y),
+        )
+    }
+
+    #[inline]
+    pub fn from_f64(other: Vec3<f64>) -> Vec3<T> {
+        Vec3::new(
+            T::from_f64(other.x),
+            T::from_f64(other.y),
+            T::from_f64(other.z),
+        )
+    }
+}
+
+impl<T: Add<Output = T>> Add for Vec3<T> {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, other: Self) -> Self::Output {
+        Self {
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+}
+
+impl<T: Sub<Output = T>> Sub for Vec3<T> {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, other: Self) -> Self::Output {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
+            z: self.z - other.z,
+        }
+    }
+}
+
+impl<T: Mul<Output = T>> Mul for Vec3<T> {
+    type Output = Vec3<T>;
+
+    #[inline]
+    fn mul(self, other: Vec3<T>) -> Self::Output {
+        Self {
+            x: self.x * other.x,
+            y: self.y * other.y,
+            z: self.z * other.z,
+        }
+    }
+}
+
+impl<T: Float> Mul<T> for Vec3<T> {
+    type Output = Vec3<T>;
+
+    #[inline]
+    fn mul(self, other: T) -> Self::Output {
+        Self {
+            x: self.x * other,
+            y: self.y * other,
+            z: self.z * other,
+        }
+    }
+}
+
+macro_rules! mul_impl {
+    ($($t:ty)*) => ($(
+        impl Mul<$t> for Vec3<$t> {
+            type Output = Vec3<$t>;
+
+            #[inline]
+            fn mul(self, other: $t) -> Self::Output {
+                Self {
+                    x: self.x * other,
+                    y: self.y * other,
+                    z: self.z * other,
+                }
+            }
+        }
+    )*)
+}
+
+mul_impl! { usize u8 u16 u32 u64 u128 isize i8 i16 i32 i64 i128 }
+
+macro_rules! scalar_mul_impl {
+    ($($t:ty)*) => ($(
+        impl Mul<Vec3<$t>> for $t {
+            type Output = Vec3<$t>;
+
+            #[inline]
+            fn mul(self, other: Vec3<$t>) -> Self::Output {
+                other * self
+            }
+        }
+
+    )*)
+}
+
+scalar_mul_impl! { usize u8 u16 u32 u64 u128 isize i8 i16 i32 i64 i128 f32 f64 }
+
+impl<T: Div<Output = T>> Div for Vec3<T> {
+    type Output = Vec3<T>;
+
+    #[inline]
+    fn div(self, other: Vec3<T>) -> Self::Output {
+        Self {
+            x: self.x / other.x,
+            y: self.y / other.y,
+            z: self.z / other.z,
+        }
+    }
+}
+
+impl<T: Float> Div<T> for Vec3<T> {
+    type Output = Vec3<T>;
+
+    #[inline]
+    fn div(self, other: T) -> Self::Output {
+        Self {
+            x: self.x / other,
+            y: self.y / other,
+            z: self.z / other,
+        }
+    }
+}
+
+macro_rules! div_impl {
+    ($($t:ty)*) => ($(
+        impl Div<$t> for Vec3<$t> {
+            type Output = Vec3<$t>;
+
+            #[inline]
+            fn div(self, other: $t) -> Self::Output {
+                Self {
+                    x: self.x / other,
+                    y: self.y / other,
+                    z: self.z / other,
+                }
+            }
+        }
+    )*)
+}
+
+div_impl! { usize u8 u16 u32 u64 u128 isize i8 i16 i32 i64 i128 }
+
+macro_rules! scalar_div_impl {
+    ($($t:ty)*) => ($(
+        impl Div<Vec3<$t>> for $t {
+            type Output = Vec3<$t>;
+
+            #[inline]
+            fn div(self, other: Vec3<$t>) -> Self::Output {
+                Vec3 {
+                    x: self / other.x,
+                    y: self / other.y,
+                    z: self / other.z,
+                }
+            }
+        }
+
+    )*)
+}
+
+scalar_div_impl! { usize u8 u16 u32 u64 u128 isize i8 i16 i32 i64 i128 f32 f64 }
+
+impl<T: Neg<Output = T>> Neg for Vec3<T> {
+    type Output = Vec3<T>;
+    fn neg(self) -> Vec3<T> {
+        Vec3 {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Vec3<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(")?;
+        self.x.fmt(f)?;
+        write!(f, ", ")?;
+        self.y.fmt(f)?;
+        write!(f, ", ")?;
+        self.z.fmt(f)?;
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map() {
+        assert_eq!(
+            Vec3::new(1, 0, -1).map(f64::from),
+            Vec3::new(1f64, 0f64, -1f64)
+        );
+    }
+
+    #[test]
+    fn test_add() {
+        assert_eq!(
+            Vec3::new(1f64, 0f64, -1f64) + Vec3::new(2f64, 3f64, 2f64),
+            Vec3::new(3f64, 3f64, 1f64)
+        );
+
+        assert_eq!(Vec3::new(1, 0, -1) + Vec3::new(2, 3, 2), Vec3::new(3, 3, 1));
+    }
+
+    #[test]
+    fn test_sub() {
+        assert_eq!(
+            Vec3::new(1, 0, -1) - Vec3::new(2, 3, 2),
+            Vec3::new(-1, -3, -3)
+        );
+    }
+
+    #[test]
+    fn test_mul() {
+        assert_eq!(Vec3::new(1, 0, -1) * 2, Vec3::new(2, 0, -2));
+        assert_eq!(2 * Vec3::new(1, 0, -1), Vec3::new(2, 0, -2));
+
+        assert_eq!(
+            Vec3::new(1f64, 0f64, -1f64) * 2f64,
+            Vec3::new(2f64, 0f64, -2f64)
+        );
+        assert_eq!(
+            2f64 * Vec3::new(1f64, 0f64, -1f64),
+            Vec3::new(2f64, 0f64, -2f64)
+        );
+
+        assert_eq!(
+            Vec3::new(1, 0, -1) * Vec3::new(4, 0, -2),
+            Vec3::new(4, 0, 2)
+        );
+    }
+
+    #[test]
+    fn test_div() {
+        assert_eq!(Vec3::new(2, 4, -6) / 2, Vec3::new(1, 2, -3));
+        assert_eq!(6 / Vec3::new(1, 2, -3), Vec3::new(6, 3, -2));
+
+        assert_eq!(
+            Vec3::new(2f64, 4f64, -6f64) / 2f64,
+            Vec3::new(1f64, 2f64, -3f64)
+        );
+        assert_eq!(
+            6f64 / Vec3::new(1f64, 2f64, -3f64),
+            Vec3::new(6f64, 3f64, -2f64)
+        );
+
+        assert_eq!(
+            Vec3::new(2, 4, -6) / Vec3::new(2, 2, -3),
+            Vec3::new(1, 2, 2)
+        );
+    }
+
+    #[test]
+    fn test_neg() {
+        assert_eq!(-Vec3::new(2, 0, -6), Vec3::new(-2, 0, 6));
+
+        assert_eq!(-Vec3::new(2f64, 0f64, -6f64), Vec3::new(-2f64, 0f64, 6f64));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(format!("{}", Vec3::new(1, 0, -1)), "(1, 0, -1)");
+        assert_eq!(
+            format!("{:.2}", Vec3::new(0.5f64, 1f64, -0.125f64)),
+            "(0.50, 1.00, -0.12)"
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        assert_eq!(Vec3::new(1, 2, 3).dot(Vec3::new(4, -5, 6)), 12);
+    }
+
+    #[test]
+    fn test_cross() {
+        assert_eq!(
+            Vec3::new(3, -3, 1).cross(Vec3::new(4, 9, 2)),
+            Vec3::new(-15, -2, 39)
+        );
+    }
+
+    #[test]
+    fn test_length() {
+        assert_eq!(Vec3::new(2f64, -2f64, 1f64).length(), 3f64);
+        assert_eq!(Vec3::new(2f32, -2f32, 1f32).length(), 3f32);
+    }
+
+    #[test]
+    fn test_unit() {
+        assert_eq!(
+            Vec3::new(2f64, 0f64, 0f64).unit(),
+            Vec3::new(1f64, 0f64, 0f64)
+        );
+        assert_eq!(
+            Vec3::new(-2f64, 0f64, 0f64).unit(),
+            Vec3::new(-1f64, 0f64, 0f64)
+        );
+        assert_eq!(
+            Vec3::new(0f64, 2f64, 0f64).unit(),
+            Vec3::new(0f64, 1f64, 0f64)
+        );
+        assert_eq!(
+            Vec3::new(0f64, -2f64, 0f64).unit(),
+            Vec3::new(0f64, -1f64, 0f64)
+        );
+        assert_eq!(
+            Vec3::new(0f64, 0f64, 2f64).unit(),
+            Vec3::new(0f64, 0f64, 1f64)
+        );
+        assert_eq!(
+            Vec3::new(0f64, 0f64, -2f64).unit(),
+            Vec3::new(0f64, 0f64, -1f64)
+        );
+        assert_eq!(
+            Vec3::new(3f64, 4f64, 0f64).unit(),
+            Vec3::new(0.6f64, 0.8f64, 0f64)
+        );
+        assert_eq!(
+            Vec3::new(3f32, 4f32, 0f32).unit(),
+            Vec3::new(0.6f32, 0.8f32, 0f32)
+        );
+    }
+
+    #[test]
+    fn test_orthonormal_basis() {
+        for normal in &[
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(1.0, 2.0, -3.0).unit(),
+            Vec3::new(-0.2, 0.1, 0.9).unit(),
+        ] {
+            let (tangent, bitangent) = normal.orthonormal_basis();
+            assert!((tangent.length() - 1.0).abs() < 1e-12);
+            assert!((bitangent.length() - 1.0).abs() < 1e-12);
+            assert!(tangent.dot(*normal).abs() < 1e-12);
+            assert!(bitangent.dot(*normal).abs() < 1e-12);
+            assert!(tangent.dot(bitangent).abs() < 1e-12);
+            assert!((tangent.cross(bitangent) - *normal).length() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_next_up_and_down() {
+        assert!(1.0.next_up() > 1.0);
+        assert_eq!(1.0.next_up() - 1.0, f64::EPSILON);
+        assert!(0.0.next_up() > 0.0);
+        assert!((-0.0_f64).next_down() < 0.0);
+        assert_eq!((-1.0_f32).next_up().next_down(), -1.0);
+        assert_eq!(f64::INFINITY.next_up(), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_to_f64() {
+        assert_eq!(
+            Vec3::new(0.5f32, 1f32, -2f32).to_f64(),
+            Vec3::new(0.5f64, 1f64, -2f64)
+        );
+        assert_eq!(
+            Vec3::<f32>::from_f64(Vec3::new(0.5, 1.0, -2.0)),
+            Vec3::new(0.5f32, 1f32, -2f32)
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::ops::Mul;
+
+use vec3::matrix::Mat3;
+use vec3::{Float, Vec3};
+
+/// A rotation as a unit quaternion `w + xi + yj + zk`, with the imaginary part held in `v`.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Quaternion<T> {
+    pub w: T,
+    pub v: Vec3<T>,
+}
+
+impl<T: Float> Quaternion<T> {
+    pub fn new(w: T, v: Vec3<T>) -> Quaternion<T> {
+        Quaternion { w, v }
+    }
+
+    pub fn identity() -> Quaternion<T> {
+        Quaternion::new(T::from_f64(1.0), Vec3::from_f64(Vec3::new(0.0, 0.0, 0.0)))
+    }
+
+    /// Rotation by `angle` radians counterclockwise about `axis`, like `Mat3::rotation`.
+    pub fn from_axis_angle(axis: Vec3<T>, angle: T) -> Quaternion<T> {
+        let half = angle / T::from_f64(2.0);
+        Quaternion::new(half.cos(), axis.unit() * half.sin())
+    }
+
+    pub fn dot(self, other: Quaternion<T>) -> T {
+        self.w * other.w + self.v.dot(other.v)
+    }
+
+    pub fn length(self) -> T {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Quaternion<T> {
+        let length = self.length();
+        Quaternion::new(self.w / length, self.v / length)
+    }
+
+    pub fn conjugate(self) -> Quaternion<T> {
+        Quaternion::new(self.w, -self.v)
+    }
+
+    /// Rotates `p`, i.e. computes `q p q*` for this (unit) quaternion.
+    pub fn rotate(self, p: Vec3<T>) -> Vec3<T> {
+        let two = T::from_f64(2.0);
+        let t = self.v.cross(p) * two;
+        p + t * self.w + self.v.cross(t)
+    }
+
+    pub fn to_mat3(self) -> Mat3<T> {
+        Mat3::from_columns(
+            self.rotate(Vec3::from_f64(Vec3::new(1.0, 0.0, 0.0))),
+            self.rotate(Vec3::from_f64(Vec3::new(0.0, 1.0, 0.0))),
+            self.rotate(Vec3::from_f64(Vec3::new(0.0, 0.0, 1.0))),
+        )
+    }
+
+    /// Spherical linear interpolation: rotates at constant angular speed from `self` at `t = 0`
+    /// to `other` at `t = 1`, taking the shorter way round.
+    pub fn slerp(self, other: Quaternion<T>, t: T) -> Quaternion<T> {
+        let one = T::from_f64(1.0);
+        let mut cos_theta = self.dot(other);
+        // q and -q are the same rotation; pick the one on our side of the hypersphere.
+        let other = if cos_theta < T::from_f64(0.0) {
+            cos_theta = -cos_theta;
+            Quaternion::new(-other.w, -other.v)
+        } else {
+            other
+        };
+        let (a, b) = if cos_theta > one - T::from_f64(1e-6) {
+            // Nearly identical: the sines below vanish, so interpolate linearly instead.
+            (one - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((one - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+        Quaternion::new(a * self.w + b * other.w, self.v * a + other.v * b).normalize()
+    }
+}
+
+/// The Hamilton product: `a * b` rotates by `b` and then by `a`.
+impl<T: Float> Mul for Quaternion<T> {
+    type Output = Quaternion<T>;
+
+    fn mul(self, other: Quaternion<T>) -> Quaternion<T> {
+        Quaternion::new(
+            self.w * other.w - self.v.dot(other.v),
+            other.v * self.w + self.v * other.w + self.v.cross(other.v),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn assert_close(a: Vec3<f64>, b: Vec3<f64>) {
+        assert!((a - b).length() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_rotate_matches_matrix() {
+        let axis = Vec3::new(1.0, 2.0, -0.5);
+        let q = Quaternion::from_axis_angle(axis, 1.1);
+        let p = Vec3::new(0.3, -1.0, 2.0);
+        assert_close(q.rotate(p), Mat3::rotation(axis, 1.1) * p);
+        assert_close(q.to_mat3() * p, q.rotate(p));
+        assert_close(q.conjugate().rotate(q.rotate(p)), p);
+    }
+
+    #[test]
+    fn test_product_composes_rotations() {
+        let a = Quaternion::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), PI / 2.0);
+        let b = Quaternion::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), PI / 2.0);
+        let p = Vec3::new(0.0, 1.0, 0.0);
+        assert_close((a * b).rotate(p), a.rotate(b.rotate(p)));
+        assert_close((a * b).rotate(p), Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_slerp() {
+        let z = Vec3::new(0.0, 0.0, 1.0);
+        let start = Quaternion::identity();
+        let end = Quaternion::from_axis_angle(z, PI / 2.0);
+        let halfway = start.slerp(end, 0.5);
+        assert_close(
+            halfway.rotate(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.5_f64.sqrt(), 0.5_f64.sqrt(), 0.0),
+        );
+        assert_eq!(start.slerp(end, 0.0), start);
+        assert_close(start.slerp(end, 1.0).rotate(z), end.rotate(z));
+        // The negated end quaternion is the same rotation, and must give the same path.
+        let negated = Quaternion::new(-end.w, -end.v);
+        assert_close(
+            start.slerp(negated, 0.5).rotate(Vec3::new(1.0, 0.0, 0.0)),
+            halfway.rotate(Vec3::new(1.0, 0.0, 0.0)),
+        );
+    }
+}